@@ -24,7 +24,7 @@ arch=('x86_64')
 url="{url}"
 license=('MIT')
 
-depends=('acpid' 'pciutils' 'usbutils' 'yad')
+depends=('pciutils' 'usbutils' 'yad')
 optdepends=(
 'brightnessctl: needed for brightness settings' 
 'net-tools: needed to disable ethernet cards' 'net-tools: needed to disable ethernet cards'
@@ -69,13 +69,11 @@ def create_install_file():
     return f"""post_install() {{
   power-daemon-mgr setup
   systemctl daemon-reload
-  systemctl restart acpid.service
   systemctl enable --now power-options.service
 }}
 
 post_upgrade() {{
   systemctl daemon-reload
-  systemctl restart acpid.service
   systemctl restart power-options.service
 }}
 
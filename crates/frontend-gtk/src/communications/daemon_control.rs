@@ -1,13 +1,18 @@
+use futures_util::Stream;
 use lazy_static::lazy_static;
 use log::debug;
+use log::error;
 use log::trace;
 use power_daemon::DefaultProfileType;
-use power_daemon::{communication::client::ControlClient, Config, Profile, ReducedUpdate};
+use power_daemon::{
+    communication::{client::ControlClient, error::ControlDBusError},
+    Config, Profile, ReducedUpdate,
+};
 use tokio::sync::MappedMutexGuard;
 use tokio::sync::Mutex;
 use tokio::sync::MutexGuard;
 
-use super::{CONFIG, PROFILES_INFO, PROFILE_OVERRIDE};
+use super::{CONFIG, DAEMON_ERROR, PROFILES_INFO, PROFILE_OVERRIDE};
 
 lazy_static! {
     static ref CLIENT: Mutex<Option<ControlClient>> = None.into();
@@ -20,103 +25,163 @@ pub async fn setup_control_client() {
 
 pub async fn get_config() {
     debug!("Obtaining config");
-    CONFIG
-        .set(get_client().await.get_config().await.unwrap())
-        .await
+    match get_client().await.get_config().await {
+        Ok(config) => CONFIG.set(config).await,
+        Err(error) => report_error("obtain the config", error).await,
+    }
 }
 pub async fn get_profiles_info() {
     debug!("Obtaining profiles info");
-    PROFILES_INFO
-        .set(get_client().await.get_profiles_info().await.unwrap())
-        .await
+    match get_client().await.get_profiles_info().await {
+        Ok(profiles_info) => PROFILES_INFO.set(profiles_info).await,
+        Err(error) => report_error("obtain the profiles", error).await,
+    }
 }
 pub async fn update_config(config: Config) {
     debug!("Updating config");
-    get_client().await.update_config(config).await.unwrap();
+    if let Err(error) = get_client().await.update_config(config).await {
+        report_error("update the config", error).await;
+    }
 }
 pub async fn get_active_profile_name() -> String {
     debug!("Obtaining the name of the active profile");
     get_client().await.get_active_profile_name().await.unwrap()
 }
-pub async fn create_profile(profile_type: DefaultProfileType) {
-    debug!("Creating profile of type {profile_type:?}");
+pub async fn receive_active_profile_changed() -> impl Stream<Item = String> {
+    debug!("Subscribing to active profile changes");
     get_client()
         .await
-        .create_profile(profile_type)
+        .receive_active_profile_changed()
         .await
-        .unwrap();
+        .unwrap()
+}
+pub async fn create_profile(profile_type: DefaultProfileType) {
+    debug!("Creating profile of type {profile_type:?}");
+    if let Err(error) = get_client().await.create_profile(profile_type).await {
+        report_error("create the profile", error).await;
+    }
 }
 pub async fn reset_profile(idx: u32) {
     debug!("Resetting profile {idx}");
-    get_client().await.reset_profile(idx).await.unwrap();
+    if let Err(error) = get_client()
+        .await
+        .reset_profile_by_id(profile_id(idx).await)
+        .await
+    {
+        report_error("reset the profile", error).await;
+    }
 }
 pub async fn remove_profile(idx: u32) {
     debug!("Removing profile {idx}");
-    get_client().await.remove_profile(idx).await.unwrap();
+    if let Err(error) = get_client()
+        .await
+        .remove_profile_by_id(profile_id(idx).await)
+        .await
+    {
+        report_error("remove the profile", error).await;
+    }
 }
 pub async fn swap_profiles(idx: u32, new_idx: u32) {
     debug!("Swapping profile {idx} with {new_idx}");
-    get_client()
+    if let Err(error) = get_client()
         .await
-        .swap_profiles(idx, new_idx)
+        .swap_profiles_by_id(profile_id(idx).await, profile_id(new_idx).await)
         .await
-        .unwrap();
+    {
+        report_error("move the profile", error).await;
+    }
 }
 pub async fn update_profile_name(idx: u32, new_name: String) {
     debug!("Updating profile {idx} name to {new_name}");
-    get_client()
+    if let Err(error) = get_client()
         .await
-        .update_profile_name(idx, new_name)
+        .update_profile_name_by_id(profile_id(idx).await, new_name)
         .await
-        .unwrap();
+    {
+        report_error("rename the profile", error).await;
+    }
 }
 pub async fn update_profile_full(idx: u32, updated: Profile) {
     debug!("Updating profile {idx} fully");
     trace!("Updated profile: {updated:#?}");
 
-    get_client()
-        .await
-        .update_profile_full(idx, updated)
-        .await
-        .unwrap();
+    // The daemon rejects profiles that cannot be applied on this system
+    if let Err(error) = get_client().await.update_profile_full_by_id(updated).await {
+        report_error("update the profile", error).await;
+    }
 }
 pub async fn update_profile_reduced(idx: u32, updated: Profile, reduced_update: ReducedUpdate) {
     debug!("Updating profile {idx} reduced: {reduced_update:?}");
     trace!("Updated profile: {updated:#?}");
 
-    get_client()
+    if let Err(error) = get_client()
         .await
-        .update_profile_reduced(idx, updated, reduced_update)
+        .update_profile_reduced_by_id(updated, reduced_update)
         .await
-        .unwrap();
+    {
+        report_error("update the profile", error).await;
+    }
 }
 
 pub async fn update_full() {
     debug!("Updating fully");
-    get_client().await.update_full().await.unwrap();
+    if let Err(error) = get_client().await.update_full().await {
+        report_error("apply the profile", error).await;
+    }
 }
 pub async fn update(reduced_update: ReducedUpdate) {
     debug!("Updating reduced: {reduced_update:?}");
-    get_client().await.update_full().await.unwrap();
+    if let Err(error) = get_client().await.update_full().await {
+        report_error("apply the profile", error).await;
+    }
 }
 
 pub async fn get_profile_override() {
     debug!("Obtaining profile override");
-    PROFILE_OVERRIDE
-        .set(get_client().await.get_profile_override().await.unwrap())
-        .await;
+    match get_client().await.get_profile_override().await {
+        Ok(profile_override) => {
+            PROFILE_OVERRIDE
+                .set(profile_override.map(|profile_override| profile_override.profile_name))
+                .await
+        }
+        Err(error) => report_error("obtain the profile override", error).await,
+    }
 }
 pub async fn set_profile_override(profile_name: String) {
     debug!("Setting profile override");
-    get_client()
-        .await
-        .set_profile_override(profile_name)
-        .await
-        .unwrap();
+    if let Err(error) = get_client().await.set_profile_override(profile_name).await {
+        report_error("override the profile", error).await;
+    }
 }
 pub async fn remove_profile_override() {
     debug!("Removing profile override profile override");
-    get_client().await.remove_profile_override().await.unwrap();
+    if let Err(error) = get_client().await.remove_profile_override().await {
+        report_error("remove the profile override", error).await;
+    }
+}
+
+/// ID of the profile at the index of the synced profiles info, so that the
+/// daemon changes the profile the user sees even if another client reordered
+/// them in the meantime
+async fn profile_id(idx: u32) -> String {
+    PROFILES_INFO
+        .get()
+        .await
+        .as_ref()
+        .expect("Profiles info should be synced before profiles are changed")
+        .profiles[idx as usize]
+        .profile_id
+        .clone()
+}
+
+/// Logs the error and passes it on to be shown to the user
+async fn report_error(action: &str, error: ControlDBusError) {
+    error!("Could not {action}: {error}");
+    // Cleared first so that the same error is reported again
+    DAEMON_ERROR.set_mut(|value| *value = None).await;
+    DAEMON_ERROR
+        .set(format!("Could not {action}: {}", error.message()))
+        .await;
 }
 
 async fn get_client() -> MappedMutexGuard<'static, ControlClient> {
@@ -12,4 +12,6 @@ lazy_static! {
     pub static ref PROFILES_INFO: SyncedValue<ProfilesInfo> = SyncedValue::new();
     pub static ref PROFILE_OVERRIDE: SyncedValue<Option<String>> = SyncedValue::new();
     pub static ref SYSTEM_INFO: SyncedValue<SystemInfo> = SyncedValue::new();
+    /// The last error the daemon replied with
+    pub static ref DAEMON_ERROR: SyncedValue<String> = SyncedValue::new();
 }
@@ -11,9 +11,11 @@ use relm4::prelude::*;
 use relm4::Controller;
 
 use enumflags2::BitFlags;
+use futures_util::StreamExt;
 use power_daemon::{Config, ProfilesInfo, SystemInfo};
 
 use super::audio::AudioGroup;
+use super::dialog::ErrorDialog;
 use super::firmware::FirmwareGroup;
 use super::gpu::GpuGroup;
 use super::groups::{
@@ -104,6 +106,8 @@ pub enum AppInput {
     ResetAllChanged,
     UpdateApplyButton,
     SetUpdating(bool),
+    /// Shows an error the daemon replied with
+    ShowError(String),
 }
 
 #[derive(Debug, Clone)]
@@ -541,6 +545,14 @@ impl SimpleAsyncComponent for App {
             AppInput::SetUpdating(v) => {
                 self.updating = v;
             }
+            AppInput::ShowError(message) => {
+                ErrorDialog {
+                    heading: "The daemon could not complete the request".to_string(),
+                    body: message,
+                }
+                .show()
+                .await
+            }
             AppInput::UpdateApplyButton => self
                 .header
                 .sender()
@@ -555,10 +567,10 @@ impl SimpleAsyncComponent for App {
 
 async fn spawn_background_sync_thread() {
     let mut last_profile_name = communications::daemon_control::get_active_profile_name().await;
+    let mut changes =
+        Box::pin(communications::daemon_control::receive_active_profile_changed().await);
     tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(std::time::Duration::from_secs_f32(1.0)).await;
-            let name = communications::daemon_control::get_active_profile_name().await;
+        while let Some(name) = changes.next().await {
             if name != last_profile_name {
                 // All changes made by the frontend that could influence the
                 // active profile are allways followed by the obtention of
@@ -643,4 +655,16 @@ async fn setup_sync_listeners(sender: AsyncComponentSender<App>) {
             }
         ))
         .await;
+
+    communications::DAEMON_ERROR
+        .set_listener(clone!(
+            #[strong]
+            sender,
+            move |error| {
+                if let Some(error) = error {
+                    sender.input(AppInput::ShowError(error.clone()));
+                }
+            }
+        ))
+        .await;
 }
@@ -33,3 +33,24 @@ impl Dialog {
         dialog_widget.choose_future(&gtk::Window::default()).await == "accept"
     }
 }
+
+/// Informs about something that went wrong, can only be closed
+pub struct ErrorDialog {
+    pub heading: String,
+    pub body: String,
+}
+
+impl ErrorDialog {
+    pub async fn show(self) {
+        relm4::view! {
+            dialog_widget = adw::AlertDialog {
+                set_heading: Some(&self.heading),
+                set_body: &self.body,
+
+                add_response: ("close", "Close"),
+            }
+        }
+
+        dialog_widget.choose_future(&gtk::Window::default()).await;
+    }
+}
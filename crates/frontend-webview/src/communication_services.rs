@@ -135,7 +135,8 @@ pub enum ControlAction {
     RemoveProfile(u32),
     SwapProfiles(u32, u32),
 
-    UpdateProfileReduced(u32, Box<Profile>, ReducedUpdate),
+    /// Updates the profile with the ID of the given one
+    UpdateProfileReduced(Box<Profile>, ReducedUpdate),
 
     GetProfileOverride,
     SetProfileOverride(String),
@@ -195,6 +196,7 @@ pub async fn control_service(
     mut config: Signal<Option<Config>>,
     mut profiles_info: Signal<Option<ProfilesInfo>>,
     mut active_profile_override: Signal<Option<String>>,
+    mut daemon_error: Signal<Option<String>>,
 ) {
     let control_client = ControlClient::new()
         .await
@@ -207,63 +209,64 @@ pub async fn control_service(
                 signal.set(true);
             }
 
-            match msg {
-                ControlAction::GetConfig => {
-                    config.set(Some(
-                        control_client
-                            .get_config()
-                            .await
-                            .expect("Could not obtain config"),
-                    ));
+            let result = match msg {
+                ControlAction::GetConfig => control_client
+                    .get_config()
+                    .await
+                    .map(|value| config.set(Some(value))),
+                ControlAction::GetProfilesInfo => control_client
+                    .get_profiles_info()
+                    .await
+                    .map(|value| profiles_info.set(Some(value))),
+                ControlAction::UpdateConfig(config) => control_client.update_config(*config).await,
+                ControlAction::UpdateProfileReduced(updated, reduced_update) => {
+                    control_client
+                        .update_profile_reduced_by_id(*updated, reduced_update)
+                        .await
                 }
-                ControlAction::GetProfilesInfo => profiles_info.set(Some(
+                ControlAction::CreateProfile(profile_type) => {
+                    control_client.create_profile(profile_type).await
+                }
+                ControlAction::SwapProfiles(idx, new_idx) => {
                     control_client
-                        .get_profiles_info()
+                        .swap_profiles_by_id(
+                            profile_id(profiles_info, idx),
+                            profile_id(profiles_info, new_idx),
+                        )
                         .await
-                        .expect("Could not obtain profiles info."),
-                )),
-                ControlAction::UpdateConfig(config) => control_client
-                    .update_config(*config)
-                    .await
-                    .expect("Could not update config"),
-                ControlAction::UpdateProfileReduced(idx, updated, reduced_update) => control_client
-                    .update_profile_reduced(idx, *updated, reduced_update)
-                    .await
-                    .expect("Could not update profile"),
-                ControlAction::CreateProfile(profile_type) => control_client
-                    .create_profile(profile_type)
-                    .await
-                    .expect("Could not create profile"),
-                ControlAction::SwapProfiles(idx, new_idx) => control_client
-                    .swap_profiles(idx, new_idx)
-                    .await
-                    .expect("Could not create profile"),
-                ControlAction::ResetProfile(idx) => control_client
-                    .reset_profile(idx)
-                    .await
-                    .expect("Could not reset profile"),
-                ControlAction::RenameProfile(idx, name) => control_client
-                    .update_profile_name(idx, name)
-                    .await
-                    .expect("Could not reset profile"),
-                ControlAction::RemoveProfile(idx) => control_client
-                    .remove_profile(idx)
-                    .await
-                    .expect("Could not remove profile"),
-                ControlAction::GetProfileOverride => active_profile_override.set(
+                }
+                ControlAction::ResetProfile(idx) => {
                     control_client
-                        .get_profile_override()
+                        .reset_profile_by_id(profile_id(profiles_info, idx))
                         .await
-                        .expect("Could not obtain profile override"),
-                ),
-                ControlAction::SetProfileOverride(profile_name) => control_client
-                    .set_profile_override(profile_name)
-                    .await
-                    .expect("Could not set profile override"),
-                ControlAction::RemoveProfileOverride => control_client
-                    .remove_profile_override()
-                    .await
-                    .expect("Could not remove profile override"),
+                }
+                ControlAction::RenameProfile(idx, name) => {
+                    control_client
+                        .update_profile_name_by_id(profile_id(profiles_info, idx), name)
+                        .await
+                }
+                ControlAction::RemoveProfile(idx) => {
+                    control_client
+                        .remove_profile_by_id(profile_id(profiles_info, idx))
+                        .await
+                }
+                ControlAction::GetProfileOverride => {
+                    control_client.get_profile_override().await.map(|value| {
+                        active_profile_override
+                            .set(value.map(|profile_override| profile_override.profile_name))
+                    })
+                }
+                ControlAction::SetProfileOverride(profile_name) => {
+                    control_client.set_profile_override(profile_name).await
+                }
+                ControlAction::RemoveProfileOverride => {
+                    control_client.remove_profile_override().await
+                }
+            };
+
+            if let Err(error) = result {
+                tracing::error!("Daemon could not complete the request: {error}");
+                daemon_error.set(Some(error.message()));
             }
 
             if let Some(mut signal) = sent_msg.1 {
@@ -275,6 +278,19 @@ pub async fn control_service(
     }
 }
 
+/// ID of the profile at the index of the synced profiles info, so that the
+/// daemon changes the profile the user sees even if another client reordered
+/// them in the meantime
+fn profile_id(profiles_info: Signal<Option<ProfilesInfo>>, idx: u32) -> String {
+    profiles_info
+        .peek()
+        .as_ref()
+        .expect("Profiles info should be synced before profiles are changed")
+        .profiles[idx as usize]
+        .profile_id
+        .clone()
+}
+
 pub fn control_routine_send_multiple(
     control_routine: ControlRoutine,
     actions: &[ControlAction],
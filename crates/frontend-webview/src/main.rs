@@ -62,8 +62,15 @@ fn App() -> Element {
     let config = use_signal(|| Option::None);
     let profiles_info = use_signal(|| Option::None);
     let active_profile_override = use_signal(|| None);
+    let daemon_error = use_signal(|| None);
     let control_routine = use_coroutine(move |rx| {
-        control_service(rx, config, profiles_info, active_profile_override)
+        control_service(
+            rx,
+            config,
+            profiles_info,
+            active_profile_override,
+            daemon_error,
+        )
     });
 
     let active_profile_name = use_signal(|| None);
@@ -91,6 +98,8 @@ fn App() -> Element {
 
         script { src: "helpers.js" }
 
+        DaemonErrorBanner { daemon_error }
+
         if settings_opened() {
             SettingsMenu {
                 settings_opened,
@@ -228,6 +237,26 @@ fn PowerProfilesNav(
     }
 }
 
+/// Shows the last error the daemon replied with until dismissed
+#[component]
+fn DaemonErrorBanner(daemon_error: Signal<Option<String>>) -> Element {
+    if let Some(error) = daemon_error() {
+        rsx! {
+            div { class: "error-banner",
+                span { "{error}" }
+                button {
+                    onclick: move |_| {
+                        daemon_error.set(None);
+                    },
+                    "Dismiss"
+                }
+            }
+        }
+    } else {
+        rsx! {}
+    }
+}
+
 #[component]
 fn ManageProfilesButton(settings_opened: Signal<bool>) -> Element {
     rsx! {
@@ -64,7 +64,6 @@ pub fn AudioGroup(
 
     let onsubmit = move || {
         let profiles_info = profiles_info().unwrap();
-        let active_profile_idx = profiles_info.active_profile;
         let mut active_profile = profiles_info.get_active_profile().clone();
 
         active_profile.audio_settings = AudioSettings {
@@ -74,11 +73,7 @@ pub fn AudioGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Audio,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Audio),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
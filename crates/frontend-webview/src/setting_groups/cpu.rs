@@ -133,7 +133,6 @@ fn CPUSettingsForm(
     }
 
     let onsubmit = move || {
-        let active_profile_idx = profiles_info.active_profile;
         let mut active_profile = profiles_info.get_active_profile().clone();
 
         active_profile.cpu_settings = CPUSettings {
@@ -168,11 +167,7 @@ fn CPUSettingsForm(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::CPU,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::CPU),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
@@ -52,7 +52,6 @@ pub fn FirmwareGroup(
     let awaiting_completion = use_signal(|| false);
 
     let onsubmit = move || {
-        let active_profile_idx = profiles_info().as_ref().unwrap().active_profile;
         let mut active_profile = profiles_info()
             .as_ref()
             .unwrap()
@@ -66,11 +65,7 @@ pub fn FirmwareGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Firmware,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Firmware),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
@@ -109,7 +109,6 @@ pub fn GpuGroup(
 
     let onsubmit = move || {
         let profiles_info = profiles_info().unwrap();
-        let active_profile_idx = profiles_info.active_profile;
         let mut active_profile = profiles_info.get_active_profile().clone();
 
         active_profile.gpu_settings = GpuSettings {
@@ -148,11 +147,7 @@ pub fn GpuGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Gpu,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Gpu),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
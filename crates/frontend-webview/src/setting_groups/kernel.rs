@@ -64,7 +64,6 @@ pub fn KernelGroup(
     let onsubmit = move || {
         let profiles_info = profiles_info().as_ref().unwrap().clone();
 
-        let active_profile_idx = profiles_info.active_profile;
         let mut active_profile = profiles_info.get_active_profile().clone();
 
         active_profile.kernel_settings = KernelSettings {
@@ -76,11 +75,7 @@ pub fn KernelGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Kernel,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Kernel),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
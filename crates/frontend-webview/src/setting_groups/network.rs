@@ -82,7 +82,6 @@ pub fn NetworkGroup(
 
     let onsubmit = move || {
         let profiles_info = profiles_info().unwrap();
-        let active_profile_idx = profiles_info.active_profile;
         let mut active_profile = profiles_info.get_active_profile().clone();
 
         active_profile.network_settings = NetworkSettings {
@@ -102,11 +101,7 @@ pub fn NetworkGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Network,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Network),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
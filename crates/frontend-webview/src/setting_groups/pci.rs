@@ -81,7 +81,6 @@ pub fn PCIAndASPMGroup(
     let onsubmit = move || {
         let profiles_info = profiles_info().as_ref().unwrap().clone();
 
-        let active_profile_idx = profiles_info.active_profile;
         let mut active_profile = profiles_info.get_active_profile().clone();
 
         active_profile.pci_settings = PCISettings {
@@ -97,15 +96,10 @@ pub fn PCIAndASPMGroup(
             control_routine,
             &[
                 ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
                     active_profile.clone().into(),
                     ReducedUpdate::PCI,
                 ),
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::ASPM,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::ASPM),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
@@ -61,7 +61,6 @@ pub fn RadioGroup(
     let awaiting_completion = use_signal(|| false);
 
     let onsubmit = move || {
-        let active_profile_idx = profiles_info().as_ref().unwrap().active_profile;
         let mut active_profile = profiles_info()
             .as_ref()
             .unwrap()
@@ -77,11 +76,7 @@ pub fn RadioGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Radio,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Radio),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
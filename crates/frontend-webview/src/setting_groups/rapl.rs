@@ -92,7 +92,6 @@ pub fn RaplGroup(
     let awaiting_completion = use_signal(|| false);
 
     let onsubmit = move || {
-        let active_profile_idx = profiles_info().as_ref().unwrap().active_profile;
         let mut active_profile = profiles_info()
             .as_ref()
             .unwrap()
@@ -108,11 +107,7 @@ pub fn RaplGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Rapl,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Rapl),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
@@ -64,7 +64,6 @@ pub fn ScreenGroup(
     let onsubmit = move || {
         let profiles_info = profiles_info().as_ref().unwrap().clone();
 
-        let active_profile_idx = profiles_info.active_profile;
         let mut active_profile = profiles_info.get_active_profile().clone();
 
         active_profile.screen_settings = ScreenSettings {
@@ -76,11 +75,7 @@ pub fn ScreenGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Screen,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Screen),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
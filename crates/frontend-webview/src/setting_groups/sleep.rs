@@ -64,7 +64,6 @@ pub fn SleepGroup(
     let awaiting_completion = use_signal(|| false);
 
     let onsubmit = move || {
-        let active_profile_idx = profiles_info().as_ref().unwrap().active_profile;
         let mut active_profile = profiles_info()
             .as_ref()
             .unwrap()
@@ -79,11 +78,7 @@ pub fn SleepGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::Sleep,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::Sleep),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
@@ -68,7 +68,6 @@ pub fn USBGroup(
     let onsubmit = move || {
         let profiles_info = profiles_info().as_ref().unwrap().clone();
 
-        let active_profile_idx = profiles_info.active_profile;
         let mut active_profile = profiles_info.get_active_profile().clone();
 
         active_profile.usb_settings = USBSettings {
@@ -80,11 +79,7 @@ pub fn USBGroup(
         control_routine_send_multiple(
             control_routine,
             &[
-                ControlAction::UpdateProfileReduced(
-                    active_profile_idx as u32,
-                    active_profile.into(),
-                    ReducedUpdate::USB,
-                ),
+                ControlAction::UpdateProfileReduced(active_profile.into(), ReducedUpdate::USB),
                 ControlAction::GetProfilesInfo,
             ],
             Some(awaiting_completion),
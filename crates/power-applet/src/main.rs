@@ -1,9 +1,9 @@
 use std::{
     io::Write,
     process::{Child, Command, Stdio},
-    time::Duration,
 };
 
+use futures_util::StreamExt;
 use power_daemon::{communication::client::ControlClient, ProfilesInfo};
 
 #[tokio::main]
@@ -21,10 +21,15 @@ async fn main() {
         .await
         .expect("Could not get profile override");
 
+    let mut changes = client
+        .receive_state_changes()
+        .await
+        .expect("Could not subscribe to state changes");
+
     #[allow(clippy::zombie_processes)]
     let mut process = tray_process(&profiles_info, profile_override.is_some());
 
-    loop {
+    while changes.next().await.is_some() {
         let new_profiles_info = client
             .get_profiles_info()
             .await
@@ -45,8 +50,6 @@ async fn main() {
 
             process = tray_process(&profiles_info, profile_override.is_some());
         }
-
-        std::thread::sleep(Duration::from_secs_f32(3.5));
     }
 }
 
@@ -0,0 +1,29 @@
+use std::{sync::Arc, time::Duration};
+
+use log::debug;
+use power_daemon::Instance;
+use tokio::sync::Mutex;
+
+/// How often the config is checked for a drift check interval while drift
+/// enforcement is disabled
+const DISABLED_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Periodically applies again the settings groups of the active profile that
+/// were changed outside of the daemon, if enabled in the config
+pub fn spawn_drift_enforcer(instance: Arc<Mutex<Instance>>) {
+    tokio::spawn(async move {
+        loop {
+            let interval = instance.lock().await.drift_check_interval();
+
+            let Some(interval) = interval else {
+                tokio::time::sleep(DISABLED_RECHECK_INTERVAL).await;
+                continue;
+            };
+
+            tokio::time::sleep(interval).await;
+
+            let drift = instance.lock().await.enforce_profile();
+            debug!("Found {} drifted values", drift.len());
+        }
+    });
+}
@@ -0,0 +1,124 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::{debug, error, warn};
+use nix::{
+    errno::Errno,
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify},
+};
+use power_daemon::Instance;
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Mutex,
+};
+
+/// Time that the files need to stay untouched before they are reloaded, so
+/// that editors and tools writing in multiple steps only trigger one reload
+const DEBOUNCE_DURATION: Duration = Duration::from_secs(1);
+
+/// Watches the config file and the profiles directory and reloads them when
+/// they get changed outside of the daemon
+pub fn spawn_file_watcher(
+    instance: Arc<Mutex<Instance>>,
+    config_path: &Path,
+    profiles_path: &Path,
+) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    let config_path = config_path.to_path_buf();
+    let profiles_path = profiles_path.to_path_buf();
+
+    match Inotify::init(InitFlags::IN_CLOEXEC) {
+        Ok(inotify) => {
+            std::thread::spawn(move || watch_files(inotify, config_path, profiles_path, sender));
+            tokio::spawn(handle_file_events(instance, receiver));
+        }
+        Err(error) => {
+            warn!("Could not initialize inotify, changes to the config files will require a restart: {error}");
+        }
+    }
+}
+
+fn watch_files(
+    inotify: Inotify,
+    config_path: PathBuf,
+    profiles_path: PathBuf,
+    sender: UnboundedSender<()>,
+) {
+    // Editors usually replace files instead of writing to them, so the
+    // directories are watched rather than the files
+    let flags = AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_DELETE;
+
+    let config_dir = config_path.parent().unwrap_or(Path::new("/"));
+    let config_file_name = config_path.file_name();
+
+    let config_watch = match inotify.add_watch(config_dir, flags) {
+        Ok(wd) => wd,
+        Err(error) => {
+            error!("Could not watch {}: {error}", config_dir.display());
+            return;
+        }
+    };
+    let profiles_watch = match inotify.add_watch(&profiles_path, flags) {
+        Ok(wd) => wd,
+        Err(error) => {
+            error!("Could not watch {}: {error}", profiles_path.display());
+            return;
+        }
+    };
+
+    debug!("Watching config and profile files for changes");
+
+    loop {
+        let events = match inotify.read_events() {
+            Ok(events) => events,
+            Err(Errno::EINTR) => continue,
+            Err(error) => {
+                error!("Could not read inotify events, no longer watching config files: {error}");
+                return;
+            }
+        };
+
+        let relevant = events.iter().any(|event| {
+            let Some(ref name) = event.name else {
+                return false;
+            };
+
+            if event.wd == config_watch {
+                Some(name.as_os_str()) == config_file_name
+            } else if event.wd == profiles_watch {
+                Path::new(name).extension() == Some(OsStr::new("toml"))
+            } else {
+                false
+            }
+        });
+
+        if relevant && sender.send(()).is_err() {
+            return;
+        }
+    }
+}
+
+async fn handle_file_events(instance: Arc<Mutex<Instance>>, mut receiver: UnboundedReceiver<()>) {
+    while receiver.recv().await.is_some() {
+        // Wait until the files stop changing
+        loop {
+            match tokio::time::timeout(DEBOUNCE_DURATION, receiver.recv()).await {
+                Ok(Some(())) => continue,
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+
+        debug!("Config files changed on disk");
+        instance.lock().await.reload_from_disk();
+    }
+}
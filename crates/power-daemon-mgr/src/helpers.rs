@@ -1,4 +1,7 @@
-use std::io::{self, Write};
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
 
 pub fn yn_prompt(prompt: &str) -> bool {
     loop {
@@ -16,3 +19,54 @@ pub fn yn_prompt(prompt: &str) -> bool {
         }
     }
 }
+
+/// Parses durations such as "30m", "1h30m" or "90s". A number without a unit
+/// is taken as minutes
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    const TOO_LONG: &str = "Duration is too long";
+
+    let input = input.trim();
+    let total = if let Ok(minutes) = input.parse::<u64>() {
+        minutes.checked_mul(60).ok_or(TOO_LONG)?
+    } else {
+        let mut total: u64 = 0;
+        let mut number = String::new();
+        for c in input.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+
+            let unit = match c {
+                's' => 1,
+                'm' => 60,
+                'h' => 60 * 60,
+                'd' => 24 * 60 * 60,
+                _ => {
+                    return Err(format!(
+                        "Unknown duration unit '{c}', expected s, m, h or d"
+                    ))
+                }
+            };
+            let value: u64 = number
+                .parse()
+                .map_err(|_| format!("Expected a number before '{c}'"))?;
+            total = value
+                .checked_mul(unit)
+                .and_then(|seconds| total.checked_add(seconds))
+                .ok_or(TOO_LONG)?;
+            number.clear();
+        }
+
+        if !number.is_empty() {
+            return Err(format!("Missing unit after {number}"));
+        }
+        total
+    };
+
+    if total == 0 {
+        return Err("Duration must not be zero".to_string());
+    }
+
+    Ok(Duration::from_secs(total))
+}
@@ -1,21 +1,35 @@
+mod drift_enforcer;
+mod file_watcher;
 mod helpers;
+mod power_monitor;
 mod setup;
 
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 
 use colored::Colorize;
-use log::{error, Level, Log, Metadata, Record};
+use log::{error, info, warn, Level, Log, Metadata, Record};
 use nix::unistd::Uid;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::Mutex,
+};
 
 use power_daemon::{communication::client::ControlClient, ReducedUpdate};
 
-use power_daemon::{Instance, SystemInfo};
+use power_daemon::{ApplyOutcome, Instance, NameCollision, OverrideExpiry, Profile, SystemInfo};
 
-use power_daemon::communication::server::CommunicationServer;
+use drift_enforcer::spawn_drift_enforcer;
+use file_watcher::spawn_file_watcher;
+use helpers::parse_duration;
+use power_daemon::communication::{logind::spawn_sleep_monitor, server::CommunicationServer};
+use power_monitor::spawn_power_monitor;
 use setup::{generate_base_files, setup};
 
 #[derive(Parser, Debug)]
@@ -47,16 +61,122 @@ enum OpMode {
     },
     /// Lists the profile names
     ListProfiles,
-    /// Creates a temporary override for a certain profile
+    /// Creates a temporary override for a certain profile. It lasts until
+    /// reboot unless an end is given
     SetProfileOverride {
         profile_name: String,
+        /// End the override after a duration, e.g. "30m" or "1h30m"
+        #[arg(long = "for", value_parser = parse_duration, conflicts_with = "until_power_source_change")]
+        duration: Option<Duration>,
+        /// End the override when the system switches between AC and battery
+        #[arg(long, action=clap::ArgAction::SetTrue)]
+        until_power_source_change: bool,
     },
+    /// Prints the temporary profile override and when it ends
+    GetProfileOverride,
     ResetProfileOverride,
+    /// Sets the profile that is used while on AC
+    SetAcProfile {
+        profile_name: String,
+    },
+    /// Sets the profile that is used while on battery
+    SetBatProfile {
+        profile_name: String,
+    },
+    /// Sets a profile that is always used, regardless of the power source and
+    /// rules, until it is cleared
+    SetPersistentOverride {
+        profile_name: String,
+    },
+    ClearPersistentOverride,
     Daemon,
     RefreshFull,
     RefreshUSB,
     RefreshPCI,
     PrintSystemInfo,
+    /// Prints the changes that applying a profile would make, without
+    /// applying it
+    Plan {
+        /// Name of an existing profile or path to a profile file
+        profile: String,
+    },
+    /// Prints the settings that differ between two profiles, or between a
+    /// profile and the live system if only one is given
+    Diff {
+        /// Name of an existing profile or path to a profile file
+        from: String,
+        /// Name of an existing profile or path to a profile file
+        to: Option<String>,
+        /// Print the changes as JSON
+        #[arg(long, action=clap::ArgAction::SetTrue)]
+        json: bool,
+    },
+    /// Checks a profile against the hardware of this system
+    Validate {
+        /// Name of an existing profile or path to a profile file
+        profile: String,
+    },
+    /// Exports a profile as a bundle that can be imported on other machines
+    Export {
+        profile_name: String,
+        /// File to write the bundle to, printed if not set
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Imports a profile bundle or profile file
+    Import {
+        path: PathBuf,
+        /// What to do if a profile with the same name exists
+        #[arg(long, value_enum, default_value_t = OnCollision::Rename)]
+        on_collision: OnCollision,
+        /// Keep settings specific to the hardware the profile was exported
+        /// from (CPU cores, device lists, frequencies, power limits) instead
+        /// of removing them
+        #[arg(long, action=clap::ArgAction::SetTrue)]
+        keep_hardware_specific: bool,
+    },
+    /// Prints what succeeded, failed or was unsupported the last time the
+    /// daemon applied a profile
+    Report,
+    /// Prints the config and profile files that could not be loaded and were
+    /// replaced
+    LoadErrors,
+    /// Migrates the config and profile files to the format of this version
+    Migrate {
+        /// Only print what would change, without writing the files
+        #[arg(long, action=clap::ArgAction::SetTrue)]
+        check: bool,
+    },
+    /// Writes back the values the system had before the daemon changed them.
+    /// They stay restored until the daemon applies a profile again
+    Restore,
+    /// Prints the values of the active profile that were changed outside of
+    /// the daemon
+    Drift {
+        /// Apply the drifted settings groups again
+        #[arg(long, action=clap::ArgAction::SetTrue)]
+        enforce: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OnCollision {
+    /// Import under a new name
+    Rename,
+    /// Overwrite the existing profile
+    Replace,
+    /// Do not import the profile
+    Fail,
+}
+
+impl From<OnCollision> for NameCollision {
+    fn from(value: OnCollision) -> Self {
+        match value {
+            OnCollision::Rename => NameCollision::Rename,
+            OnCollision::Replace => NameCollision::Replace,
+            OnCollision::Fail => NameCollision::Fail,
+        }
+    }
 }
 
 static LOGGER: StdoutLogger = StdoutLogger;
@@ -93,6 +213,10 @@ impl Log for StdoutLogger {
 pub const CONFIG_FILE: &str = "/etc/power-options/config.toml";
 pub const PROFILES_DIRECTORY: &str = "/etc/power-options/profiles";
 
+/// How often the daemon re-evaluates the profile rules (battery percentage,
+/// time windows, temperatures...)
+const PROFILE_RULES_INTERVAL: Duration = Duration::from_secs(30);
+
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
@@ -120,18 +244,54 @@ async fn main() {
                     .profiles
             );
         }
-        OpMode::SetProfileOverride { profile_name } => ControlClient::new()
-            .await
-            .expect("Could not create control client")
-            .set_profile_override(profile_name)
-            .await
-            .expect("Could not set profile override"),
+        OpMode::SetProfileOverride {
+            profile_name,
+            duration,
+            until_power_source_change,
+        } => {
+            let expiry = match duration {
+                Some(duration) => OverrideExpiry::Duration(duration),
+                None if until_power_source_change => OverrideExpiry::PowerSourceChange,
+                None => OverrideExpiry::Reboot,
+            };
+            ControlClient::new()
+                .await
+                .expect("Could not create control client")
+                .set_profile_override_until(profile_name, expiry)
+                .await
+                .expect("Could not set profile override")
+        }
+        OpMode::GetProfileOverride => get_profile_override().await,
         OpMode::ResetProfileOverride => ControlClient::new()
             .await
             .expect("Could not create control client")
             .remove_profile_override()
             .await
             .expect("Could not reset profile override"),
+        OpMode::SetAcProfile { profile_name } => ControlClient::new()
+            .await
+            .expect("Could not create control client")
+            .set_ac_profile(profile_name)
+            .await
+            .expect("Could not set AC profile"),
+        OpMode::SetBatProfile { profile_name } => ControlClient::new()
+            .await
+            .expect("Could not create control client")
+            .set_bat_profile(profile_name)
+            .await
+            .expect("Could not set battery profile"),
+        OpMode::SetPersistentOverride { profile_name } => ControlClient::new()
+            .await
+            .expect("Could not create control client")
+            .set_persistent_profile_override(profile_name)
+            .await
+            .expect("Could not set persistent profile override"),
+        OpMode::ClearPersistentOverride => ControlClient::new()
+            .await
+            .expect("Could not create control client")
+            .clear_persistent_profile_override()
+            .await
+            .expect("Could not clear persistent profile override"),
         OpMode::RefreshFull => refresh_full().await,
         OpMode::RefreshUSB => refresh_reduced(ReducedUpdate::USB).await,
         OpMode::RefreshPCI => {
@@ -141,6 +301,23 @@ async fn main() {
         OpMode::PrintSystemInfo => {
             println!("{:#?}", SystemInfo::obtain());
         }
+        OpMode::Plan { profile } => plan(&profile).await,
+        OpMode::Diff { from, to, json } => diff(&from, to.as_deref(), json).await,
+        OpMode::Validate { profile } => validate(&profile).await,
+        OpMode::Export {
+            profile_name,
+            output,
+        } => export(profile_name, output).await,
+        OpMode::Import {
+            path,
+            on_collision,
+            keep_hardware_specific,
+        } => import(&path, on_collision.into(), !keep_hardware_specific).await,
+        OpMode::Report => report().await,
+        OpMode::LoadErrors => load_errors().await,
+        OpMode::Migrate { check } => migrate(check),
+        OpMode::Restore => restore().await,
+        OpMode::Drift { enforce } => drift(enforce).await,
     }
 }
 
@@ -161,13 +338,44 @@ async fn daemon() {
 
     handle.update_full();
 
-    let _com_server = CommunicationServer::new(handle)
+    let handle = Arc::new(Mutex::new(handle));
+
+    let _com_server = CommunicationServer::new(handle.clone())
         .await
         .expect("Could not initialize communications server");
 
+    spawn_power_monitor(handle.clone());
+    spawn_drift_enforcer(handle.clone());
+    spawn_file_watcher(handle.clone(), config_path, profiles_path);
+
+    if let Err(error) = spawn_sleep_monitor(handle.clone()).await {
+        warn!("Could not listen to logind, the profile will not be applied again after resuming from suspend: {error}");
+    }
+
+    let mut sigterm = signal(SignalKind::terminate()).expect("Could not listen to SIGTERM");
+    let mut sigint = signal(SignalKind::interrupt()).expect("Could not listen to SIGINT");
+
     loop {
-        std::thread::park();
+        // Wake up in time to end a temporary override with a duration
+        let rules_interval = handle
+            .lock()
+            .await
+            .profile_override_remaining()
+            .map_or(PROFILE_RULES_INTERVAL, |remaining| {
+                remaining.min(PROFILE_RULES_INTERVAL)
+            });
+
+        tokio::select! {
+            _ = tokio::time::sleep(rules_interval) => {
+                handle.lock().await.reevaluate_profile();
+            }
+            _ = sigterm.recv() => break,
+            _ = sigint.recv() => break,
+        }
     }
+
+    info!("Stopping, restoring original system values");
+    handle.lock().await.restore_original_values();
 }
 
 async fn refresh_full() {
@@ -189,3 +397,275 @@ async fn refresh_reduced(reduced_update: ReducedUpdate) {
         .await
         .expect("Could not reset reducedu update");
 }
+
+async fn plan(profile: &str) {
+    let Some(profile) = load_profile(profile).await else {
+        return;
+    };
+
+    for action in profile.plan() {
+        println!("{action}");
+    }
+}
+
+async fn diff(from: &str, to: Option<&str>, json: bool) {
+    let Some(from) = load_profile(from).await else {
+        return;
+    };
+
+    let changes = if let Some(to) = to {
+        let Some(to) = load_profile(to).await else {
+            return;
+        };
+        from.diff(&to)
+    } else {
+        from.diff_live(&SystemInfo::obtain())
+    };
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&changes).expect("Could not serialize changes")
+        );
+        return;
+    }
+
+    let mut group = None;
+    for change in changes {
+        if group != Some(change.group) {
+            group = Some(change.group);
+            println!("{:?}", change.group);
+        }
+        println!("    {change}");
+    }
+}
+
+async fn validate(profile: &str) {
+    let Some(profile) = load_profile(profile).await else {
+        return;
+    };
+
+    let diagnostics = ControlClient::new()
+        .await
+        .expect("Could not intialize control client")
+        .validate_profile(&profile)
+        .await
+        .expect("Could not validate profile");
+
+    if diagnostics.is_empty() {
+        println!("Profile {} is valid on this system", profile.profile_name);
+        return;
+    }
+
+    for diagnostic in diagnostics {
+        if diagnostic.is_error() {
+            println!("error: {diagnostic}");
+        } else {
+            println!("warning: {diagnostic}");
+        }
+    }
+}
+
+/// Loads a profile from a file if `profile` is a path, otherwise obtains the
+/// daemon's profile with that name
+async fn load_profile(profile: &str) -> Option<Profile> {
+    let path = Path::new(profile);
+
+    if path.is_file() {
+        let content = fs::read_to_string(path).expect("Could not read profile file");
+        let name = path.file_stem().unwrap().to_string_lossy();
+        match Profile::parse(&content, &name) {
+            Ok(profile) => Some(profile),
+            Err(error) => {
+                error!("{error}");
+                None
+            }
+        }
+    } else {
+        let profiles_info = ControlClient::new()
+            .await
+            .expect("Could not intialize control client")
+            .get_profiles_info()
+            .await
+            .expect("Could not obtain profiles info");
+
+        let Some(idx) = profiles_info.try_find_profile_index_by_name(profile) else {
+            error!("Profile {profile} does not exist");
+            return None;
+        };
+
+        Some(profiles_info.profiles[idx].clone())
+    }
+}
+
+async fn restore() {
+    ControlClient::new()
+        .await
+        .expect("Could not intialize control client")
+        .restore_original_values()
+        .await
+        .expect("Could not restore original values");
+}
+
+async fn report() {
+    let report = ControlClient::new()
+        .await
+        .expect("Could not intialize control client")
+        .get_apply_report()
+        .await
+        .expect("Could not obtain apply report");
+
+    println!("Profile: {}", report.profile_name);
+
+    for group in report.groups {
+        let succeeded = group
+            .outcomes
+            .iter()
+            .filter(|o| matches!(o, ApplyOutcome::Succeeded { .. }))
+            .count();
+        if group.rolled_back {
+            println!("{:?}: {succeeded} succeeded (rolled back)", group.group);
+        } else {
+            println!("{:?}: {succeeded} succeeded", group.group);
+        }
+
+        for outcome in group
+            .outcomes
+            .iter()
+            .filter(|o| !matches!(o, ApplyOutcome::Succeeded { .. }))
+        {
+            println!("    {outcome}");
+        }
+    }
+}
+
+async fn get_profile_override() {
+    let profile_override = ControlClient::new()
+        .await
+        .expect("Could not create control client")
+        .get_profile_override()
+        .await
+        .expect("Could not obtain profile override");
+
+    let Some(profile_override) = profile_override else {
+        println!("No temporary profile override");
+        return;
+    };
+
+    match profile_override.expiry {
+        OverrideExpiry::Reboot => println!("{} until reboot", profile_override.profile_name),
+        OverrideExpiry::PowerSourceChange => println!(
+            "{} until the power source changes",
+            profile_override.profile_name
+        ),
+        OverrideExpiry::Duration(_) => {
+            let remaining = profile_override.remaining.unwrap_or_default().as_secs();
+            println!(
+                "{} for {}h {:02}m {:02}s",
+                profile_override.profile_name,
+                remaining / 3600,
+                remaining / 60 % 60,
+                remaining % 60
+            );
+        }
+    }
+}
+
+async fn load_errors() {
+    let load_errors = ControlClient::new()
+        .await
+        .expect("Could not intialize control client")
+        .get_load_errors()
+        .await
+        .expect("Could not obtain load errors");
+
+    if load_errors.is_empty() {
+        println!("All config and profile files were loaded");
+    }
+
+    for load_error in load_errors {
+        println!("{load_error}");
+    }
+}
+
+fn migrate(check: bool) {
+    if !check && !Uid::effective().is_root() {
+        error!("Root priviliges required");
+        return;
+    }
+
+    let migrations = power_daemon::migration::migrate_files(
+        Path::new(CONFIG_FILE),
+        Path::new(PROFILES_DIRECTORY),
+        !check,
+    );
+
+    for migration in migrations {
+        print!("{migration}");
+    }
+}
+
+async fn drift(enforce: bool) {
+    let client = ControlClient::new()
+        .await
+        .expect("Could not intialize control client");
+
+    let drift = if enforce {
+        client.enforce_profile().await
+    } else {
+        client.get_drift().await
+    }
+    .expect("Could not obtain drift");
+
+    if drift.is_empty() {
+        println!("No values drifted from the active profile");
+        return;
+    }
+
+    for entry in drift {
+        println!("{:?}: {entry}", entry.group);
+    }
+}
+
+async fn export(profile_name: String, output: Option<PathBuf>) {
+    let bundle = ControlClient::new()
+        .await
+        .expect("Could not intialize control client")
+        .export_profile(profile_name)
+        .await
+        .expect("Could not export profile");
+
+    if let Some(output) = output {
+        fs::write(output, bundle).expect("Could not write profile bundle");
+    } else {
+        print!("{bundle}");
+    }
+}
+
+async fn import(path: &Path, on_collision: NameCollision, strip_hardware_specific: bool) {
+    let bundle = fs::read_to_string(path).expect("Could not read profile bundle");
+
+    let result = ControlClient::new()
+        .await
+        .expect("Could not intialize control client")
+        .import_profile(bundle, on_collision, strip_hardware_specific)
+        .await
+        .expect("Could not import profile");
+
+    println!("Imported profile {}", result.profile_name);
+
+    if !result.hardware_specific_fields.is_empty() {
+        if result.stripped {
+            println!("Removed hardware specific settings:");
+        } else {
+            println!("Kept hardware specific settings, check that they suit this system:");
+        }
+        for field in result.hardware_specific_fields {
+            println!("    {field}");
+        }
+    }
+
+    for diagnostic in result.diagnostics {
+        println!("warning: {diagnostic}");
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    os::fd::{AsRawFd, OwnedFd},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::{debug, error, info, warn};
+use nix::{
+    errno::Errno,
+    sys::socket::{
+        bind, recv, socket, AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType,
+    },
+};
+use power_daemon::{system_on_ac, Instance};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Mutex,
+};
+
+/// Time that the power supplies need to stay quiet before their state is
+/// acted upon, so that flapping adapters don't trigger a flood of updates
+const DEBOUNCE_DURATION: Duration = Duration::from_secs(2);
+/// How often the power supply state is checked if kernel uevents are
+/// unavailable
+const POLLING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Watches /sys/class/power_supply and applies the picked profile whenever
+/// the system switches between AC and battery
+pub fn spawn_power_monitor(instance: Arc<Mutex<Instance>>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    match open_uevent_socket() {
+        Ok(fd) => {
+            debug!("Listening to power supply kernel uevents");
+            std::thread::spawn(move || listen_uevents(fd, sender));
+        }
+        Err(error) => {
+            warn!("Could not open kernel uevent socket, falling back to polling: {error}");
+            std::thread::spawn(move || poll_power_supply(sender));
+        }
+    }
+
+    tokio::spawn(handle_power_supply_events(instance, receiver));
+}
+
+fn open_uevent_socket() -> nix::Result<OwnedFd> {
+    let fd = socket(
+        AddressFamily::Netlink,
+        SockType::Datagram,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::NetlinkKObjectUEvent,
+    )?;
+
+    // Group 1 receives the kernel's own uevents
+    bind(fd.as_raw_fd(), &NetlinkAddr::new(0, 1))?;
+
+    Ok(fd)
+}
+
+fn listen_uevents(fd: OwnedFd, sender: UnboundedSender<()>) {
+    let mut buf = vec![0; 8192];
+
+    loop {
+        match recv(fd.as_raw_fd(), &mut buf, MsgFlags::empty()) {
+            Ok(len) => {
+                // Uevents are a header followed by NUL separated KEY=VALUE pairs
+                let is_power_supply = buf[..len]
+                    .split(|b| *b == 0)
+                    .any(|field| field == b"SUBSYSTEM=power_supply");
+
+                if is_power_supply && sender.send(()).is_err() {
+                    return;
+                }
+            }
+            // The kernel drops messages if we don't read fast enough, that's
+            // fine since we re-read the state from sysfs anyways
+            Err(Errno::EINTR) | Err(Errno::ENOBUFS) => {}
+            Err(error) => {
+                error!("Could not read kernel uevent, falling back to polling: {error}");
+                poll_power_supply(sender);
+                return;
+            }
+        }
+    }
+}
+
+fn poll_power_supply(sender: UnboundedSender<()>) {
+    loop {
+        std::thread::sleep(POLLING_INTERVAL);
+
+        if sender.send(()).is_err() {
+            return;
+        }
+    }
+}
+
+async fn handle_power_supply_events(
+    instance: Arc<Mutex<Instance>>,
+    mut receiver: UnboundedReceiver<()>,
+) {
+    let mut on_ac = system_on_ac();
+
+    while receiver.recv().await.is_some() {
+        // Wait until the power supplies stop sending events
+        loop {
+            match tokio::time::timeout(DEBOUNCE_DURATION, receiver.recv()).await {
+                Ok(Some(())) => continue,
+                Ok(None) => return,
+                Err(_) => break,
+            }
+        }
+
+        let new_on_ac = system_on_ac();
+        if new_on_ac == on_ac {
+            continue;
+        }
+        on_ac = new_on_ac;
+
+        info!(
+            "Power source changed to {}, updating profile",
+            if on_ac { "AC" } else { "battery" }
+        );
+
+        instance.lock().await.update_full();
+    }
+}
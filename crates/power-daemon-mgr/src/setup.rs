@@ -1,7 +1,10 @@
-use std::{fs, path::Path};
+use std::{fs, io::ErrorKind, path::Path};
 
-use log::{debug, trace};
-use power_daemon::{profiles_generator, Config, DefaultProfileType, SystemInfo};
+use log::{debug, trace, warn};
+use power_daemon::{
+    communication::polkit::{EDIT_PROFILES_ACTION, SWITCH_PROFILE_ACTION},
+    profiles_generator, Config, DefaultProfileType, SystemInfo,
+};
 
 use crate::helpers::yn_prompt;
 
@@ -20,9 +23,10 @@ pub fn setup(root: &Path) {
 
 pub fn generate_base_files(path: &Path, program_path: &Path, verbose_daemon: bool) {
     generate_udev_file(path, program_path);
-    generate_acpi_file(path, program_path);
     generate_dbus_file(path);
+    generate_polkit_file(path);
     genereate_systemd_file(path, program_path, verbose_daemon);
+    remove_acpi_file(path);
 }
 
 fn generate_config_files(path: &Path) {
@@ -114,22 +118,16 @@ ACTION=="add", SUBSYSTEM=="pci", ENV{{DEVTYPE}}=="pci_device", RUN+="{program_pa
     fs::write(dir.join("85-power-daemon.rules"), &content).expect("Could not write to file");
 }
 
-fn generate_acpi_file(path: &Path, program_path: &Path) {
-    debug!("Generating ACPI file");
+/// Older versions refreshed on AC changes through an acpid rule, which would
+/// now refresh a second time after the daemon's own power supply monitor
+fn remove_acpi_file(path: &Path) {
+    let file = path.join("etc/acpi/events/power-options");
 
-    let dir = path.join("etc/acpi/events/");
-    fs::create_dir_all(&dir).expect("Could not create directory");
-
-    let program_path = program_path.display();
-
-    let content = format!(
-        r#"
-event=ac_adapter
-action={program_path} refresh-full
-"#
-    );
-
-    fs::write(dir.join("power-options"), content).expect("COuld not write to file");
+    match fs::remove_file(&file) {
+        Ok(()) => debug!("Removed obsolete ACPI file"),
+        Err(error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => warn!("Could not remove {}: {error}", file.display()),
+    }
 }
 
 fn generate_dbus_file(path: &Path) {
@@ -161,6 +159,54 @@ fn generate_dbus_file(path: &Path) {
     fs::write(dir.join("power-daemon.conf"), content).expect("Could not write to file");
 }
 
+fn generate_polkit_file(path: &Path) {
+    debug!("Generating polkit file");
+
+    let dir = path.join("usr/share/polkit-1/actions/");
+    fs::create_dir_all(&dir).expect("Could not create directory");
+
+    // Active local users may switch profiles like with power-profiles-daemon,
+    // changing what the daemon applies requires an administrator
+    let content = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE policyconfig PUBLIC "-//freedesktop//DTD PolicyKit Policy Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/PolicyKit/1/policyconfig.dtd">
+<policyconfig>
+  <vendor>power-options</vendor>
+  <vendor_url>https://github.com/TheAlexDev23/power-options</vendor_url>
+
+  <action id="{SWITCH_PROFILE_ACTION}">
+    <description>Switch and apply power profiles</description>
+    <message>Authentication is required to switch the power profile</message>
+    <defaults>
+      <allow_any>auth_admin</allow_any>
+      <allow_inactive>auth_admin</allow_inactive>
+      <allow_active>yes</allow_active>
+    </defaults>
+  </action>
+
+  <action id="{EDIT_PROFILES_ACTION}">
+    <description>Edit power profiles and the power-options configuration</description>
+    <message>Authentication is required to change power profiles</message>
+    <defaults>
+      <allow_any>auth_admin</allow_any>
+      <allow_inactive>auth_admin</allow_inactive>
+      <allow_active>auth_admin_keep</allow_active>
+    </defaults>
+  </action>
+</policyconfig>
+"#
+    );
+
+    trace!("{content}");
+
+    fs::write(
+        dir.join("io.github.thealexdev23.power_daemon.policy"),
+        content,
+    )
+    .expect("Could not write to file");
+}
+
 fn genereate_systemd_file(path: &Path, program_path: &Path, verbose_daemon: bool) {
     debug!("Generating systemd file");
 
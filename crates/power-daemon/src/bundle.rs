@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{migration, Profile, ProfileDiagnostic};
+
+/// A profile in a form that can be shared between machines
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProfileBundle {
+    /// Version of the daemon that exported the profile
+    pub exported_by: String,
+    pub profile: Profile,
+}
+
+impl ProfileBundle {
+    pub fn new(profile: Profile) -> ProfileBundle {
+        ProfileBundle {
+            exported_by: env!("CARGO_PKG_VERSION").to_string(),
+            profile,
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("Could not serialize profile bundle")
+    }
+
+    /// Parses a bundle, plain profile files are accepted too. Profiles of
+    /// older versions are migrated
+    pub fn parse(content: &str) -> Result<ProfileBundle, String> {
+        let table = content
+            .parse::<toml::Table>()
+            .map_err(|error| format!("Not a profile bundle or profile: {error}"))?;
+
+        if let Some(toml::Value::Table(profile)) = table.get("profile") {
+            let (profile, _) = migration::migrate_profile_table(profile.clone())?;
+            let exported_by = table
+                .get("exported_by")
+                .and_then(toml::Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+
+            return Ok(ProfileBundle {
+                exported_by,
+                profile,
+            });
+        }
+
+        migration::migrate_profile_table(table)
+            .map(|(profile, _)| ProfileBundle::new(profile))
+            .map_err(|error| format!("Not a profile bundle or profile: {error}"))
+    }
+}
+
+/// What to do when importing a profile whose name is already taken
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum NameCollision {
+    /// Import under a new name with a " #N" suffix
+    #[default]
+    Rename,
+    /// Overwrite the existing profile
+    Replace,
+    /// Do not import the profile
+    Fail,
+}
+
+/// Outcome of a successful import
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImportResult {
+    /// Name the profile was imported as
+    pub profile_name: String,
+    /// Settings that only make sense on the machine the profile was exported
+    /// from, see [`hardware_specific_fields`]
+    pub hardware_specific_fields: Vec<String>,
+    /// True if the settings in `hardware_specific_fields` were removed
+    pub stripped: bool,
+    /// Problems found when checking the profile against this system
+    pub diagnostics: Vec<ProfileDiagnostic>,
+}
+
+/// Lists the set settings that refer to specific hardware, like CPU cores,
+/// device addresses, frequencies or power limits
+pub fn hardware_specific_fields(profile: &Profile) -> Vec<String> {
+    let fields = [
+        (
+            "cpu_settings.min_freq",
+            profile.cpu_settings.min_freq.is_some(),
+        ),
+        (
+            "cpu_settings.max_freq",
+            profile.cpu_settings.max_freq.is_some(),
+        ),
+        (
+            "cpu_core_settings.cores",
+            profile.cpu_core_settings.cores.is_some(),
+        ),
+        (
+            "pci_settings.whiteblacklist",
+            profile.pci_settings.whiteblacklist.is_some(),
+        ),
+        (
+            "usb_settings.whiteblacklist",
+            profile.usb_settings.whiteblacklist.is_some(),
+        ),
+        (
+            "gpu_settings.intel_min",
+            profile.gpu_settings.intel_min.is_some(),
+        ),
+        (
+            "gpu_settings.intel_max",
+            profile.gpu_settings.intel_max.is_some(),
+        ),
+        (
+            "gpu_settings.intel_boost",
+            profile.gpu_settings.intel_boost.is_some(),
+        ),
+        (
+            "rapl_settings.package",
+            profile.rapl_settings.package.is_some(),
+        ),
+        ("rapl_settings.core", profile.rapl_settings.core.is_some()),
+        (
+            "rapl_settings.uncore",
+            profile.rapl_settings.uncore.is_some(),
+        ),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(field, _)| field.to_string())
+        .collect()
+}
+
+/// Unsets every setting listed by [`hardware_specific_fields`]
+pub fn strip_hardware_specific(profile: &mut Profile) {
+    profile.cpu_settings.min_freq = None;
+    profile.cpu_settings.max_freq = None;
+    profile.cpu_core_settings.cores = None;
+    profile.pci_settings.whiteblacklist = None;
+    profile.usb_settings.whiteblacklist = None;
+    profile.gpu_settings.intel_min = None;
+    profile.gpu_settings.intel_max = None;
+    profile.gpu_settings.intel_boost = None;
+    profile.rapl_settings.package = None;
+    profile.rapl_settings.core = None;
+    profile.rapl_settings.uncore = None;
+}
@@ -1,10 +1,16 @@
 use crate::{
     systeminfo::{CPUInfo, SystemInfo},
-    Config, DefaultProfileType, FirmwareInfo, GpuInfo, OptionalFeaturesInfo, PCIInfo, Profile,
-    ProfilesInfo, ReducedUpdate, SATAInfo, USBInfo,
+    ApplyReport, BatteryInfo, Config, DefaultProfileType, Drift, FirmwareInfo, GpuInfo,
+    ImportResult, LoadError, NameCollision, OptionalFeaturesInfo, OverrideExpiry, PCIInfo, Profile,
+    ProfileDiagnostic, ProfileOverride, ProfilePickReason, ProfilesInfo, ReducedUpdate, SATAInfo,
+    SettingChange, StateChange, USBInfo,
 };
+use futures_util::{future, stream, Stream, StreamExt};
+use serde::de::DeserializeOwned;
 use zbus::proxy;
 
+use super::error::{self, ControlDBusError};
+
 #[proxy(
     default_service = "io.github.thealexdev23.power_daemon",
     interface = "io.github.thealexdev23.power_daemon.system_info",
@@ -32,6 +38,9 @@ trait SystemInfoDBus {
     /// Returns a JSON encoded `GpuInfo`
     fn get_gpu_info(&self) -> zbus::Result<String>;
 
+    /// Returns a JSON encoded `BatteryInfo`
+    fn get_battery_info(&self) -> zbus::Result<String>;
+
     /// Returns a JSON encoded `OptionalFeaturesInfo`
     fn get_optional_features_info(&self) -> zbus::Result<String>;
 }
@@ -68,6 +77,9 @@ impl SystemInfoClient {
     pub async fn get_gpu_info(&self) -> zbus::Result<GpuInfo> {
         Ok(serde_json::from_str(&self.get_proxy().await?.get_gpu_info().await?).unwrap())
     }
+    pub async fn get_battery_info(&self) -> zbus::Result<BatteryInfo> {
+        Ok(serde_json::from_str(&self.get_proxy().await?.get_battery_info().await?).unwrap())
+    }
     pub async fn get_optional_features_info(&self) -> zbus::Result<OptionalFeaturesInfo> {
         Ok(
             serde_json::from_str(&self.get_proxy().await?.get_optional_features_info().await?)
@@ -86,37 +98,83 @@ impl SystemInfoClient {
     default_path = "/io/github/thealexdev23/power_daemon/control"
 )]
 trait ControlDBus {
-    async fn get_config(&self) -> zbus::Result<String>;
-    async fn get_profiles_info(&self) -> zbus::Result<String>;
-
-    async fn update_full(&self) -> zbus::Result<()>;
-    async fn update_reduced(&self, partial_update: String) -> zbus::Result<()>;
-
-    async fn update_config(&self, updated: String) -> zbus::Result<()>;
-
-    async fn get_active_profile_name(&self) -> zbus::Result<String>;
-
-    async fn create_profile(&self, profile_type: String) -> zbus::Result<()>;
-    async fn remove_profile(&self, idx: u32) -> zbus::Result<()>;
-    async fn reset_profile(&self, idx: u32) -> zbus::Result<()>;
-
-    async fn swap_profiles(&self, idx: u32, new_idx: u32) -> zbus::Result<()>;
-    async fn update_profile_name(&self, idx: u32, new_name: String) -> zbus::Result<()>;
-
-    async fn update_profile_full(&self, idx: u32, updated: String) -> zbus::Result<()>;
+    async fn get_config(&self) -> error::Result<String>;
+    async fn get_profiles_info(&self) -> error::Result<String>;
+
+    async fn update_full(&self) -> error::Result<()>;
+    async fn update_reduced(&self, partial_update: String) -> error::Result<()>;
+
+    async fn update_config(&self, updated: String) -> error::Result<()>;
+    async fn set_ac_profile(&self, profile_name: String) -> error::Result<()>;
+    async fn set_bat_profile(&self, profile_name: String) -> error::Result<()>;
+    async fn set_persistent_profile_override(&self, profile_name: String) -> error::Result<()>;
+    async fn clear_persistent_profile_override(&self) -> error::Result<()>;
+
+    async fn get_active_profile_name(&self) -> error::Result<String>;
+    async fn get_profile_pick_reason(&self) -> error::Result<String>;
+    async fn get_apply_report(&self) -> error::Result<String>;
+    async fn get_load_errors(&self) -> error::Result<String>;
+    async fn restore_original_values(&self) -> error::Result<()>;
+    async fn get_drift(&self) -> error::Result<String>;
+    async fn enforce_profile(&self) -> error::Result<String>;
+
+    async fn create_profile(&self, profile_type: String) -> error::Result<()>;
+    async fn remove_profile(&self, idx: u32) -> error::Result<()>;
+    async fn remove_profile_by_id(&self, id: String) -> error::Result<()>;
+    async fn reset_profile(&self, idx: u32) -> error::Result<()>;
+    async fn reset_profile_by_id(&self, id: String) -> error::Result<()>;
+    async fn diff_profiles(&self, from: String, to: String) -> error::Result<String>;
+    async fn diff_profile_live(&self, profile_name: String) -> error::Result<String>;
+    async fn export_profile(&self, profile_name: String) -> error::Result<String>;
+    async fn import_profile(
+        &self,
+        bundle: String,
+        on_collision: String,
+        strip_hardware_specific: bool,
+    ) -> error::Result<String>;
+
+    async fn swap_profiles(&self, idx: u32, new_idx: u32) -> error::Result<()>;
+    async fn swap_profiles_by_id(&self, id: String, other_id: String) -> error::Result<()>;
+    async fn update_profile_name(&self, idx: u32, new_name: String) -> error::Result<()>;
+    async fn update_profile_name_by_id(&self, id: String, new_name: String) -> error::Result<()>;
+
+    async fn update_profile_full(&self, idx: u32, updated: String) -> error::Result<()>;
+    async fn update_profile_full_by_id(&self, id: String, updated: String) -> error::Result<()>;
+    async fn validate_profile(&self, profile: String) -> error::Result<String>;
     async fn update_profile_reduced(
         &self,
         idx: u32,
         updated: String,
         reduced_update: String,
-    ) -> zbus::Result<()>;
+    ) -> error::Result<()>;
+    async fn update_profile_reduced_by_id(
+        &self,
+        id: String,
+        updated: String,
+        reduced_update: String,
+    ) -> error::Result<()>;
 
-    async fn set_reduced_update(&self, reduced_update: String) -> zbus::Result<()>;
-    async fn reset_reduced_update(&self) -> zbus::Result<()>;
+    async fn set_reduced_update(&self, reduced_update: String) -> error::Result<()>;
+    async fn reset_reduced_update(&self) -> error::Result<()>;
 
-    async fn get_profile_override(&self) -> zbus::Result<String>;
-    async fn set_profile_override(&self, profile_name: String) -> zbus::Result<()>;
-    async fn remove_profile_override(&self) -> zbus::Result<()>;
+    async fn get_profile_override(&self) -> error::Result<String>;
+    async fn set_profile_override(&self, profile_name: String) -> error::Result<()>;
+    async fn set_profile_override_until(
+        &self,
+        profile_name: String,
+        expiry: String,
+    ) -> error::Result<()>;
+    async fn remove_profile_override(&self) -> error::Result<()>;
+
+    #[zbus(signal)]
+    fn active_profile_changed(&self, profile_name: String) -> zbus::Result<()>;
+    #[zbus(signal)]
+    fn profiles_changed(&self) -> zbus::Result<()>;
+    #[zbus(signal)]
+    fn config_changed(&self) -> zbus::Result<()>;
+    /// Carries a JSON encoded `Option<ProfileOverride>`
+    #[zbus(signal)]
+    fn override_changed(&self, profile_override: String) -> zbus::Result<()>;
 }
 
 #[derive(Clone)]
@@ -130,17 +188,17 @@ impl ControlClient {
         Ok(Self { dbus_con: con })
     }
 
-    pub async fn get_config(&self) -> zbus::Result<Config> {
-        Ok(serde_json::from_str(&self.get_proxy().await?.get_config().await?).unwrap())
+    pub async fn get_config(&self) -> error::Result<Config> {
+        parse_reply(self.get_proxy().await?.get_config().await?)
     }
-    pub async fn get_profiles_info(&self) -> zbus::Result<ProfilesInfo> {
-        Ok(serde_json::from_str(&self.get_proxy().await?.get_profiles_info().await?).unwrap())
+    pub async fn get_profiles_info(&self) -> error::Result<ProfilesInfo> {
+        parse_reply(self.get_proxy().await?.get_profiles_info().await?)
     }
 
-    pub async fn update_full(&self) -> zbus::Result<()> {
+    pub async fn update_full(&self) -> error::Result<()> {
         self.get_proxy().await?.update_full().await
     }
-    pub async fn update_reduced(&self, reduced_update: ReducedUpdate) -> zbus::Result<()> {
+    pub async fn update_reduced(&self, reduced_update: ReducedUpdate) -> error::Result<()> {
         self.get_proxy()
             .await?
             .update_reduced(
@@ -149,40 +207,146 @@ impl ControlClient {
             .await
     }
 
-    pub async fn update_config(&self, config: Config) -> zbus::Result<()> {
+    pub async fn update_config(&self, config: Config) -> error::Result<()> {
         self.get_proxy()
             .await?
             .update_config(serde_json::to_string(&config).expect("Could not serialize config"))
             .await
     }
+    pub async fn set_ac_profile(&self, profile_name: String) -> error::Result<()> {
+        self.get_proxy().await?.set_ac_profile(profile_name).await
+    }
+    pub async fn set_bat_profile(&self, profile_name: String) -> error::Result<()> {
+        self.get_proxy().await?.set_bat_profile(profile_name).await
+    }
+    pub async fn set_persistent_profile_override(&self, profile_name: String) -> error::Result<()> {
+        self.get_proxy()
+            .await?
+            .set_persistent_profile_override(profile_name)
+            .await
+    }
+    pub async fn clear_persistent_profile_override(&self) -> error::Result<()> {
+        self.get_proxy()
+            .await?
+            .clear_persistent_profile_override()
+            .await
+    }
 
-    pub async fn get_active_profile_name(&self) -> zbus::Result<String> {
+    pub async fn get_active_profile_name(&self) -> error::Result<String> {
         self.get_proxy().await?.get_active_profile_name().await
     }
-    pub async fn create_profile(&self, profile_type: DefaultProfileType) -> zbus::Result<()> {
+    pub async fn get_profile_pick_reason(&self) -> error::Result<ProfilePickReason> {
+        parse_reply(self.get_proxy().await?.get_profile_pick_reason().await?)
+    }
+    pub async fn get_apply_report(&self) -> error::Result<ApplyReport> {
+        parse_reply(self.get_proxy().await?.get_apply_report().await?)
+    }
+    pub async fn get_load_errors(&self) -> error::Result<Vec<LoadError>> {
+        parse_reply(self.get_proxy().await?.get_load_errors().await?)
+    }
+    pub async fn restore_original_values(&self) -> error::Result<()> {
+        self.get_proxy().await?.restore_original_values().await
+    }
+    pub async fn get_drift(&self) -> error::Result<Vec<Drift>> {
+        parse_reply(self.get_proxy().await?.get_drift().await?)
+    }
+    pub async fn enforce_profile(&self) -> error::Result<Vec<Drift>> {
+        parse_reply(self.get_proxy().await?.enforce_profile().await?)
+    }
+    pub async fn create_profile(&self, profile_type: DefaultProfileType) -> error::Result<()> {
         self.get_proxy()
             .await?
             .create_profile(serde_json::to_string(&profile_type).unwrap())
             .await
     }
-    pub async fn remove_profile(&self, idx: u32) -> zbus::Result<()> {
+    #[deprecated(note = "Indices change when profiles are reordered, use `remove_profile_by_id`")]
+    pub async fn remove_profile(&self, idx: u32) -> error::Result<()> {
         self.get_proxy().await?.remove_profile(idx).await
     }
-    pub async fn reset_profile(&self, idx: u32) -> zbus::Result<()> {
+    pub async fn remove_profile_by_id(&self, id: String) -> error::Result<()> {
+        self.get_proxy().await?.remove_profile_by_id(id).await
+    }
+    #[deprecated(note = "Indices change when profiles are reordered, use `reset_profile_by_id`")]
+    pub async fn reset_profile(&self, idx: u32) -> error::Result<()> {
         self.get_proxy().await?.reset_profile(idx).await
     }
+    pub async fn reset_profile_by_id(&self, id: String) -> error::Result<()> {
+        self.get_proxy().await?.reset_profile_by_id(id).await
+    }
+    pub async fn diff_profiles(
+        &self,
+        from: String,
+        to: String,
+    ) -> error::Result<Vec<SettingChange>> {
+        parse_reply(self.get_proxy().await?.diff_profiles(from, to).await?)
+    }
+    pub async fn diff_profile_live(
+        &self,
+        profile_name: String,
+    ) -> error::Result<Vec<SettingChange>> {
+        parse_reply(
+            self.get_proxy()
+                .await?
+                .diff_profile_live(profile_name)
+                .await?,
+        )
+    }
+    /// Returns the profile as a TOML encoded `ProfileBundle`
+    pub async fn export_profile(&self, profile_name: String) -> error::Result<String> {
+        self.get_proxy().await?.export_profile(profile_name).await
+    }
+    pub async fn import_profile(
+        &self,
+        bundle: String,
+        on_collision: NameCollision,
+        strip_hardware_specific: bool,
+    ) -> error::Result<ImportResult> {
+        parse_reply(
+            self.get_proxy()
+                .await?
+                .import_profile(
+                    bundle,
+                    serde_json::to_string(&on_collision).unwrap(),
+                    strip_hardware_specific,
+                )
+                .await?,
+        )
+    }
 
-    pub async fn swap_profiles(&self, idx: u32, new_idx: u32) -> zbus::Result<()> {
+    #[deprecated(note = "Indices change when profiles are reordered, use `swap_profiles_by_id`")]
+    pub async fn swap_profiles(&self, idx: u32, new_idx: u32) -> error::Result<()> {
         self.get_proxy().await?.swap_profiles(idx, new_idx).await
     }
-    pub async fn update_profile_name(&self, idx: u32, new_name: String) -> zbus::Result<()> {
+    pub async fn swap_profiles_by_id(&self, id: String, other_id: String) -> error::Result<()> {
+        self.get_proxy()
+            .await?
+            .swap_profiles_by_id(id, other_id)
+            .await
+    }
+    #[deprecated(
+        note = "Indices change when profiles are reordered, use `update_profile_name_by_id`"
+    )]
+    pub async fn update_profile_name(&self, idx: u32, new_name: String) -> error::Result<()> {
         self.get_proxy()
             .await?
             .update_profile_name(idx, new_name)
             .await
     }
+    pub async fn update_profile_name_by_id(
+        &self,
+        id: String,
+        new_name: String,
+    ) -> error::Result<()> {
+        self.get_proxy()
+            .await?
+            .update_profile_name_by_id(id, new_name)
+            .await
+    }
 
-    pub async fn update_profile_full(&self, idx: u32, updated: Profile) -> zbus::Result<()> {
+    #[deprecated(
+        note = "Indices change when profiles are reordered, use `update_profile_full_by_id`"
+    )]
+    pub async fn update_profile_full(&self, idx: u32, updated: Profile) -> error::Result<()> {
         self.get_proxy()
             .await?
             .update_profile_full(
@@ -191,12 +355,39 @@ impl ControlClient {
             )
             .await
     }
+    /// Replaces the profile with the ID of `updated`
+    pub async fn update_profile_full_by_id(&self, updated: Profile) -> error::Result<()> {
+        self.get_proxy()
+            .await?
+            .update_profile_full_by_id(
+                updated.profile_id.clone(),
+                serde_json::to_string(&updated).expect("Could not serialize profile"),
+            )
+            .await
+    }
+    /// Checks the profile against the hardware the daemon runs on
+    pub async fn validate_profile(
+        &self,
+        profile: &Profile,
+    ) -> error::Result<Vec<ProfileDiagnostic>> {
+        parse_reply(
+            self.get_proxy()
+                .await?
+                .validate_profile(
+                    serde_json::to_string(profile).expect("Could not serialize profile"),
+                )
+                .await?,
+        )
+    }
+    #[deprecated(
+        note = "Indices change when profiles are reordered, use `update_profile_reduced_by_id`"
+    )]
     pub async fn update_profile_reduced(
         &self,
         idx: u32,
         updated: Profile,
         reduced_update: ReducedUpdate,
-    ) -> zbus::Result<()> {
+    ) -> error::Result<()> {
         self.get_proxy()
             .await?
             .update_profile_reduced(
@@ -206,26 +397,119 @@ impl ControlClient {
             )
             .await
     }
-
-    pub async fn get_profile_override(&self) -> zbus::Result<Option<String>> {
+    /// Replaces the profile with the ID of `updated`, only applying the
+    /// settings of the reduced update
+    pub async fn update_profile_reduced_by_id(
+        &self,
+        updated: Profile,
+        reduced_update: ReducedUpdate,
+    ) -> error::Result<()> {
         self.get_proxy()
             .await?
-            .get_profile_override()
+            .update_profile_reduced_by_id(
+                updated.profile_id.clone(),
+                serde_json::to_string(&updated).expect("Could not serialize profile"),
+                serde_json::to_string(&reduced_update).expect("Could not serialize reduced update"),
+            )
             .await
-            .map(|p| if p.is_empty() { None } else { Some(p) })
     }
 
-    pub async fn set_profile_override(&self, profile_name: String) -> zbus::Result<()> {
+    pub async fn get_profile_override(&self) -> error::Result<Option<ProfileOverride>> {
+        parse_reply(self.get_proxy().await?.get_profile_override().await?)
+    }
+
+    pub async fn set_profile_override(&self, profile_name: String) -> error::Result<()> {
         self.get_proxy()
             .await?
             .set_profile_override(profile_name)
             .await
     }
-    pub async fn remove_profile_override(&self) -> zbus::Result<()> {
+    pub async fn set_profile_override_until(
+        &self,
+        profile_name: String,
+        expiry: OverrideExpiry,
+    ) -> error::Result<()> {
+        self.get_proxy()
+            .await?
+            .set_profile_override_until(profile_name, serde_json::to_string(&expiry).unwrap())
+            .await
+    }
+    pub async fn remove_profile_override(&self) -> error::Result<()> {
         self.get_proxy().await?.remove_profile_override().await
     }
 
+    /// Yields the name of the active profile whenever another one is applied
+    pub async fn receive_active_profile_changed(&self) -> zbus::Result<impl Stream<Item = String>> {
+        Ok(self
+            .get_proxy()
+            .await?
+            .receive_active_profile_changed()
+            .await?
+            .filter_map(|signal| future::ready(signal.args().ok().map(|args| args.profile_name))))
+    }
+    /// Yields whenever profiles are added, removed, renamed, reordered or
+    /// edited
+    pub async fn receive_profiles_changed(&self) -> zbus::Result<impl Stream<Item = ()>> {
+        Ok(self
+            .get_proxy()
+            .await?
+            .receive_profiles_changed()
+            .await?
+            .map(|_| ()))
+    }
+    pub async fn receive_config_changed(&self) -> zbus::Result<impl Stream<Item = ()>> {
+        Ok(self
+            .get_proxy()
+            .await?
+            .receive_config_changed()
+            .await?
+            .map(|_| ()))
+    }
+    pub async fn receive_override_changed(
+        &self,
+    ) -> zbus::Result<impl Stream<Item = Option<ProfileOverride>>> {
+        Ok(self
+            .get_proxy()
+            .await?
+            .receive_override_changed()
+            .await?
+            .filter_map(|signal| {
+                future::ready(
+                    signal
+                        .args()
+                        .ok()
+                        .and_then(|args| serde_json::from_str(&args.profile_override).ok()),
+                )
+            }))
+    }
+    /// Every change of the daemon's state, in the order they are received
+    pub async fn receive_state_changes(&self) -> zbus::Result<impl Stream<Item = StateChange>> {
+        Ok(stream::select_all([
+            self.receive_active_profile_changed()
+                .await?
+                .map(StateChange::ActiveProfile)
+                .boxed(),
+            self.receive_profiles_changed()
+                .await?
+                .map(|_| StateChange::Profiles)
+                .boxed(),
+            self.receive_config_changed()
+                .await?
+                .map(|_| StateChange::Config)
+                .boxed(),
+            self.receive_override_changed()
+                .await?
+                .map(StateChange::Override)
+                .boxed(),
+        ]))
+    }
+
     async fn get_proxy(&self) -> zbus::Result<ControlDBusProxy<'_>> {
         ControlDBusProxy::new(&self.dbus_con).await
     }
 }
+
+fn parse_reply<T: DeserializeOwned>(reply: String) -> error::Result<T> {
+    serde_json::from_str(&reply)
+        .map_err(|error| ControlDBusError::InvalidReply(format!("Could not parse reply: {error}")))
+}
@@ -0,0 +1,66 @@
+use zbus::{fdo, DBusError};
+
+use crate::ControlError;
+
+/// Result of a control method, analogous to `zbus::fdo::Result`
+pub type Result<T> = std::result::Result<T, ControlDBusError>;
+
+/// Errors the control interfaces reply with. Their D-Bus names are prefixed
+/// with the interface's error namespace, e.g.
+/// "io.github.thealexdev23.power_daemon.Error.NameTaken"
+#[derive(DBusError, Debug)]
+#[zbus(prefix = "io.github.thealexdev23.power_daemon.Error")]
+pub enum ControlDBusError {
+    /// The call itself failed, e.g. because the daemon is not running
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    /// An argument could not be parsed
+    InvalidArgs(String),
+    /// Polkit did not authorize the caller
+    AccessDenied(String),
+    IndexOutOfRange(String),
+    ProfileNotFound(String),
+    InvalidProfileName(String),
+    NameTaken(String),
+    ProfileActive(String),
+    LastProfile(String),
+    /// The profile cannot be applied on this system
+    InvalidProfile(String),
+    ImportFailed(String),
+    /// The reply could not be parsed, e.g. because the daemon runs a
+    /// different version. Only returned by the client
+    InvalidReply(String),
+}
+
+impl ControlDBusError {
+    /// Description of the error that can be shown to the user
+    pub fn message(&self) -> String {
+        match self {
+            ControlDBusError::ZBus(error) => error.to_string(),
+            other => other.description().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+impl From<ControlError> for ControlDBusError {
+    fn from(error: ControlError) -> Self {
+        let message = error.to_string();
+        match error {
+            ControlError::IndexOutOfRange(_) => ControlDBusError::IndexOutOfRange(message),
+            ControlError::ProfileNotFound(_) | ControlError::ProfileIdNotFound(_) => {
+                ControlDBusError::ProfileNotFound(message)
+            }
+            ControlError::InvalidProfileName(_) => ControlDBusError::InvalidProfileName(message),
+            ControlError::NameTaken(_) => ControlDBusError::NameTaken(message),
+            ControlError::ProfileActive(_) => ControlDBusError::ProfileActive(message),
+            ControlError::LastProfile => ControlDBusError::LastProfile(message),
+            ControlError::InvalidProfile(_) => ControlDBusError::InvalidProfile(message),
+        }
+    }
+}
+
+impl From<fdo::Error> for ControlDBusError {
+    fn from(error: fdo::Error) -> Self {
+        ControlDBusError::ZBus(error.into())
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use log::{debug, error, info};
+use tokio::sync::Mutex;
+use zbus::{proxy, Connection};
+
+use crate::Instance;
+
+#[proxy(
+    default_service = "org.freedesktop.login1",
+    interface = "org.freedesktop.login1.Manager",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LogindManager {
+    /// Sent with `start` set to true right before the system goes to sleep and
+    /// with false after it woke up
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Listens to logind and applies the active profile again every time the
+/// system resumes from suspend
+pub async fn spawn_sleep_monitor(instance: Arc<Mutex<Instance>>) -> zbus::Result<()> {
+    let con = Connection::system().await?;
+    let proxy = LogindManagerProxy::new(&con).await?;
+    let mut signals = proxy.receive_prepare_for_sleep().await?;
+
+    debug!("Listening to logind sleep signals");
+
+    tokio::spawn(async move {
+        while let Some(signal) = signals.next().await {
+            let start = match signal.args() {
+                Ok(args) => args.start,
+                Err(error) => {
+                    error!("Could not parse PrepareForSleep signal: {error}");
+                    continue;
+                }
+            };
+
+            if start {
+                debug!("System is going to sleep");
+                continue;
+            }
+
+            info!("System resumed from suspend, applying profile again");
+            instance.lock().await.update_after_resume();
+        }
+    });
+
+    Ok(())
+}
@@ -1,4 +1,12 @@
 #[cfg(feature = "client")]
 pub mod client;
+pub mod error;
+#[cfg(feature = "server")]
+pub mod logind;
+pub mod native;
+#[cfg(feature = "server")]
+pub mod polkit;
 #[cfg(feature = "server")]
 pub mod server;
+#[cfg(feature = "server")]
+mod server_v2;
@@ -0,0 +1,153 @@
+//! Conversion between the daemon's types and native D-Bus values, used by the
+//! v2 interfaces. Structs become `a{sv}` dicts keyed by field name, lists
+//! become arrays and enums become either their variant name or a dict with the
+//! variant name as the only key. Unset options are left out, since D-Bus has
+//! no null value
+
+use std::collections::HashMap;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Number, Value as Json};
+use zbus::zvariant::{Array, Dict, OwnedValue, Signature, Value};
+
+/// Version of the D-Bus API, exposed as the `ApiVersion` property of the v2
+/// interfaces
+pub const API_VERSION: u32 = 2;
+
+/// D-Bus `a{sv}`
+pub type NativeDict = HashMap<String, OwnedValue>;
+
+/// Converts a type that serializes to a map, such as a struct, to a dict
+pub fn to_dict(value: &impl Serialize) -> NativeDict {
+    match serde_json::to_value(value).expect("Could not serialize value") {
+        Json::Object(map) => map
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, to_owned(json_to_value(value)?))))
+            .collect(),
+        other => panic!("Expected a map, got {other}"),
+    }
+}
+
+pub fn to_dicts<T: Serialize>(values: &[T]) -> Vec<NativeDict> {
+    values.iter().map(to_dict).collect()
+}
+
+/// Converts any type to a variant. Unset values become an empty dict
+pub fn to_variant(value: &impl Serialize) -> OwnedValue {
+    let value = serde_json::to_value(value).expect("Could not serialize value");
+    to_owned(json_to_value(value).unwrap_or_else(|| Value::Dict(empty_dict())))
+}
+
+pub fn from_dict<T: DeserializeOwned>(dict: NativeDict) -> Result<T, String> {
+    let map = dict
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), value_to_json(value)?)))
+        .collect::<Result<Map<_, _>, String>>()?;
+
+    serde_json::from_value(Json::Object(map)).map_err(|error| error.to_string())
+}
+
+pub fn from_variant<T: DeserializeOwned>(value: &Value) -> Result<T, String> {
+    serde_json::from_value(value_to_json(value)?).map_err(|error| error.to_string())
+}
+
+/// Parses an enum without data from the name of its variant, e.g.
+/// "Performance"
+pub fn from_variant_name<T: DeserializeOwned>(name: &str) -> Result<T, String> {
+    serde_json::from_value(Json::String(name.to_string())).map_err(|error| error.to_string())
+}
+
+fn json_to_value(json: Json) -> Option<Value<'static>> {
+    Some(match json {
+        Json::Null => return None,
+        Json::Bool(value) => Value::Bool(value),
+        Json::Number(number) => {
+            if let Some(number) = number.as_i64() {
+                Value::I64(number)
+            } else if let Some(number) = number.as_u64() {
+                Value::U64(number)
+            } else {
+                Value::F64(number.as_f64().unwrap_or_default())
+            }
+        }
+        Json::String(value) => Value::from(value),
+        Json::Array(items) => {
+            let mut array = Array::new(variant_signature());
+            for item in items.into_iter().filter_map(json_to_value) {
+                array
+                    .append(Value::Value(Box::new(item)))
+                    .expect("Arrays of variants accept any value");
+            }
+            Value::Array(array)
+        }
+        Json::Object(map) => {
+            let mut dict = empty_dict();
+            for (key, value) in map {
+                if let Some(value) = json_to_value(value) {
+                    dict.append(Value::from(key), Value::Value(Box::new(value)))
+                        .expect("Dicts of variants accept any value");
+                }
+            }
+            Value::Dict(dict)
+        }
+    })
+}
+
+fn value_to_json(value: &Value) -> Result<Json, String> {
+    Ok(match value {
+        Value::U8(number) => (*number).into(),
+        Value::Bool(value) => Json::Bool(*value),
+        Value::I16(number) => (*number).into(),
+        Value::U16(number) => (*number).into(),
+        Value::I32(number) => (*number).into(),
+        Value::U32(number) => (*number).into(),
+        Value::I64(number) => (*number).into(),
+        Value::U64(number) => (*number).into(),
+        Value::F64(number) => Number::from_f64(*number).map_or(Json::Null, Json::Number),
+        Value::Str(value) => Json::String(value.to_string()),
+        Value::Signature(value) => Json::String(value.to_string()),
+        Value::ObjectPath(value) => Json::String(value.to_string()),
+        Value::Value(value) => value_to_json(value)?,
+        Value::Array(array) => Json::Array(
+            array
+                .inner()
+                .iter()
+                .map(value_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Dict(dict) => Json::Object(
+            dict.iter()
+                .map(|(key, value)| {
+                    let key = match value_to_json(key)? {
+                        Json::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    Ok((key, value_to_json(value)?))
+                })
+                .collect::<Result<_, String>>()?,
+        ),
+        Value::Structure(structure) => Json::Array(
+            structure
+                .fields()
+                .iter()
+                .map(value_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        _ => return Err(format!("Unsupported value {value:?}")),
+    })
+}
+
+fn to_owned(value: Value<'static>) -> OwnedValue {
+    OwnedValue::try_from(value).expect("Values without file descriptors can always be owned")
+}
+
+fn empty_dict() -> Dict<'static, 'static> {
+    Dict::new(
+        Signature::from_static_str_unchecked("s"),
+        variant_signature(),
+    )
+}
+
+fn variant_signature() -> Signature<'static> {
+    Signature::from_static_str_unchecked("v")
+}
@@ -0,0 +1,117 @@
+use std::{collections::HashMap, fmt::Display};
+
+use log::{debug, warn};
+use zbus::{fdo, message::Header, names::BusName, proxy, zvariant::Value, Connection};
+
+use super::error::{self, ControlDBusError};
+
+/// Polkit action for applying profiles and overriding the active one
+pub const SWITCH_PROFILE_ACTION: &str = "io.github.thealexdev23.power_daemon.switch-profile";
+/// Polkit action for changing the config and creating, editing or removing
+/// profiles
+pub const EDIT_PROFILES_ACTION: &str = "io.github.thealexdev23.power_daemon.edit-profiles";
+
+/// Lets the polkit agent of the caller ask for a password if needed
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+#[proxy(
+    default_service = "org.freedesktop.PolicyKit1",
+    interface = "org.freedesktop.PolicyKit1.Authority",
+    default_path = "/org/freedesktop/PolicyKit1/Authority"
+)]
+trait PolkitAuthority {
+    /// Returns whether the subject is authorized, whether it could be after
+    /// authenticating and additional details
+    #[allow(clippy::type_complexity)]
+    fn check_authorization(
+        &self,
+        subject: &(&str, HashMap<&str, Value<'_>>),
+        action_id: &str,
+        details: &HashMap<&str, &str>,
+        flags: u32,
+        cancellation_id: &str,
+    ) -> zbus::Result<(bool, bool, HashMap<String, String>)>;
+}
+
+/// What a control method does, and so which polkit action guards it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    SwitchProfile,
+    EditProfiles,
+}
+
+impl Action {
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::SwitchProfile => SWITCH_PROFILE_ACTION,
+            Action::EditProfiles => EDIT_PROFILES_ACTION,
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::SwitchProfile => write!(f, "switch profiles"),
+            Action::EditProfiles => write!(f, "edit profiles"),
+        }
+    }
+}
+
+/// Checks that the sender of the message may perform `action`. If polkit is
+/// not available only root is allowed to
+pub async fn authorize(
+    connection: &Connection,
+    header: &Header<'_>,
+    action: Action,
+) -> error::Result<()> {
+    let sender = header
+        .sender()
+        .ok_or_else(|| ControlDBusError::AccessDenied("Message has no sender".to_string()))?;
+
+    let authorized = match check_authorization(connection, sender, action).await {
+        Ok(authorized) => authorized,
+        Err(error) => {
+            warn!("Could not check authorization with polkit, only allowing root: {error}");
+            fdo::DBusProxy::new(connection)
+                .await?
+                .get_connection_unix_user(BusName::from(sender.to_owned()))
+                .await?
+                == 0
+        }
+    };
+
+    debug!("{sender} authorized to {action}: {authorized}");
+
+    if authorized {
+        Ok(())
+    } else {
+        Err(ControlDBusError::AccessDenied(format!(
+            "Not authorized to {action}"
+        )))
+    }
+}
+
+async fn check_authorization(
+    connection: &Connection,
+    sender: &str,
+    action: Action,
+) -> zbus::Result<bool> {
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name", Value::from(sender))]),
+    );
+
+    let (authorized, _, _) = PolkitAuthorityProxy::new(connection)
+        .await?
+        .check_authorization(
+            &subject,
+            action.id(),
+            &HashMap::new(),
+            ALLOW_USER_INTERACTION,
+            "",
+        )
+        .await?;
+
+    Ok(authorized)
+}
@@ -1,39 +1,159 @@
-use log::{debug, error, info, trace};
+use std::sync::Arc;
 
-use tokio::sync::Mutex;
-use zbus::{conn::Builder, interface, Connection, Error};
+use log::{debug, error, info, trace, warn};
+use serde::de::DeserializeOwned;
 
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver},
+    Mutex,
+};
+use zbus::{
+    conn::Builder, interface, message::Header, object_server::InterfaceRef, Connection, Error,
+    SignalContext,
+};
+
+use super::{
+    error::{self, ControlDBusError},
+    native::to_dict,
+    polkit::{authorize, Action},
+    server_v2::{ControlServerV2, SystemInfoServerV2},
+};
 use crate::{
     systeminfo::{CPUInfo, SystemInfo},
-    FirmwareInfo, GpuInfo, Instance, OptionalFeaturesInfo, PCIInfo, SATAInfo, USBInfo,
+    BatteryInfo, ControlError, FirmwareInfo, GpuInfo, Instance, OptionalFeaturesInfo, PCIInfo,
+    Profile, ProfileBundle, SATAInfo, StateChange, USBInfo,
 };
 
 pub struct CommunicationServer {
     _con: Connection,
 }
 
+const CONTROL_PATH: &str = "/io/github/thealexdev23/power_daemon/control";
+
 impl CommunicationServer {
-    pub async fn new(instance: Instance) -> Result<CommunicationServer, Error> {
+    pub async fn new(instance: Arc<Mutex<Instance>>) -> Result<CommunicationServer, Error> {
         debug!("Initializing communications server");
         let con = Builder::system()?
             .name("io.github.thealexdev23.power_daemon")?
             .serve_at(
-                "/io/github/thealexdev23/power_daemon/control",
+                CONTROL_PATH,
                 ControlServer {
-                    instance: instance.into(),
+                    instance: instance.clone(),
+                },
+            )?
+            .serve_at(
+                CONTROL_PATH,
+                ControlServerV2 {
+                    instance: instance.clone(),
                 },
             )?
             .serve_at(
                 "/io/github/thealexdev23/power_daemon/system_info",
                 SystemInfoServer,
             )?
+            .serve_at(
+                "/io/github/thealexdev23/power_daemon/system_info",
+                SystemInfoServerV2,
+            )?
             .build()
             .await?;
         debug!("Finished setting up communications server connection");
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        instance.lock().await.set_change_listener(move |change| {
+            // The receiver only goes away with the connection
+            let _ = sender.send(change);
+        });
+        let control = con
+            .object_server()
+            .interface::<_, ControlServer>(CONTROL_PATH)
+            .await?;
+        let control_v2 = con
+            .object_server()
+            .interface::<_, ControlServerV2>(CONTROL_PATH)
+            .await?;
+        tokio::spawn(emit_state_changes(control, control_v2, receiver));
+
         Ok(CommunicationServer { _con: con })
     }
 }
 
+/// Turns the changes of the daemon's state into signals, so that clients
+/// don't need to poll
+async fn emit_state_changes(
+    control: InterfaceRef<ControlServer>,
+    control_v2: InterfaceRef<ControlServerV2>,
+    mut receiver: UnboundedReceiver<StateChange>,
+) {
+    while let Some(change) = receiver.recv().await {
+        trace!(target: "D-BUS", "Emitting {change:?}");
+        if let Err(error) = emit_state_change(&control, change.clone()).await {
+            warn!(target: "D-BUS", "Could not emit state change signal: {error}");
+        }
+        if let Err(error) = emit_state_change_v2(&control_v2, change).await {
+            warn!(target: "D-BUS", "Could not emit v2 state change signal: {error}");
+        }
+    }
+}
+
+async fn emit_state_change(
+    control: &InterfaceRef<ControlServer>,
+    change: StateChange,
+) -> zbus::Result<()> {
+    let ctxt = control.signal_context();
+    let server = control.get().await;
+
+    match change {
+        StateChange::ActiveProfile(profile_name) => {
+            ControlServer::active_profile_changed(ctxt, &profile_name).await?;
+            server.active_profile_name_changed(ctxt).await
+        }
+        StateChange::Profiles => {
+            ControlServer::profiles_changed(ctxt).await?;
+            server.profiles_info_changed(ctxt).await
+        }
+        StateChange::Config => {
+            ControlServer::emit_config_changed(ctxt).await?;
+            server.config_changed(ctxt).await
+        }
+        StateChange::Override(profile_override) => {
+            let profile_override = serde_json::to_string(&profile_override).unwrap();
+            ControlServer::override_changed(ctxt, &profile_override).await?;
+            server.profile_override_changed(ctxt).await
+        }
+    }
+}
+
+async fn emit_state_change_v2(
+    control: &InterfaceRef<ControlServerV2>,
+    change: StateChange,
+) -> zbus::Result<()> {
+    let ctxt = control.signal_context();
+    let server = control.get().await;
+
+    match change {
+        StateChange::ActiveProfile(profile_name) => {
+            ControlServerV2::active_profile_changed(ctxt, &profile_name).await?;
+            server.active_profile_name_changed(ctxt).await
+        }
+        StateChange::Profiles => {
+            ControlServerV2::profiles_changed(ctxt).await?;
+            server.profiles_info_changed(ctxt).await
+        }
+        StateChange::Config => {
+            ControlServerV2::emit_config_changed(ctxt).await?;
+            server.config_changed(ctxt).await
+        }
+        StateChange::Override(profile_override) => {
+            let profile_override = profile_override
+                .map(|profile_override| to_dict(&profile_override))
+                .unwrap_or_default();
+            ControlServerV2::override_changed(ctxt, profile_override).await?;
+            server.profile_override_changed(ctxt).await
+        }
+    }
+}
+
 struct SystemInfoServer;
 
 #[interface(name = "io.github.thealexdev23.power_daemon.system_info")]
@@ -66,13 +186,17 @@ impl SystemInfoServer {
         serde_json::to_string(&GpuInfo::obtain()).unwrap()
     }
 
+    async fn get_battery_info(&self) -> String {
+        serde_json::to_string(&BatteryInfo::obtain()).unwrap()
+    }
+
     async fn get_optional_features_info(&self) -> String {
         serde_json::to_string(&OptionalFeaturesInfo::obtain()).unwrap()
     }
 }
 
 struct ControlServer {
-    instance: Mutex<Instance>,
+    instance: Arc<Mutex<Instance>>,
 }
 
 #[interface(name = "io.github.thealexdev23.power_daemon.control")]
@@ -86,127 +210,465 @@ impl ControlServer {
         serde_json::to_string(&self.instance.lock().await.profiles_info).unwrap()
     }
 
-    async fn update_full(&mut self) {
+    async fn update_full(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "update_full");
-        self.instance.get_mut().update_full();
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance.lock().await.update_full();
+        Ok(())
     }
-    async fn update_reduced(&mut self, reduced_update: String) {
+    async fn update_reduced(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        reduced_update: String,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "update_reduced: {reduced_update}");
-        let reduced_update = match serde_json::from_str(&reduced_update) {
-            Ok(reduced_update) => reduced_update,
-            Err(error) => {
-                error!("Could not parse reduced update: {error}");
-                return;
-            }
-        };
-        self.instance.get_mut().update_reduced(reduced_update);
+        let reduced_update = parse_json(&reduced_update, "reduced update")?;
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance.lock().await.update_reduced(reduced_update);
+        Ok(())
     }
 
-    async fn update_config(&mut self, updated: String) {
+    async fn update_config(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        updated: String,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "update_config: {updated}");
-        match serde_json::from_str(&updated) {
-            Ok(conf) => {
-                self.instance.get_mut().update_config(conf);
-            }
-            Err(error) => {
-                error!("Could not parse new requested config: {error}")
-            }
-        }
+        let config = parse_json(&updated, "config")?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance.lock().await.update_config(config);
+        Ok(())
+    }
+
+    async fn set_ac_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "set_ac_profile: {profile_name}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        Ok(self.instance.lock().await.set_ac_profile(profile_name)?)
+    }
+    async fn set_bat_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "set_bat_profile: {profile_name}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        Ok(self.instance.lock().await.set_bat_profile(profile_name)?)
+    }
+    async fn set_persistent_profile_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "set_persistent_profile_override: {profile_name}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        Ok(self
+            .instance
+            .lock()
+            .await
+            .set_persistent_profile_override(profile_name)?)
+    }
+    async fn clear_persistent_profile_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "clear_persistent_profile_override");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance
+            .lock()
+            .await
+            .clear_persistent_profile_override();
+        Ok(())
     }
 
-    async fn get_active_profile_name(&mut self) -> String {
+    async fn get_active_profile_name(&self) -> String {
         debug!(target: "D-BUS", "get_active_profile_name");
-        self.instance.get_mut().get_active_profile_name()
+        self.instance.lock().await.get_active_profile_name()
+    }
+    async fn get_profile_pick_reason(&self) -> String {
+        debug!(target: "D-BUS", "get_profile_pick_reason");
+        serde_json::to_string(&self.instance.lock().await.get_profile_pick_reason()).unwrap()
+    }
+    async fn get_apply_report(&self) -> String {
+        debug!(target: "D-BUS", "get_apply_report");
+        serde_json::to_string(&self.instance.lock().await.get_apply_report()).unwrap()
+    }
+    async fn get_load_errors(&self) -> String {
+        debug!(target: "D-BUS", "get_load_errors");
+        serde_json::to_string(&self.instance.lock().await.get_load_errors()).unwrap()
+    }
+    async fn restore_original_values(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "restore_original_values");
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance.lock().await.restore_original_values();
+        Ok(())
     }
 
-    async fn create_profile(&mut self, profile_type: String) {
+    async fn get_drift(&self) -> String {
+        debug!(target: "D-BUS", "get_drift");
+        serde_json::to_string(&self.instance.lock().await.detect_drift()).unwrap()
+    }
+    async fn enforce_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<String> {
+        info!(target: "D-BUS", "enforce_profile");
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        Ok(serde_json::to_string(&self.instance.lock().await.enforce_profile()).unwrap())
+    }
+
+    async fn create_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_type: String,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "create_profile: {profile_type}");
-        match serde_json::from_str(&profile_type) {
-            Ok(profile_type) => self.instance.get_mut().create_profile(profile_type),
-            Err(error) => {
-                error!("Could not parse new requested profile type: {error}")
-            }
-        }
+        let profile_type = parse_json(&profile_type, "profile type")?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance.lock().await.create_profile(profile_type);
+        Ok(())
+    }
+    async fn diff_profiles(&self, from: String, to: String) -> error::Result<String> {
+        debug!(target: "D-BUS", "diff_profiles: {from} {to}");
+        let changes = self
+            .instance
+            .lock()
+            .await
+            .diff_profiles(&from, &to)
+            .ok_or_else(|| {
+                ControlDBusError::ProfileNotFound(format!("Profile {from} or {to} does not exist"))
+            })?;
+        Ok(serde_json::to_string(&changes).unwrap())
     }
-    async fn reset_profile(&mut self, idx: u32) {
+    async fn diff_profile_live(&self, profile_name: String) -> error::Result<String> {
+        debug!(target: "D-BUS", "diff_profile_live: {profile_name}");
+        let changes = self
+            .instance
+            .lock()
+            .await
+            .diff_profile_live(&profile_name)
+            .ok_or(ControlError::ProfileNotFound(profile_name))?;
+        Ok(serde_json::to_string(&changes).unwrap())
+    }
+    async fn export_profile(&self, profile_name: String) -> error::Result<String> {
+        info!(target: "D-BUS", "export_profile: {profile_name}");
+        Ok(self
+            .instance
+            .lock()
+            .await
+            .export_profile(&profile_name)
+            .ok_or(ControlError::ProfileNotFound(profile_name))?)
+    }
+    async fn import_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        bundle: String,
+        on_collision: String,
+        strip_hardware_specific: bool,
+    ) -> error::Result<String> {
+        info!(target: "D-BUS", "import_profile: {on_collision} {strip_hardware_specific}");
+        trace!("Bundle: {bundle}");
+
+        let on_collision = parse_json(&on_collision, "name collision handling")?;
+        // The instance parses it again, this only rejects malformed bundles
+        // before asking for authorization
+        ProfileBundle::parse(&bundle).map_err(ControlDBusError::ImportFailed)?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+
+        let result = self
+            .instance
+            .lock()
+            .await
+            .import_profile(&bundle, on_collision, strip_hardware_specific)
+            .map_err(|error| {
+                error!("Could not import profile: {error}");
+                ControlDBusError::ImportFailed(error)
+            })?;
+
+        Ok(serde_json::to_string(&result).unwrap())
+    }
+    /// Deprecated, indices change when profiles are reordered. Use
+    /// `ResetProfileById` instead
+    async fn reset_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "reset_profile: {idx}");
-        self.instance.get_mut().reset_profile(idx as usize);
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.reset_profile_by_id(header, connection, id).await
     }
-    async fn remove_profile(&mut self, idx: u32) {
+    async fn reset_profile_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "reset_profile_by_id: {id}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        Ok(instance.reset_profile(idx)?)
+    }
+    /// Deprecated, use `RemoveProfileById` instead
+    async fn remove_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "remove_profile: {idx}");
-        self.instance.get_mut().remove_profile(idx as usize);
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.remove_profile_by_id(header, connection, id).await
+    }
+    async fn remove_profile_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "remove_profile_by_id: {id}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        Ok(instance.remove_profile(idx)?)
     }
 
-    async fn swap_profiles(&mut self, idx: u32, new_idx: u32) {
+    /// Deprecated, use `SwapProfilesById` instead
+    async fn swap_profiles(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+        new_idx: u32,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "swap_profiles: {idx} with {new_idx}");
-        self.instance
-            .get_mut()
-            .swap_profile_order(idx as usize, new_idx as usize);
+        let (id, other_id) = {
+            let instance = self.instance.lock().await;
+            (
+                instance.profile_id_at(idx as usize)?,
+                instance.profile_id_at(new_idx as usize)?,
+            )
+        };
+        self.swap_profiles_by_id(header, connection, id, other_id)
+            .await
+    }
+    async fn swap_profiles_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+        other_id: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "swap_profiles_by_id: {id} with {other_id}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        let other_idx = instance.find_profile_by_id(&other_id)?;
+        Ok(instance.swap_profile_order(idx, other_idx)?)
     }
-    async fn update_profile_name(&mut self, idx: u32, new_name: String) {
+    /// Deprecated, use `UpdateProfileNameById` instead
+    async fn update_profile_name(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+        new_name: String,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "update_profile_name: {idx} with {new_name}");
-        self.instance
-            .get_mut()
-            .update_profile_name(idx as usize, new_name);
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.update_profile_name_by_id(header, connection, id, new_name)
+            .await
+    }
+    async fn update_profile_name_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+        new_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "update_profile_name_by_id: {id} with {new_name}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        Ok(instance.update_profile_name(idx, new_name)?)
     }
 
-    async fn update_profile_full(&mut self, idx: u32, updated: String) {
+    /// Deprecated, use `UpdateProfileFullById` instead
+    async fn update_profile_full(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+        updated: String,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "update_profile_full: {idx}");
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.update_profile_full_by_id(header, connection, id, updated)
+            .await
+    }
+    async fn update_profile_full_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+        updated: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "update_profile_full_by_id: {id}");
         trace!("New profile: {updated}");
 
-        match serde_json::from_str(&updated) {
-            Ok(profile) => {
-                self.instance
-                    .get_mut()
-                    .update_profile_full(idx as usize, profile);
-            }
-            Err(error) => {
-                error!("Could not parse updated profile: {error}")
-            }
-        }
+        let profile = parse_json(&updated, "updated profile")?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        instance.update_profile_full(idx, profile)?;
+        Ok(())
+    }
+    async fn validate_profile(&self, profile: String) -> error::Result<String> {
+        debug!(target: "D-BUS", "validate_profile");
+        trace!("Profile: {profile}");
+
+        let profile: Profile = parse_json(&profile, "profile to validate")?;
+        Ok(serde_json::to_string(&profile.validate(&SystemInfo::obtain())).unwrap())
     }
-    async fn update_profile_reduced(&mut self, idx: u32, updated: String, reduced_update: String) {
+    /// Deprecated, use `UpdateProfileReducedById` instead
+    async fn update_profile_reduced(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+        updated: String,
+        reduced_update: String,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "update_profile_reduced: {idx} {reduced_update}");
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.update_profile_reduced_by_id(header, connection, id, updated, reduced_update)
+            .await
+    }
+    async fn update_profile_reduced_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+        updated: String,
+        reduced_update: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "update_profile_reduced_by_id: {id} {reduced_update}");
         trace!("New profile: {updated}");
 
-        let reduced_update = match serde_json::from_str(&reduced_update) {
-            Ok(reduced_update) => reduced_update,
-            Err(error) => {
-                error!("Could not parse reduced update: {error}");
-                return;
-            }
-        };
-
-        match serde_json::from_str(&updated) {
-            Ok(profile) => {
-                self.instance.get_mut().update_profile_reduced(
-                    idx as usize,
-                    profile,
-                    reduced_update,
-                );
-            }
-            Err(error) => {
-                error!("Could not parse updated profile: {error}")
-            }
-        }
+        let reduced_update = parse_json(&reduced_update, "reduced update")?;
+        let profile = parse_json(&updated, "updated profile")?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        Ok(instance.update_profile_reduced(idx, profile, reduced_update)?)
     }
 
-    async fn get_profile_override(&mut self) -> String {
+    async fn get_profile_override(&self) -> String {
         info!(target: "D-BUS", "get_profile_override");
-        self.instance
-            .get_mut()
-            .temporary_override
-            .clone()
-            .unwrap_or_default()
+        serde_json::to_string(&self.instance.lock().await.get_profile_override()).unwrap()
     }
-    async fn set_profile_override(&mut self, profile_name: String) {
+    async fn set_profile_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "set_profile_override: {profile_name}");
-        self.instance
-            .get_mut()
-            .try_set_profile_override(profile_name);
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        Ok(self
+            .instance
+            .lock()
+            .await
+            .try_set_profile_override(profile_name)?)
+    }
+    async fn set_profile_override_until(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+        expiry: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "set_profile_override_until: {profile_name}, {expiry}");
+        let expiry = parse_json(&expiry, "override expiry")?;
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        Ok(self
+            .instance
+            .lock()
+            .await
+            .try_set_profile_override_until(profile_name, expiry)?)
     }
-    async fn remove_profile_override(&mut self) {
+    async fn remove_profile_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<()> {
         info!(target: "D-BUS", "remove_profile_override");
-        self.instance.get_mut().remove_profile_override();
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance.lock().await.remove_profile_override();
+        Ok(())
+    }
+    #[zbus(property)]
+    async fn active_profile_name(&self) -> String {
+        self.instance.lock().await.get_active_profile_name()
     }
+    /// JSON encoded `ProfilesInfo`
+    #[zbus(property)]
+    async fn profiles_info(&self) -> String {
+        serde_json::to_string(&self.instance.lock().await.profiles_info).unwrap()
+    }
+    /// JSON encoded `Config`
+    #[zbus(property)]
+    async fn config(&self) -> String {
+        serde_json::to_string(&self.instance.lock().await.config).unwrap()
+    }
+    /// JSON encoded `Option<ProfileOverride>`
+    #[zbus(property)]
+    async fn profile_override(&self) -> String {
+        serde_json::to_string(&self.instance.lock().await.get_profile_override()).unwrap()
+    }
+
+    #[zbus(signal)]
+    async fn active_profile_changed(
+        ctxt: &SignalContext<'_>,
+        profile_name: &str,
+    ) -> zbus::Result<()>;
+    #[zbus(signal)]
+    async fn profiles_changed(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+    // Named explicitly since config_changed is taken by the Config property
+    #[zbus(signal, name = "ConfigChanged")]
+    async fn emit_config_changed(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+    /// Carries a JSON encoded `Option<ProfileOverride>`
+    #[zbus(signal)]
+    async fn override_changed(ctxt: &SignalContext<'_>, profile_override: &str)
+        -> zbus::Result<()>;
+}
+
+/// Parses a JSON encoded argument
+fn parse_json<T: DeserializeOwned>(json: &str, what: &str) -> error::Result<T> {
+    serde_json::from_str(json).map_err(|error| {
+        error!("Could not parse {what}: {error}");
+        ControlDBusError::InvalidArgs(format!("Could not parse {what}: {error}"))
+    })
 }
@@ -0,0 +1,561 @@
+use std::sync::Arc;
+
+use log::{debug, info, trace};
+
+use tokio::sync::Mutex;
+use zbus::{interface, message::Header, zvariant::OwnedValue, Connection, SignalContext};
+
+use super::{
+    error::{self, ControlDBusError},
+    native::{
+        from_dict, from_variant, from_variant_name, to_dict, to_dicts, to_variant, NativeDict,
+        API_VERSION,
+    },
+    polkit::{authorize, Action},
+};
+use crate::{
+    systeminfo::{CPUInfo, SystemInfo},
+    BatteryInfo, ControlError, FirmwareInfo, GpuInfo, Instance, OptionalFeaturesInfo, PCIInfo,
+    Profile, ProfileBundle, SATAInfo, USBInfo,
+};
+
+/// Same as the JSON system info interface, with native D-Bus types
+pub(super) struct SystemInfoServerV2;
+
+#[interface(name = "io.github.thealexdev23.power_daemon.system_info.v2")]
+impl SystemInfoServerV2 {
+    async fn get_system_info(&self) -> NativeDict {
+        to_dict(&SystemInfo::obtain())
+    }
+
+    async fn get_cpu_info(&self) -> NativeDict {
+        to_dict(&CPUInfo::obtain())
+    }
+
+    async fn get_pci_info(&self) -> NativeDict {
+        to_dict(&PCIInfo::obtain())
+    }
+
+    async fn get_usb_info(&self) -> NativeDict {
+        to_dict(&USBInfo::obtain())
+    }
+
+    async fn get_sata_info(&self) -> NativeDict {
+        to_dict(&SATAInfo::obtain())
+    }
+
+    async fn get_firmware_info(&self) -> NativeDict {
+        to_dict(&FirmwareInfo::obtain())
+    }
+
+    async fn get_gpu_info(&self) -> NativeDict {
+        to_dict(&GpuInfo::obtain())
+    }
+
+    async fn get_battery_info(&self) -> NativeDict {
+        to_dict(&BatteryInfo::obtain())
+    }
+
+    async fn get_optional_features_info(&self) -> NativeDict {
+        to_dict(&OptionalFeaturesInfo::obtain())
+    }
+
+    #[zbus(property)]
+    async fn api_version(&self) -> u32 {
+        API_VERSION
+    }
+}
+
+/// Same as the JSON control interface, with native D-Bus types. Arguments
+/// that cannot be parsed are rejected instead of ignored
+pub(super) struct ControlServerV2 {
+    pub(super) instance: Arc<Mutex<Instance>>,
+}
+
+#[interface(name = "io.github.thealexdev23.power_daemon.control.v2")]
+impl ControlServerV2 {
+    async fn get_config(&self) -> NativeDict {
+        info!(target: "D-BUS", "v2 get_config");
+        to_dict(&self.instance.lock().await.config)
+    }
+    async fn get_profiles_info(&self) -> NativeDict {
+        info!(target: "D-BUS", "v2 get_profiles_info");
+        to_dict(&self.instance.lock().await.profiles_info)
+    }
+
+    async fn update_full(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_full");
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance.lock().await.update_full();
+        Ok(())
+    }
+    async fn update_reduced(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        reduced_update: OwnedValue,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_reduced: {reduced_update:?}");
+        let reduced_update =
+            from_variant(&reduced_update).map_err(invalid_args("reduced update"))?;
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance.lock().await.update_reduced(reduced_update);
+        Ok(())
+    }
+
+    async fn update_config(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        updated: NativeDict,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_config");
+        trace!("New config: {updated:?}");
+        let config = from_dict(updated).map_err(invalid_args("config"))?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance.lock().await.update_config(config);
+        Ok(())
+    }
+    async fn set_ac_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 set_ac_profile: {profile_name}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance.lock().await.set_ac_profile(profile_name)?;
+        Ok(())
+    }
+    async fn set_bat_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 set_bat_profile: {profile_name}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance.lock().await.set_bat_profile(profile_name)?;
+        Ok(())
+    }
+    async fn set_persistent_profile_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 set_persistent_profile_override: {profile_name}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance
+            .lock()
+            .await
+            .set_persistent_profile_override(profile_name)?;
+        Ok(())
+    }
+    async fn clear_persistent_profile_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 clear_persistent_profile_override");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance
+            .lock()
+            .await
+            .clear_persistent_profile_override();
+        Ok(())
+    }
+
+    async fn get_active_profile_name(&self) -> String {
+        debug!(target: "D-BUS", "v2 get_active_profile_name");
+        self.instance.lock().await.get_active_profile_name()
+    }
+    async fn get_profile_pick_reason(&self) -> OwnedValue {
+        debug!(target: "D-BUS", "v2 get_profile_pick_reason");
+        to_variant(&self.instance.lock().await.get_profile_pick_reason())
+    }
+    async fn get_apply_report(&self) -> NativeDict {
+        debug!(target: "D-BUS", "v2 get_apply_report");
+        to_dict(&self.instance.lock().await.get_apply_report())
+    }
+    async fn get_load_errors(&self) -> Vec<NativeDict> {
+        debug!(target: "D-BUS", "v2 get_load_errors");
+        to_dicts(&self.instance.lock().await.get_load_errors())
+    }
+    async fn restore_original_values(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 restore_original_values");
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance.lock().await.restore_original_values();
+        Ok(())
+    }
+
+    async fn get_drift(&self) -> Vec<NativeDict> {
+        debug!(target: "D-BUS", "v2 get_drift");
+        to_dicts(&self.instance.lock().await.detect_drift())
+    }
+    async fn enforce_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<Vec<NativeDict>> {
+        info!(target: "D-BUS", "v2 enforce_profile");
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        Ok(to_dicts(&self.instance.lock().await.enforce_profile()))
+    }
+
+    /// `profile_type` is the name of a `DefaultProfileType`, e.g. "Balanced"
+    async fn create_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_type: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 create_profile: {profile_type}");
+        let profile_type =
+            from_variant_name(&profile_type).map_err(invalid_args("profile type"))?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+        self.instance.lock().await.create_profile(profile_type);
+        Ok(())
+    }
+    async fn diff_profiles(&self, from: String, to: String) -> error::Result<Vec<NativeDict>> {
+        debug!(target: "D-BUS", "v2 diff_profiles: {from} {to}");
+        let changes = self
+            .instance
+            .lock()
+            .await
+            .diff_profiles(&from, &to)
+            .ok_or_else(|| {
+                ControlDBusError::ProfileNotFound(format!("Profile {from} or {to} does not exist"))
+            })?;
+        Ok(to_dicts(&changes))
+    }
+    async fn diff_profile_live(&self, profile_name: String) -> error::Result<Vec<NativeDict>> {
+        debug!(target: "D-BUS", "v2 diff_profile_live: {profile_name}");
+        let changes = self
+            .instance
+            .lock()
+            .await
+            .diff_profile_live(&profile_name)
+            .ok_or(ControlError::ProfileNotFound(profile_name))?;
+        Ok(to_dicts(&changes))
+    }
+    /// Returns the TOML bundle of the profile
+    async fn export_profile(&self, profile_name: String) -> error::Result<String> {
+        info!(target: "D-BUS", "v2 export_profile: {profile_name}");
+        Ok(self
+            .instance
+            .lock()
+            .await
+            .export_profile(&profile_name)
+            .ok_or(ControlError::ProfileNotFound(profile_name))?)
+    }
+    /// `on_collision` is the name of a `NameCollision`, e.g. "Rename"
+    async fn import_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        bundle: String,
+        on_collision: String,
+        strip_hardware_specific: bool,
+    ) -> error::Result<NativeDict> {
+        info!(target: "D-BUS", "v2 import_profile: {on_collision} {strip_hardware_specific}");
+        trace!("Bundle: {bundle}");
+
+        let on_collision =
+            from_variant_name(&on_collision).map_err(invalid_args("name collision handling"))?;
+        // The instance parses it again, this only rejects malformed bundles
+        // before asking for authorization
+        ProfileBundle::parse(&bundle).map_err(ControlDBusError::ImportFailed)?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+
+        let result = self
+            .instance
+            .lock()
+            .await
+            .import_profile(&bundle, on_collision, strip_hardware_specific)
+            .map_err(ControlDBusError::ImportFailed)?;
+
+        Ok(to_dict(&result))
+    }
+    /// Deprecated, indices change when profiles are reordered. Use
+    /// `ResetProfileById` instead
+    async fn reset_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 reset_profile: {idx}");
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.reset_profile_by_id(header, connection, id).await
+    }
+    async fn reset_profile_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 reset_profile_by_id: {id}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        Ok(instance.reset_profile(idx)?)
+    }
+    /// Deprecated, use `RemoveProfileById` instead
+    async fn remove_profile(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 remove_profile: {idx}");
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.remove_profile_by_id(header, connection, id).await
+    }
+    async fn remove_profile_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 remove_profile_by_id: {id}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        Ok(instance.remove_profile(idx)?)
+    }
+
+    /// Deprecated, use `SwapProfilesById` instead
+    async fn swap_profiles(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+        new_idx: u32,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 swap_profiles: {idx} with {new_idx}");
+        let (id, other_id) = {
+            let instance = self.instance.lock().await;
+            (
+                instance.profile_id_at(idx as usize)?,
+                instance.profile_id_at(new_idx as usize)?,
+            )
+        };
+        self.swap_profiles_by_id(header, connection, id, other_id)
+            .await
+    }
+    async fn swap_profiles_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+        other_id: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 swap_profiles_by_id: {id} with {other_id}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        let other_idx = instance.find_profile_by_id(&other_id)?;
+        Ok(instance.swap_profile_order(idx, other_idx)?)
+    }
+    /// Deprecated, use `UpdateProfileNameById` instead
+    async fn update_profile_name(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+        new_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_profile_name: {idx} with {new_name}");
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.update_profile_name_by_id(header, connection, id, new_name)
+            .await
+    }
+    async fn update_profile_name_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+        new_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_profile_name_by_id: {id} with {new_name}");
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        Ok(instance.update_profile_name(idx, new_name)?)
+    }
+
+    /// Deprecated, use `UpdateProfileFullById` instead
+    async fn update_profile_full(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+        updated: NativeDict,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_profile_full: {idx}");
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.update_profile_full_by_id(header, connection, id, updated)
+            .await
+    }
+    async fn update_profile_full_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+        updated: NativeDict,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_profile_full_by_id: {id}");
+        trace!("New profile: {updated:?}");
+
+        let profile = from_dict(updated).map_err(invalid_args("profile"))?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        instance.update_profile_full(idx, profile)?;
+        Ok(())
+    }
+    async fn validate_profile(&self, profile: NativeDict) -> error::Result<Vec<NativeDict>> {
+        debug!(target: "D-BUS", "v2 validate_profile");
+        trace!("Profile: {profile:?}");
+
+        let profile: Profile = from_dict(profile).map_err(invalid_args("profile"))?;
+        Ok(to_dicts(&profile.validate(&SystemInfo::obtain())))
+    }
+    /// Deprecated, use `UpdateProfileReducedById` instead
+    async fn update_profile_reduced(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        idx: u32,
+        updated: NativeDict,
+        reduced_update: OwnedValue,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_profile_reduced: {idx} {reduced_update:?}");
+        let id = self.instance.lock().await.profile_id_at(idx as usize)?;
+        self.update_profile_reduced_by_id(header, connection, id, updated, reduced_update)
+            .await
+    }
+    async fn update_profile_reduced_by_id(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        id: String,
+        updated: NativeDict,
+        reduced_update: OwnedValue,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 update_profile_reduced_by_id: {id} {reduced_update:?}");
+        trace!("New profile: {updated:?}");
+
+        let reduced_update =
+            from_variant(&reduced_update).map_err(invalid_args("reduced update"))?;
+        let profile = from_dict(updated).map_err(invalid_args("profile"))?;
+        authorize(connection, &header, Action::EditProfiles).await?;
+        let mut instance = self.instance.lock().await;
+        let idx = instance.find_profile_by_id(&id)?;
+        Ok(instance.update_profile_reduced(idx, profile, reduced_update)?)
+    }
+
+    /// Returns an empty dict if there is no override
+    async fn get_profile_override(&self) -> NativeDict {
+        info!(target: "D-BUS", "v2 get_profile_override");
+        self.instance
+            .lock()
+            .await
+            .get_profile_override()
+            .map(|profile_override| to_dict(&profile_override))
+            .unwrap_or_default()
+    }
+    async fn set_profile_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 set_profile_override: {profile_name}");
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance
+            .lock()
+            .await
+            .try_set_profile_override(profile_name)?;
+        Ok(())
+    }
+    /// `expiry` is an `OverrideExpiry`, either the name of the variant or a
+    /// dict such as {"Duration": {"secs": 3600, "nanos": 0}}
+    async fn set_profile_override_until(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        profile_name: String,
+        expiry: OwnedValue,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 set_profile_override_until: {profile_name}, {expiry:?}");
+        let expiry = from_variant(&expiry).map_err(invalid_args("override expiry"))?;
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance
+            .lock()
+            .await
+            .try_set_profile_override_until(profile_name, expiry)?;
+        Ok(())
+    }
+    async fn remove_profile_override(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+    ) -> error::Result<()> {
+        info!(target: "D-BUS", "v2 remove_profile_override");
+        authorize(connection, &header, Action::SwitchProfile).await?;
+        self.instance.lock().await.remove_profile_override();
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn api_version(&self) -> u32 {
+        API_VERSION
+    }
+    #[zbus(property)]
+    async fn active_profile_name(&self) -> String {
+        self.instance.lock().await.get_active_profile_name()
+    }
+    #[zbus(property)]
+    async fn profiles_info(&self) -> NativeDict {
+        to_dict(&self.instance.lock().await.profiles_info)
+    }
+    #[zbus(property)]
+    async fn config(&self) -> NativeDict {
+        to_dict(&self.instance.lock().await.config)
+    }
+    /// Empty if there is no override
+    #[zbus(property)]
+    async fn profile_override(&self) -> NativeDict {
+        self.get_profile_override().await
+    }
+
+    #[zbus(signal)]
+    pub(super) async fn active_profile_changed(
+        ctxt: &SignalContext<'_>,
+        profile_name: &str,
+    ) -> zbus::Result<()>;
+    #[zbus(signal)]
+    pub(super) async fn profiles_changed(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+    #[zbus(signal, name = "ConfigChanged")]
+    pub(super) async fn emit_config_changed(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+    /// Empty if the override was removed
+    #[zbus(signal)]
+    pub(super) async fn override_changed(
+        ctxt: &SignalContext<'_>,
+        profile_override: NativeDict,
+    ) -> zbus::Result<()>;
+}
+
+fn invalid_args(what: &'static str) -> impl Fn(String) -> ControlDBusError {
+    move |error| ControlDBusError::InvalidArgs(format!("Could not parse {what}: {error}"))
+}
@@ -1,65 +1,185 @@
-use log::{debug, warn};
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 
-use crate::profiles_generator::DefaultProfileType;
+use crate::{
+    helpers,
+    migration::{self, CONFIG_SCHEMA_VERSION},
+    profiles_generator::DefaultProfileType,
+    report::SettingsGroup,
+};
 
 use itertools::Itertools;
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub struct Config {
+    /// Version of the format of the config, see `migration`
+    pub schema_version: u32,
+
     pub ac_profile: String,
     pub bat_profile: String,
 
     pub profile_override: Option<String>,
 
+    /// Ordered list of rules, the first one whose conditions are all met
+    /// picks the active profile. If none match the daemon falls back to
+    /// `ac_profile`/`bat_profile`
+    pub profile_rules: Vec<ProfileRule>,
+
+    /// Seconds between checks for values of the active profile that were
+    /// changed outside of the daemon. Drifted settings groups get applied
+    /// again. Disabled if None or 0
+    pub drift_check_interval: Option<u32>,
+
+    /// Settings groups of the active profile that get applied again after
+    /// resuming from suspend, since the firmware resets many values. All of
+    /// them if None
+    pub resume_groups: Option<Vec<SettingsGroup>>,
+
     pub profiles: Vec<String>,
 }
 
 impl Config {
     pub fn create_default() -> Config {
         Config {
+            schema_version: CONFIG_SCHEMA_VERSION,
+
             ac_profile: DefaultProfileType::Performance.get_name(),
             bat_profile: DefaultProfileType::Powersave.get_name(),
 
             profile_override: None,
 
+            profile_rules: Vec::new(),
+
+            drift_check_interval: None,
+
+            resume_groups: None,
+
             profiles: DefaultProfileType::get_name_of_all(),
         }
     }
     pub fn create_empty() -> Config {
         Config {
+            schema_version: CONFIG_SCHEMA_VERSION,
+
             ac_profile: "Default".to_string(),
             bat_profile: "Default".to_string(),
 
             profile_override: None,
 
+            profile_rules: Vec::new(),
+
+            drift_check_interval: None,
+
+            resume_groups: None,
+
             profiles: vec!["Default".to_string()],
         }
     }
 
-    /// Will attempt to parse `contentent`, if it fails will merge `content`
-    /// with the deafult config and attempt to merge that too
-    pub fn parse_or_default(content: &str) -> Config {
-        match toml::from_str::<Config>(content) {
-            Ok(c) => c,
-            Err(_) => {
-                warn!("Failed to parse config, attempting to migrate to newer version");
+    /// Parses a config file, migrating configs of older versions
+    pub fn parse(content: &str) -> Result<Config, String> {
+        let (mut config, report) = migration::migrate_config(content)?;
+
+        if report.is_migrated() {
+            info!(
+                "Migrated config from version {} to {}",
+                report.from_version, report.to_version
+            );
+            for change in report.changes.iter() {
+                debug!("{change}");
+            }
+        }
+
+        config.profiles = config.profiles.into_iter().unique().collect();
 
-                let default_content = toml::to_string(&Config::create_default()).unwrap();
-                let merged = serde_toml_merge::merge(
-                    default_content.parse::<toml::Value>().unwrap(),
-                    content.parse::<toml::Value>().unwrap(),
-                )
-                .expect("Could not merge default config and user config");
+        Ok(config)
+    }
+}
 
-                debug!("Merged config: {merged:?}");
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ProfileRule {
+    /// Name of the profile that will be picked if the rule matches
+    pub profile: String,
+    /// All conditions need to be met for the rule to match. A rule without
+    /// conditions always matches
+    pub conditions: Vec<RuleCondition>,
+}
 
-                let mut config = Config::deserialize(merged).unwrap();
+impl ProfileRule {
+    pub fn matches(&self) -> bool {
+        self.conditions.iter().all(RuleCondition::is_met)
+    }
+}
 
-                config.profiles = config.profiles.into_iter().unique().collect();
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub enum RuleCondition {
+    OnAC,
+    OnBattery,
+    /// Combined charge percentage of the system batteries is below the value.
+    /// Never met on systems without batteries
+    BatteryBelow(u8),
+    /// Combined charge percentage of the system batteries is above the value.
+    /// Never met on systems without batteries
+    BatteryAbove(u8),
+    /// Local time is inside the window, both ends formatted as HH:MM. Windows
+    /// where `start` is after `end` wrap around midnight
+    TimeWindow {
+        start: String,
+        end: String,
+    },
+    /// Temperature in °C of a thermal zone is above the value. If `zone` is
+    /// None the hottest thermal zone is used, otherwise the zone whose type
+    /// matches (e.g. x86_pkg_temp)
+    ThermalAbove {
+        zone: Option<String>,
+        celsius: u32,
+    },
+}
 
-                config
+impl RuleCondition {
+    pub fn is_met(&self) -> bool {
+        match self {
+            RuleCondition::OnAC => helpers::system_on_ac(),
+            RuleCondition::OnBattery => !helpers::system_on_ac(),
+            RuleCondition::BatteryBelow(percentage) => {
+                helpers::battery_percentage().is_some_and(|p| p < *percentage)
+            }
+            RuleCondition::BatteryAbove(percentage) => {
+                helpers::battery_percentage().is_some_and(|p| p > *percentage)
+            }
+            RuleCondition::TimeWindow { start, end } => {
+                let (Some(start), Some(end)) = (parse_time_of_day(start), parse_time_of_day(end))
+                else {
+                    warn!("Invalid time window {start}-{end} in profile rule, expected HH:MM");
+                    return false;
+                };
+
+                let Some(now) = helpers::local_time_of_day() else {
+                    return false;
+                };
+
+                if start <= end {
+                    start <= now && now < end
+                } else {
+                    now >= start || now < end
+                }
+            }
+            RuleCondition::ThermalAbove { zone, celsius } => {
+                helpers::thermal_zone_temperature(zone.as_deref()).is_some_and(|t| t > *celsius)
             }
         }
     }
 }
+
+/// Parses HH:MM into minutes since midnight
+fn parse_time_of_day(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+
+    if hours >= 24 || minutes >= 60 {
+        None
+    } else {
+        Some(hours * 60 + minutes)
+    }
+}
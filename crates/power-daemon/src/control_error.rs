@@ -0,0 +1,53 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ProfileDiagnostic;
+
+/// Why a change requested from the daemon was not made
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ControlError {
+    /// There is no profile at the index
+    IndexOutOfRange(usize),
+    ProfileNotFound(String),
+    /// There is no profile with the ID
+    ProfileIdNotFound(String),
+    /// The name cannot be used as a file name
+    InvalidProfileName(String),
+    /// Another profile already has the name
+    NameTaken(String),
+    /// The profile is active and cannot be removed
+    ProfileActive(String),
+    /// There must always be at least one profile
+    LastProfile,
+    /// The profile cannot be applied on this system
+    InvalidProfile(Vec<ProfileDiagnostic>),
+}
+
+impl Display for ControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlError::IndexOutOfRange(idx) => write!(f, "There is no profile No {idx}"),
+            ControlError::ProfileNotFound(name) => write!(f, "Profile {name} does not exist"),
+            ControlError::ProfileIdNotFound(id) => write!(f, "There is no profile with ID {id}"),
+            ControlError::InvalidProfileName(name) => {
+                write!(f, "\"{name}\" is not a valid profile name")
+            }
+            ControlError::NameTaken(name) => write!(f, "A profile named {name} already exists"),
+            ControlError::ProfileActive(name) => {
+                write!(f, "Profile {name} is active and cannot be removed")
+            }
+            ControlError::LastProfile => write!(f, "The last profile cannot be removed"),
+            ControlError::InvalidProfile(diagnostics) => write!(
+                f,
+                "The profile cannot be applied on this system: {}",
+                diagnostics
+                    .iter()
+                    .filter(|d| d.is_error())
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    report::SettingsGroup, AmdGpuInfo, CPUSettings, CoreSetting, GpuSettings,
+    IntelRaplInterfaceInfo, IntelRaplInterfaceSettings, Profile, SystemInfo,
+};
+
+/// A setting that has a different value in two profiles
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SettingChange {
+    pub group: SettingsGroup,
+    /// Path of the setting inside of its group, e.g. "governor" or
+    /// "cores[cpu3].epp"
+    pub field: String,
+    /// None if the setting is unset
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl Display for SettingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            self.from.as_deref().unwrap_or("unset"),
+            self.to.as_deref().unwrap_or("unset")
+        )
+    }
+}
+
+/// Name of each settings group in a serialized profile
+const GROUP_FIELDS: [(&str, SettingsGroup); 16] = [
+    ("sleep_settings", SettingsGroup::Sleep),
+    ("cpu_settings", SettingsGroup::CPU),
+    ("cpu_core_settings", SettingsGroup::CPUCores),
+    ("screen_settings", SettingsGroup::Screen),
+    ("radio_settings", SettingsGroup::Radio),
+    ("network_settings", SettingsGroup::Network),
+    ("aspm_settings", SettingsGroup::ASPM),
+    ("pci_settings", SettingsGroup::PCI),
+    ("usb_settings", SettingsGroup::USB),
+    ("sata_settings", SettingsGroup::SATA),
+    ("kernel_settings", SettingsGroup::Kernel),
+    ("firmware_settings", SettingsGroup::Firmware),
+    ("audio_settings", SettingsGroup::Audio),
+    ("gpu_settings", SettingsGroup::Gpu),
+    ("rapl_settings", SettingsGroup::Rapl),
+    ("battery_settings", SettingsGroup::Battery),
+];
+
+pub(crate) fn diff_profiles(from: &Profile, to: &Profile) -> Vec<SettingChange> {
+    let from = serde_json::to_value(from).expect("Could not serialize profile");
+    let to = serde_json::to_value(to).expect("Could not serialize profile");
+
+    let mut changes = Vec::new();
+
+    for (key, group) in GROUP_FIELDS {
+        let mut from_fields = BTreeMap::new();
+        flatten(&from[key], "", &mut from_fields);
+        let mut to_fields = BTreeMap::new();
+        flatten(&to[key], "", &mut to_fields);
+
+        let fields: BTreeSet<&String> = from_fields.keys().chain(to_fields.keys()).collect();
+
+        for field in fields {
+            let from_value = from_fields.get(field);
+            let to_value = to_fields.get(field);
+
+            if from_value != to_value {
+                changes.push(SettingChange {
+                    group,
+                    field: field.clone(),
+                    from: from_value.cloned(),
+                    to: to_value.cloned(),
+                });
+            }
+        }
+    }
+
+    changes
+}
+
+/// Flattens a serialized settings group into a map of setting paths to
+/// values. Unset settings are left out
+fn flatten(value: &Value, path: &str, fields: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Null => {}
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                flatten(value, &path, fields);
+            }
+        }
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+            for (idx, item) in items.iter().enumerate() {
+                // Core overrides are matched by CPU rather than by position
+                if let Some(cpu_id) = item.get("cpu_id") {
+                    let mut item = item.clone();
+                    item.as_object_mut().unwrap().remove("cpu_id");
+                    flatten(&item, &format!("{path}[cpu{cpu_id}]"), fields);
+                } else {
+                    flatten(item, &format!("{path}[{idx}]"), fields);
+                }
+            }
+        }
+        Value::Array(items) => {
+            let items: Vec<_> = items.iter().map(value_to_string).collect();
+            fields.insert(path.to_string(), items.join(", "));
+        }
+        value => {
+            fields.insert(path.to_string(), value_to_string(value));
+        }
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Builds a profile out of the values the system currently has. Settings that
+/// are not part of `SystemInfo` are left unset
+pub fn live_profile(system_info: &SystemInfo) -> Profile {
+    let mut profile = Profile {
+        profile_name: "Live system".to_string(),
+        ..Default::default()
+    };
+
+    let cpu_info = &system_info.cpu_info;
+    let first_online = cpu_info
+        .cores
+        .iter()
+        .find(|c| c.online.unwrap_or(true) && !c.governor.is_empty());
+
+    profile.cpu_settings = CPUSettings {
+        mode: cpu_info.mode.clone(),
+        governor: first_online.map(|c| c.governor.clone()),
+        energy_perf_ratio: first_online.and_then(|c| c.epp.clone()),
+        min_freq: first_online.map(|c| c.scaling_min_frequency),
+        max_freq: first_online.map(|c| c.scaling_max_frequency),
+        boost: cpu_info.boost,
+        hwp_dyn_boost: cpu_info.hwp_dynamic_boost,
+        ..Default::default()
+    };
+
+    profile.cpu_core_settings.cores = Some(
+        cpu_info
+            .cores
+            .iter()
+            .map(|core| {
+                if core.online.unwrap_or(true) {
+                    CoreSetting {
+                        cpu_id: core.logical_cpu_id,
+                        online: core.online,
+                        max_frequency: Some(core.scaling_max_frequency),
+                        min_frequency: Some(core.scaling_min_frequency),
+                        governor: Some(core.governor.clone()),
+                        epp: core.epp.clone(),
+                    }
+                } else {
+                    CoreSetting {
+                        cpu_id: core.logical_cpu_id,
+                        online: Some(false),
+                        ..Default::default()
+                    }
+                }
+            })
+            .collect(),
+    );
+
+    if let Some(ref intel) = system_info.gpu_info.intel_info {
+        profile.gpu_settings = GpuSettings {
+            intel_min: Some(intel.min_frequency),
+            intel_max: Some(intel.max_frequency),
+            intel_boost: Some(intel.boost_frequency),
+            ..Default::default()
+        };
+    }
+
+    match system_info.gpu_info.amd_info {
+        Some(AmdGpuInfo::AmdGpu { ref dpm_perf }) => {
+            profile.gpu_settings.amd_dpm_perf_level = Some(dpm_perf.clone());
+        }
+        Some(AmdGpuInfo::Radeon {
+            ref dpm_perf,
+            ref dpm_state,
+        }) => {
+            profile.gpu_settings.amd_dpm_perf_level = Some(dpm_perf.clone());
+            profile.gpu_settings.amd_dpm_power_state = Some(dpm_state.clone());
+        }
+        Some(AmdGpuInfo::Legacy { ref power_profile }) => {
+            profile.gpu_settings.amd_power_profile = Some(power_profile.clone());
+        }
+        None => {}
+    }
+
+    let rapl = &system_info.rapl_info;
+    let rapl_settings = |interface: &Option<IntelRaplInterfaceInfo>| {
+        interface
+            .as_ref()
+            .map(|interface| IntelRaplInterfaceSettings {
+                long_term_limit: interface.long_term.as_ref().map(|c| c.power_limit),
+                short_term_limit: interface.short_term.as_ref().map(|c| c.power_limit),
+                peak_power_limit: interface.peak_power.as_ref().map(|c| c.power_limit),
+            })
+    };
+    profile.rapl_settings.package = rapl_settings(&rapl.package);
+    profile.rapl_settings.core = rapl_settings(&rapl.core);
+    profile.rapl_settings.uncore = rapl_settings(&rapl.uncore);
+
+    if let Some(battery) = system_info
+        .battery_info
+        .batteries
+        .iter()
+        .find(|b| b.charge_end_threshold.is_some())
+    {
+        profile.battery_settings.charge_start_threshold = battery.charge_start_threshold;
+        profile.battery_settings.charge_end_threshold = battery.charge_end_threshold;
+    }
+
+    profile
+}
@@ -0,0 +1,78 @@
+use std::{fmt::Display, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    plan::{self, PlannedAction},
+    report::SettingsGroup,
+    sysfs::snapshot::{selected_value, written_value},
+    CPUSettings,
+};
+
+/// A value set by the active profile that was changed afterwards by something
+/// else, e.g. another tool, the firmware or a resume from suspend
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub group: SettingsGroup,
+    pub path: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: expected \"{}\", found \"{}\"",
+            self.path, self.expected, self.actual
+        )
+    }
+}
+
+/// Reads back every node that applying a settings group with `f` would write
+/// to, returning the ones whose value differs. Changes made through commands
+/// cannot be verified and are not checked.
+///
+/// Drivers may round, clamp or normalize what is written, so the value a node
+/// read right after the daemon wrote to it counts as expected as well
+pub fn detect_group_drift(group: SettingsGroup, f: impl FnOnce()) -> Vec<Drift> {
+    let mut expected: Vec<(String, String)> = Vec::new();
+
+    for action in plan::record_actions(f) {
+        if let PlannedAction::Write { path, value } = action {
+            // Only the last value written to a node counts
+            expected.retain(|(p, _)| *p != path);
+            expected.push((path, value));
+        }
+    }
+
+    expected
+        .into_iter()
+        .filter_map(|(path, expected)| {
+            // Nodes that cannot be read show up as failures in the apply
+            // report instead
+            let actual = selected_value(&fs::read_to_string(&path).ok()?);
+
+            let drifted = !same_value(&path, &actual, &expected)
+                && written_value(Path::new(&path), &expected)
+                    .is_none_or(|read_back| !same_value(&path, &actual, &read_back));
+
+            drifted.then_some(Drift {
+                group,
+                path,
+                expected,
+                actual,
+            })
+        })
+        .collect()
+}
+
+/// The EPB reads back as a number whatever name was written to it
+fn same_value(path: &str, actual: &str, expected: &str) -> bool {
+    if path.ends_with("energy_perf_bias") {
+        CPUSettings::translate_epb_to_epp(actual)
+            == CPUSettings::translate_epb_to_epp(expected.trim())
+    } else {
+        actual == expected.trim()
+    }
+}
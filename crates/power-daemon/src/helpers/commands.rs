@@ -1,10 +1,26 @@
 use std::{
+    collections::HashMap,
     fs,
-    process::{Command, Stdio},
+    process::{Command, Output, Stdio},
+    sync::Mutex,
 };
 
 use log::{debug, error, trace, warn};
 
+use lazy_static::lazy_static;
+
+use crate::{
+    plan::{record_if_planning, PlannedAction},
+    report::{record_outcome, ApplyOutcome},
+    sysfs::root::SysRoot,
+    transaction::{record_undo, UndoAction},
+};
+
+lazy_static! {
+    /// Parameters each kernel module was last loaded with by the daemon
+    static ref MODULE_PARAMETERS: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+}
+
 pub fn command_exists(command: &str) -> bool {
     Command::new("which")
         .arg(command)
@@ -13,6 +29,12 @@ pub fn command_exists(command: &str) -> bool {
 }
 
 pub fn run_command(command_name: &str) {
+    if record_if_planning(|| PlannedAction::Command {
+        command: command_name.to_string(),
+    }) {
+        return;
+    }
+
     debug!("running: {command_name}");
 
     let mut command = get_command_from_string(command_name);
@@ -26,15 +48,17 @@ pub fn run_command(command_name: &str) {
     if !output.stdout.is_empty() {
         trace!(
             "Command output: {}",
-            String::from_utf8(output.stdout).unwrap()
+            String::from_utf8_lossy(&output.stdout)
         );
     }
     if !output.stderr.is_empty() {
         error!(
             "Command returned with stderr: {}",
-            String::from_utf8(output.stderr).unwrap()
+            String::from_utf8_lossy(&output.stderr)
         );
     }
+
+    record_command_outcome(command_name, &output);
 }
 
 // Runs command, returns (stdout, stdin), does not check for argument validity or program succesful completion.
@@ -57,6 +81,12 @@ pub fn run_command_with_output(command: &str) -> (String, String) {
 }
 
 pub fn run_graphical_command(command: &str) {
+    if record_if_planning(|| PlannedAction::Command {
+        command: command.to_string(),
+    }) {
+        return;
+    }
+
     debug!("running graphical command: {command}");
 
     let (display, xauth_path) = get_x_session_info();
@@ -73,17 +103,26 @@ pub fn run_graphical_command(command: &str) {
 
     trace!(
         "Command output: {}",
-        String::from_utf8(output.stdout).unwrap()
+        String::from_utf8_lossy(&output.stdout)
     );
     if !output.stderr.is_empty() {
         error!(
             "Command returned with stderr: {}",
-            String::from_utf8(output.stderr).unwrap()
+            String::from_utf8_lossy(&output.stderr)
         );
     }
+
+    record_command_outcome(command, &output);
 }
 
-pub fn run_graphical_command_in_background(command: &str) -> std::process::Child {
+/// Returns None if the command was only planned and not spawned
+pub fn run_graphical_command_in_background(command: &str) -> Option<std::process::Child> {
+    if record_if_planning(|| PlannedAction::Command {
+        command: command.to_string(),
+    }) {
+        return None;
+    }
+
     debug!("running graphical command in background: {command}");
 
     let (display, xauth_path) = get_x_session_info();
@@ -95,6 +134,85 @@ pub fn run_graphical_command_in_background(command: &str) -> std::process::Child
         .env("XAUTHORITY", xauth_path)
         .spawn()
         .unwrap_or_else(|e| panic!("Could not run graphical command in background: {command}: {e}"))
+        .into()
+}
+
+/// Unloads all `modules` in order and then loads them back in the same order
+/// with their respective parameters
+pub fn reload_kernel_modules(modules: &[(&str, &str)]) {
+    let mut planned = false;
+    for (module, parameters) in modules {
+        planned |= record_if_planning(|| PlannedAction::ModuleReload {
+            module: module.to_string(),
+            parameters: parameters.trim().to_string(),
+        });
+    }
+    if planned {
+        return;
+    }
+
+    for (module, _) in modules {
+        run_command(&format!("modprobe -r {module}"));
+
+        if !module_loaded(module) {
+            let previous_parameters = MODULE_PARAMETERS
+                .lock()
+                .unwrap()
+                .get(*module)
+                .cloned()
+                .unwrap_or_default();
+            record_undo(|| UndoAction::LoadModule {
+                module: module.to_string(),
+                parameters: previous_parameters,
+            });
+        }
+    }
+    for (module, parameters) in modules {
+        let command = format!("modprobe {module} {parameters}");
+        run_command(&command);
+
+        if module_loaded(module) {
+            MODULE_PARAMETERS
+                .lock()
+                .unwrap()
+                .insert(module.to_string(), parameters.to_string());
+        } else {
+            error!("Module {module} is not loaded after running {command}");
+            record_outcome(|| ApplyOutcome::Failed {
+                target: command.clone(),
+                errno: None,
+                error: "Module is not loaded".to_string(),
+            });
+        }
+    }
+}
+
+fn module_loaded(module: &str) -> bool {
+    fs::metadata(SysRoot::path(format!("/sys/module/{module}"))).is_ok()
+}
+
+fn record_command_outcome(command: &str, output: &Output) {
+    if !output.status.success() {
+        error!("Command {command} failed with {}", output.status);
+    }
+
+    record_outcome(|| {
+        if output.status.success() {
+            ApplyOutcome::Succeeded {
+                target: command.to_string(),
+            }
+        } else {
+            ApplyOutcome::Failed {
+                target: command.to_string(),
+                errno: None,
+                error: format!(
+                    "{}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            }
+        }
+    });
 }
 
 fn get_command_from_string(command: &str) -> Command {
@@ -1,7 +1,11 @@
 use std::fs;
 
+use log::warn;
+
 use serde::{Deserialize, Serialize};
 
+use crate::{sysfs::root::SysRoot, BatteryInfo};
+
 pub mod commands;
 
 pub use commands::*;
@@ -61,7 +65,7 @@ impl WhiteBlackListType {
 pub fn system_on_ac() -> bool {
     let mut ac_online = false;
 
-    if let Ok(entries) = fs::read_dir("/sys/class/power_supply/") {
+    if let Ok(entries) = fs::read_dir(SysRoot::path("/sys/class/power_supply/")) {
         for entry in entries.flatten() {
             let entry_path = entry.path();
             if let Ok(type_path) = fs::read_to_string(entry_path.join("type")) {
@@ -77,3 +81,63 @@ pub fn system_on_ac() -> bool {
 
     ac_online
 }
+
+/// Returns the combined charge percentage of the batteries powering the
+/// system as shown to clients, None if the system has no batteries
+pub fn battery_percentage() -> Option<u8> {
+    BatteryInfo::obtain().total?.capacity
+}
+
+/// Returns the temperature in °C of the thermal zone with the requested type,
+/// or the hottest thermal zone if no type is requested
+pub fn thermal_zone_temperature(zone_type: Option<&str>) -> Option<u32> {
+    let mut temperature = None;
+
+    if let Ok(entries) = fs::read_dir(SysRoot::path("/sys/class/thermal/")) {
+        for entry in entries.flatten() {
+            if !entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+            {
+                continue;
+            }
+
+            let entry_path = entry.path();
+
+            if let Some(zone_type) = zone_type {
+                match fs::read_to_string(entry_path.join("type")) {
+                    Ok(t) if t.trim() == zone_type => {}
+                    _ => continue,
+                }
+            }
+
+            if let Ok(temp) = fs::read_to_string(entry_path.join("temp")) {
+                // Reported in millidegrees, can be negative on broken sensors
+                if let Ok(temp) = temp.trim().parse::<i64>() {
+                    let temp = (temp.max(0) / 1000) as u32;
+                    temperature = Some(temperature.map_or(temp, |t: u32| t.max(temp)));
+                }
+            }
+        }
+    }
+
+    temperature
+}
+
+/// Returns the current local time as minutes since midnight
+pub fn local_time_of_day() -> Option<u32> {
+    // SAFETY: both pointers point to valid values owned by this function,
+    // localtime_r does not keep them
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm = std::mem::zeroed::<libc::tm>();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            warn!("Could not get the local time");
+            return None;
+        }
+        tm
+    };
+
+    Some(tm.tm_hour as u32 * 60 + tm.tm_min as u32)
+}
@@ -1,28 +1,49 @@
+pub mod bundle;
 #[cfg(feature = "communication")]
 pub mod communication;
 pub mod config;
+pub mod control_error;
+pub mod diff;
+pub mod drift;
+pub mod load_errors;
+pub mod migration;
+pub mod persistence;
+pub mod plan;
 pub mod profile;
 pub mod profiles_generator;
+pub mod report;
 pub mod sysfs;
 pub mod systeminfo;
+pub mod transaction;
+pub mod validation;
 
 mod helpers;
 
 use serde::{Deserialize, Serialize};
 
+pub use bundle::{ImportResult, NameCollision, ProfileBundle};
 pub use config::*;
-pub use helpers::{WhiteBlackList, WhiteBlackListType};
+pub use control_error::ControlError;
+pub use diff::SettingChange;
+pub use drift::Drift;
+pub use helpers::{system_on_ac, WhiteBlackList, WhiteBlackListType};
+pub use load_errors::LoadError;
+pub use migration::{FileMigration, MigrationReport};
+pub use plan::PlannedAction;
 pub use profile::*;
 pub use profiles_generator::DefaultProfileType;
+pub use report::{ApplyOutcome, ApplyReport, GroupReport, SettingsGroup};
 pub use systeminfo::*;
+pub use validation::ProfileDiagnostic;
 
 use std::{
     fs,
-    io::{Read, Write},
     path::{Path, PathBuf},
+    slice,
+    time::{Duration, Instant},
 };
 
-use log::{debug, error, trace};
+use log::{debug, error, info, trace, warn};
 
 #[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
 pub enum ReducedUpdate {
@@ -44,6 +65,109 @@ pub enum ReducedUpdate {
     Audio,
     Gpu,
     Rapl,
+    Battery,
+}
+
+impl From<SettingsGroup> for ReducedUpdate {
+    fn from(group: SettingsGroup) -> Self {
+        match group {
+            SettingsGroup::Sleep => ReducedUpdate::Sleep,
+            SettingsGroup::CPU => ReducedUpdate::CPU,
+            SettingsGroup::CPUCores => ReducedUpdate::CPUCores,
+            SettingsGroup::Screen => ReducedUpdate::Screen,
+            SettingsGroup::Radio => ReducedUpdate::Radio,
+            SettingsGroup::Network => ReducedUpdate::Network,
+            SettingsGroup::ASPM => ReducedUpdate::ASPM,
+            SettingsGroup::PCI => ReducedUpdate::PCI,
+            SettingsGroup::USB => ReducedUpdate::USB,
+            SettingsGroup::SATA => ReducedUpdate::SATA,
+            SettingsGroup::Kernel => ReducedUpdate::Kernel,
+            SettingsGroup::Firmware => ReducedUpdate::Firmware,
+            SettingsGroup::Audio => ReducedUpdate::Audio,
+            SettingsGroup::Gpu => ReducedUpdate::Gpu,
+            SettingsGroup::Rapl => ReducedUpdate::Rapl,
+            SettingsGroup::Battery => ReducedUpdate::Battery,
+        }
+    }
+}
+
+/// Why the active profile was picked
+#[derive(Clone, Deserialize, Serialize, PartialEq, Debug, Default)]
+pub enum ProfilePickReason {
+    TemporaryOverride,
+    PersistentOverride,
+    /// Index of the matching rule in `Config::profile_rules`
+    Rule(usize),
+    #[default]
+    AC,
+    Battery,
+}
+
+/// When a temporary profile override ends by itself
+#[derive(Clone, Deserialize, Serialize, PartialEq, Debug, Default)]
+pub enum OverrideExpiry {
+    /// Lasts until the daemon stops, which is usually on reboot
+    #[default]
+    Reboot,
+    /// Ends when the system switches between AC and battery
+    PowerSourceChange,
+    /// Ends once the duration has passed
+    Duration(Duration),
+}
+
+/// A temporary profile override as reported to clients
+#[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+pub struct ProfileOverride {
+    pub profile_name: String,
+    pub expiry: OverrideExpiry,
+    /// Time left until an override with a duration ends
+    pub remaining: Option<Duration>,
+}
+
+/// Part of the daemon's state that changed, reported to the change listener
+#[derive(Clone, Deserialize, Serialize, PartialEq, Debug)]
+pub enum StateChange {
+    ActiveProfile(String),
+    /// Profiles were added, removed, renamed, reordered or edited
+    Profiles,
+    Config,
+    Override(Option<ProfileOverride>),
+}
+
+/// State that was last reported to the change listener
+#[derive(Default)]
+struct PublishedState {
+    active_profile: String,
+    profiles: Vec<Profile>,
+    config: Option<Config>,
+    profile_override: Option<(String, OverrideExpiry)>,
+}
+
+struct TemporaryOverride {
+    profile_name: String,
+    expiry: OverrideExpiry,
+    set_at: Instant,
+    /// Power source at the time the override was set
+    on_ac: bool,
+}
+
+impl TemporaryOverride {
+    fn remaining(&self) -> Option<Duration> {
+        match self.expiry {
+            OverrideExpiry::Duration(duration) => {
+                Some(duration.saturating_sub(self.set_at.elapsed()))
+            }
+            _ => None,
+        }
+    }
+
+    fn has_expired(&self) -> bool {
+        match self.expiry {
+            OverrideExpiry::Reboot => false,
+            OverrideExpiry::PowerSourceChange => helpers::system_on_ac() != self.on_ac,
+            OverrideExpiry::Duration(_) => self.remaining() == Some(Duration::ZERO),
+        }
+    }
 }
 
 pub struct Instance {
@@ -51,7 +175,12 @@ pub struct Instance {
     config_path: PathBuf,
     config: Config,
     profiles_info: ProfilesInfo,
-    temporary_override: Option<String>,
+    temporary_override: Option<TemporaryOverride>,
+    pick_reason: ProfilePickReason,
+    /// Results of the latest profile application
+    apply_report: ApplyReport,
+    change_listener: Option<Box<dyn Fn(StateChange) + Send>>,
+    published: PublishedState,
 }
 
 impl Instance {
@@ -66,39 +195,248 @@ impl Instance {
                 ..Default::default()
             },
             temporary_override: None,
+            pick_reason: ProfilePickReason::default(),
+            apply_report: ApplyReport::default(),
+            change_listener: None,
+            published: PublishedState::default(),
+        }
+    }
+
+    /// Calls `listener` whenever the active profile, the profiles, the config
+    /// or the temporary override change, however they were changed
+    pub fn set_change_listener(&mut self, listener: impl Fn(StateChange) + Send + 'static) {
+        self.published = PublishedState {
+            active_profile: self.get_active_profile_name(),
+            profiles: self.profiles_info.profiles.clone(),
+            config: Some(self.config.clone()),
+            profile_override: self.published_override(),
+        };
+        self.change_listener = Some(Box::new(listener));
+    }
+
+    fn published_override(&self) -> Option<(String, OverrideExpiry)> {
+        self.temporary_override.as_ref().map(|temporary_override| {
+            (
+                temporary_override.profile_name.clone(),
+                temporary_override.expiry.clone(),
+            )
+        })
+    }
+
+    /// Reports the state that changed since it was last reported
+    fn publish_changes(&mut self) {
+        let Some(ref listener) = self.change_listener else {
+            return;
+        };
+
+        let active_profile = self.profiles_info.get_active_profile().profile_name.clone();
+        if active_profile != self.published.active_profile {
+            listener(StateChange::ActiveProfile(active_profile.clone()));
+            self.published.active_profile = active_profile;
+        }
+
+        if self.profiles_info.profiles != self.published.profiles {
+            listener(StateChange::Profiles);
+            self.published.profiles = self.profiles_info.profiles.clone();
+        }
+
+        if self.published.config.as_ref() != Some(&self.config) {
+            listener(StateChange::Config);
+            self.published.config = Some(self.config.clone());
+        }
+
+        let profile_override = self.published_override();
+        if profile_override != self.published.profile_override {
+            listener(StateChange::Override(self.get_profile_override()));
+            self.published.profile_override = profile_override;
         }
     }
 
     pub fn set_profile_override(&mut self, name: String) {
-        self.temporary_override = Some(name);
+        self.set_profile_override_until(name, OverrideExpiry::Reboot);
+    }
+    pub fn set_profile_override_until(&mut self, name: String, expiry: OverrideExpiry) {
+        self.temporary_override = Some(TemporaryOverride {
+            profile_name: name,
+            expiry,
+            set_at: Instant::now(),
+            on_ac: helpers::system_on_ac(),
+        });
         self.update_full();
     }
-    pub fn try_set_profile_override(&mut self, name: String) {
+    pub fn try_set_profile_override(&mut self, name: String) -> Result<(), ControlError> {
+        self.try_set_profile_override_until(name, OverrideExpiry::Reboot)
+    }
+    pub fn try_set_profile_override_until(
+        &mut self,
+        name: String,
+        expiry: OverrideExpiry,
+    ) -> Result<(), ControlError> {
         if self
             .profiles_info
             .try_find_profile_index_by_name(&name)
             .is_none()
         {
             debug!("Not updating profile override because profile name does not match with any existing profiles");
-        } else {
-            self.set_profile_override(name);
+            return Err(ControlError::ProfileNotFound(name));
         }
+
+        self.set_profile_override_until(name, expiry);
+        Ok(())
     }
     pub fn remove_profile_override(&mut self) {
         self.temporary_override = None;
         self.update_full();
     }
 
+    pub fn get_profile_override(&self) -> Option<ProfileOverride> {
+        self.temporary_override
+            .as_ref()
+            .map(|temporary_override| ProfileOverride {
+                profile_name: temporary_override.profile_name.clone(),
+                expiry: temporary_override.expiry.clone(),
+                remaining: temporary_override.remaining(),
+            })
+    }
+
+    /// Time until the temporary override ends by itself, if it has a duration
+    pub fn profile_override_remaining(&self) -> Option<Duration> {
+        self.temporary_override
+            .as_ref()
+            .and_then(TemporaryOverride::remaining)
+    }
+
+    /// Drops the temporary override if it has ended. The picked profile is not
+    /// applied, returns true if that needs to be done
+    fn expire_profile_override(&mut self) -> bool {
+        match self.temporary_override {
+            Some(ref temporary_override) if temporary_override.has_expired() => {
+                info!(
+                    "Temporary override to profile {} ended ({:?})",
+                    temporary_override.profile_name, temporary_override.expiry
+                );
+                self.temporary_override = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
     pub fn update_full(&mut self) {
-        self.profiles_info.active_profile = self.pick_profile();
+        self.update_picked_profile();
 
-        self.profiles_info.get_active_profile().apply_all();
+        self.apply_report = self.profiles_info.get_active_profile().apply_all();
+        self.publish_changes();
     }
     pub fn update_reduced(&mut self, reduced_update: ReducedUpdate) {
-        self.profiles_info.active_profile = self.pick_profile();
-        self.profiles_info
+        let active = self.profiles_info.active_profile;
+        self.update_picked_profile();
+        if self.profiles_info.active_profile != active {
+            // Nothing of the newly picked profile has been applied yet
+            self.update_full();
+            return;
+        }
+
+        let report = self
+            .profiles_info
             .get_active_profile()
             .apply_reduced(&reduced_update);
+        self.apply_report.merge(report);
+        self.publish_changes();
+    }
+
+    /// Applies the active profile again after the system resumed from
+    /// suspend, only the configured settings groups if there are any. The
+    /// rules and overrides are evaluated first since the power source may
+    /// have changed while suspended
+    pub fn update_after_resume(&mut self) {
+        let active = self.profiles_info.active_profile;
+        self.reevaluate_profile();
+        if self.profiles_info.active_profile != active {
+            // The newly picked profile was applied as a whole
+            return;
+        }
+
+        match self.config.resume_groups.clone() {
+            None => self.update_full(),
+            Some(groups) => {
+                for group in groups {
+                    self.update_reduced(group.into());
+                }
+            }
+        }
+    }
+
+    /// Re-evaluates the profile rules and overrides, applying the picked
+    /// profile only if it differs from the currently active one. Temporary
+    /// overrides that have ended are dropped
+    pub fn reevaluate_profile(&mut self) {
+        self.expire_profile_override();
+
+        let (idx, reason) = self.pick_profile();
+        self.pick_reason = reason;
+
+        if idx != self.profiles_info.active_profile {
+            info!(
+                "Active profile changed to {} because of {:?}",
+                self.profiles_info.profiles[idx].profile_name, self.pick_reason
+            );
+            self.profiles_info.active_profile = idx;
+            self.apply_report = self.profiles_info.get_active_profile().apply_all();
+        }
+
+        self.publish_changes();
+    }
+
+    pub fn get_profile_pick_reason(&self) -> ProfilePickReason {
+        self.pick_reason.clone()
+    }
+
+    pub fn get_apply_report(&self) -> ApplyReport {
+        self.apply_report.clone()
+    }
+
+    /// Values of the active profile that were changed outside of the daemon
+    pub fn detect_drift(&self) -> Vec<Drift> {
+        self.profiles_info.get_active_profile().detect_drift()
+    }
+
+    /// Applies again the settings groups of the active profile whose values
+    /// were changed outside of the daemon. Returns the drift that was found
+    pub fn enforce_profile(&mut self) -> Vec<Drift> {
+        let drift = self.detect_drift();
+
+        let mut groups: Vec<SettingsGroup> = Vec::new();
+        for entry in drift.iter() {
+            if !groups.contains(&entry.group) {
+                groups.push(entry.group);
+            }
+        }
+
+        for group in groups {
+            warn!(
+                "{group:?} settings drifted from profile {}, applying them again",
+                self.get_active_profile_name()
+            );
+            self.update_reduced(group.into());
+        }
+
+        drift
+    }
+
+    /// How often drifted settings should be enforced, None if they should
+    /// not be
+    pub fn drift_check_interval(&self) -> Option<std::time::Duration> {
+        self.config
+            .drift_check_interval
+            .filter(|secs| *secs > 0)
+            .map(|secs| std::time::Duration::from_secs(secs.into()))
+    }
+
+    /// Writes back the values that every node changed by the daemon had
+    /// before. They stay restored until a profile gets applied again
+    pub fn restore_original_values(&mut self) {
+        sysfs::snapshot::restore_all();
     }
 
     pub fn update_config(&mut self, config: Config) {
@@ -114,6 +452,114 @@ impl Instance {
         self.update_full();
     }
 
+    pub fn set_ac_profile(&mut self, name: String) -> Result<(), ControlError> {
+        self.verify_profile_name(&name)?;
+        info!("Setting AC profile to {name}");
+
+        self.config.ac_profile = name;
+        self.apply_config_change();
+        Ok(())
+    }
+    pub fn set_bat_profile(&mut self, name: String) -> Result<(), ControlError> {
+        self.verify_profile_name(&name)?;
+        info!("Setting battery profile to {name}");
+
+        self.config.bat_profile = name;
+        self.apply_config_change();
+        Ok(())
+    }
+    pub fn set_persistent_profile_override(&mut self, name: String) -> Result<(), ControlError> {
+        self.verify_profile_name(&name)?;
+        info!("Setting persistent profile override to {name}");
+
+        self.config.profile_override = Some(name);
+        self.apply_config_change();
+        Ok(())
+    }
+    pub fn clear_persistent_profile_override(&mut self) {
+        info!("Clearing persistent profile override");
+
+        self.config.profile_override = None;
+        self.apply_config_change();
+    }
+
+    fn verify_profile_name(&self, name: &str) -> Result<(), ControlError> {
+        if !is_valid_profile_name(name) {
+            return Err(ControlError::InvalidProfileName(name.to_string()));
+        }
+        if self
+            .profiles_info
+            .try_find_profile_index_by_name(name)
+            .is_none()
+        {
+            return Err(ControlError::ProfileNotFound(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Saves a change to the config that may pick a different profile
+    fn apply_config_change(&mut self) {
+        serialize_config(&self.config, &self.config_path);
+        self.reevaluate_profile();
+    }
+
+    /// Re-reads the config and profile files after they were changed outside
+    /// of the daemon and applies them. Files that match the current state,
+    /// like the ones the daemon wrote itself, are ignored. Invalid files are
+    /// rejected and the current state is kept
+    pub fn reload_from_disk(&mut self) {
+        let (config, mut profiles) = match read_config_files(&self.config_path, &self.profiles_path)
+        {
+            Ok(files) => files,
+            Err(error) => {
+                error!("Not reloading config files: {error}");
+                return;
+            }
+        };
+
+        for idx in assign_profile_ids(&mut profiles, &self.profiles_info.profiles) {
+            serialize_profiles(slice::from_ref(&profiles[idx]), &self.profiles_path);
+        }
+
+        if config == self.config && profiles == self.profiles_info.profiles {
+            debug!("Config files did not change, not reloading");
+            return;
+        }
+
+        let system_info = SystemInfo::obtain();
+        for profile in profiles.iter() {
+            let diagnostics = profile.validate(&system_info);
+            for diagnostic in diagnostics.iter() {
+                warn!("Profile {}: {diagnostic}", profile.profile_name);
+            }
+            if diagnostics.iter().any(ProfileDiagnostic::is_error) {
+                error!(
+                    "Not reloading config files: profile {} cannot be applied on this system",
+                    profile.profile_name
+                );
+                return;
+            }
+        }
+
+        info!("Config files changed on disk, reloading");
+
+        if let Some(ref temporary_override) = self.temporary_override {
+            if !config.profiles.contains(&temporary_override.profile_name) {
+                self.temporary_override = None;
+            }
+        }
+
+        self.config = config;
+        self.profiles_info.profiles = profiles;
+
+        self.update_full();
+    }
+
+    /// Config and profile files that could not be loaded and were replaced
+    pub fn get_load_errors(&self) -> Vec<LoadError> {
+        load_errors::load_errors()
+    }
+
     pub fn get_active_profile_name(&self) -> String {
         self.profiles_info.get_active_profile().profile_name.clone()
     }
@@ -142,13 +588,142 @@ impl Instance {
         // config. If the config's order changed then re-callign parse_profiles
         // should give a list of profiles in the new order
         self.profiles_info.profiles = parse_profiles(&self.config, &self.profiles_path);
+        self.publish_changes();
     }
 
-    pub fn reset_profile(&mut self, idx: usize) {
-        if self.verify_index_ranges(idx) {
-            return;
+    /// Lists the settings that differ between two profiles
+    pub fn diff_profiles(&self, from: &str, to: &str) -> Option<Vec<SettingChange>> {
+        let from = self.profiles_info.try_find_profile_index_by_name(from)?;
+        let to = self.profiles_info.try_find_profile_index_by_name(to)?;
+
+        Some(self.profiles_info.profiles[from].diff(&self.profiles_info.profiles[to]))
+    }
+
+    /// Lists the settings of a profile that have a different value on the
+    /// live system
+    pub fn diff_profile_live(&self, name: &str) -> Option<Vec<SettingChange>> {
+        let idx = self.profiles_info.try_find_profile_index_by_name(name)?;
+
+        Some(self.profiles_info.profiles[idx].diff_live(&SystemInfo::obtain()))
+    }
+
+    /// Returns the profile as a bundle that can be imported on other machines
+    pub fn export_profile(&self, name: &str) -> Option<String> {
+        let Some(idx) = self.profiles_info.try_find_profile_index_by_name(name) else {
+            error!("Requested to export profile {name} which does not exist");
+            return None;
+        };
+
+        debug!("Exporting profile {name}");
+
+        Some(ProfileBundle::new(self.profiles_info.profiles[idx].clone()).to_toml())
+    }
+
+    /// Adds the profile of a bundle. Hardware specific settings are removed
+    /// if `strip_hardware_specific` is set, otherwise they are only listed in
+    /// the result. Profiles that cannot be applied on this system are rejected
+    pub fn import_profile(
+        &mut self,
+        bundle: &str,
+        on_collision: NameCollision,
+        strip_hardware_specific: bool,
+    ) -> Result<ImportResult, String> {
+        let mut profile = ProfileBundle::parse(bundle)?.profile;
+
+        if !is_valid_profile_name(&profile.profile_name) {
+            return Err(format!(
+                "\"{}\" is not a valid profile name",
+                profile.profile_name
+            ));
         }
 
+        let hardware_specific_fields = bundle::hardware_specific_fields(&profile);
+        if strip_hardware_specific {
+            bundle::strip_hardware_specific(&mut profile);
+        }
+
+        let diagnostics = profile.validate(&SystemInfo::obtain());
+        if diagnostics.iter().any(ProfileDiagnostic::is_error) {
+            return Err(diagnostics
+                .iter()
+                .filter(|d| d.is_error())
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; "));
+        }
+
+        let existing = self
+            .profiles_info
+            .try_find_profile_index_by_name(&profile.profile_name);
+
+        match (existing, on_collision) {
+            (Some(_), NameCollision::Fail) => {
+                return Err(format!("Profile {} already exists", profile.profile_name));
+            }
+            (Some(idx), NameCollision::Replace) => {
+                info!(
+                    "Importing profile {} over the existing one",
+                    profile.profile_name
+                );
+
+                let name = profile.profile_name.clone();
+                self.update_profile(idx, profile)
+                    .map_err(|error| error.to_string())?;
+                if idx == self.profiles_info.active_profile {
+                    self.update_full();
+                }
+
+                return Ok(ImportResult {
+                    profile_name: name,
+                    hardware_specific_fields,
+                    stripped: strip_hardware_specific,
+                    diagnostics,
+                });
+            }
+            (Some(_), NameCollision::Rename) => {
+                let base_name = profile.profile_name.clone();
+                let mut count = 1;
+                while self.config.profiles.contains(&profile.profile_name) {
+                    profile.profile_name = format!("{} #{}", base_name, count);
+                    count += 1;
+                }
+            }
+            (None, _) => {}
+        }
+
+        info!("Importing profile {}", profile.profile_name);
+        // The ID in the bundle belongs to the profile it was exported from
+        profile.profile_id = profiles_generator::new_profile_id();
+
+        self.config.profiles.push(profile.profile_name.clone());
+        serialize_profiles(std::slice::from_ref(&profile), &self.profiles_path);
+        serialize_config(&self.config, &self.config_path);
+        self.profiles_info.profiles = parse_profiles(&self.config, &self.profiles_path);
+        self.publish_changes();
+
+        Ok(ImportResult {
+            profile_name: profile.profile_name,
+            hardware_specific_fields,
+            stripped: strip_hardware_specific,
+            diagnostics,
+        })
+    }
+
+    /// Index of the profile with the ID. Unlike indices kept by clients, it
+    /// stays valid as long as the instance is not changed
+    pub fn find_profile_by_id(&self, id: &str) -> Result<usize, ControlError> {
+        self.profiles_info
+            .try_find_profile_index_by_id(id)
+            .ok_or_else(|| ControlError::ProfileIdNotFound(id.to_string()))
+    }
+    pub fn profile_id_at(&self, idx: usize) -> Result<String, ControlError> {
+        self.verify_index(idx)?;
+        Ok(self.profiles_info.profiles[idx].profile_id.clone())
+    }
+
+    pub fn reset_profile(&mut self, idx: usize) -> Result<(), ControlError> {
+        self.verify_index(idx)?;
+
         debug!("Resetting profile No {idx}");
         let system_info = SystemInfo::obtain();
 
@@ -157,23 +732,24 @@ impl Instance {
         serialize_profiles(&self.profiles_info.profiles, &self.profiles_path);
 
         self.update_full();
+        Ok(())
     }
 
-    pub fn remove_profile(&mut self, idx: usize) {
+    pub fn remove_profile(&mut self, idx: usize) -> Result<(), ControlError> {
         if self.profiles_info.profiles.len() <= 1 {
             error!(
                 "There's only 1 or less available profiles. Cannot remove remaining. Ignoring..."
             );
-            return;
+            return Err(ControlError::LastProfile);
         }
 
-        if self.verify_index_ranges(idx) {
-            return;
-        }
+        self.verify_index(idx)?;
 
         if self.profiles_info.active_profile == idx {
             error!("Cannot remove currently active profile, ignoring...");
-            return;
+            return Err(ControlError::ProfileActive(
+                self.profiles_info.profiles[idx].profile_name.clone(),
+            ));
         }
 
         if self.profiles_info.active_profile > idx {
@@ -186,7 +762,7 @@ impl Instance {
         let mut should_update = false;
 
         if let Some(ref temporary_override) = self.temporary_override {
-            if *temporary_override == profile_to_remove.profile_name {
+            if temporary_override.profile_name == profile_to_remove.profile_name {
                 self.temporary_override = None;
                 should_update = true;
             }
@@ -197,6 +773,17 @@ impl Instance {
                 should_update = true;
             }
         }
+        if self
+            .config
+            .profile_rules
+            .iter()
+            .any(|r| r.profile == profile_to_remove.profile_name)
+        {
+            self.config
+                .profile_rules
+                .retain(|r| r.profile != profile_to_remove.profile_name);
+            should_update = true;
+        }
 
         self.config.profiles.remove(
             self.config
@@ -231,29 +818,37 @@ impl Instance {
             should_update = true;
         }
 
-        fs::remove_file(
-            self.profiles_path
-                .join(format!("{}.toml", &profile_to_remove_name)),
-        )
-        .expect("Could not remove profile file");
-
         serialize_config(&self.config, &self.config_path);
+        if let Err(e) = persistence::remove_with_backup(
+            &self
+                .profiles_path
+                .join(format!("{}.toml", &profile_to_remove_name)),
+        ) {
+            error!("Could not remove profile file of {profile_to_remove_name}: {e}");
+        }
 
         if should_update {
             self.update_full();
         }
+        self.publish_changes();
+        Ok(())
     }
 
-    pub fn update_profile_name(&mut self, idx: usize, new_name: String) {
-        if self.verify_index_ranges(idx) {
-            return;
+    pub fn update_profile_name(
+        &mut self,
+        idx: usize,
+        new_name: String,
+    ) -> Result<(), ControlError> {
+        self.verify_index(idx)?;
+        if !is_valid_profile_name(&new_name) {
+            return Err(ControlError::InvalidProfileName(new_name));
         }
         for profile in &self.config.profiles {
             if new_name == *profile {
                 error!(
                     "Requested to update profile name to an already occupied name. Ignorring..."
                 );
-                return;
+                return Err(ControlError::NameTaken(new_name));
             }
         }
 
@@ -272,22 +867,36 @@ impl Instance {
                 self.config.profile_override = Some(new_name.clone());
             }
         }
-        if let Some(ref profile_override) = self.temporary_override {
-            if *profile_override == old_name {
-                self.temporary_override = Some(new_name.clone());
+        if let Some(ref mut temporary_override) = self.temporary_override {
+            if temporary_override.profile_name == old_name {
+                temporary_override.profile_name = new_name.clone();
+            }
+        }
+        for rule in self.config.profile_rules.iter_mut() {
+            if rule.profile == old_name {
+                rule.profile = new_name.clone();
             }
         }
 
+        // The config must only refer to the new name once its file exists,
+        // and the old file must outlive the config referring to it
+        serialize_profiles(
+            std::slice::from_ref(&self.profiles_info.profiles[idx]),
+            &self.profiles_path,
+        );
         serialize_config(&self.config, &self.config_path);
-        // Renaming a profile could cause a previous file with the same name
-        // left behind. Therefore we need to clear the directory first and then serialize
-        serialize_profiles_clean(&self.profiles_info.profiles, &self.profiles_path);
+        if let Err(e) =
+            persistence::remove_with_backup(&self.profiles_path.join(format!("{old_name}.toml")))
+        {
+            error!("Could not remove old profile file of {old_name}: {e}");
+        }
+        self.publish_changes();
+        Ok(())
     }
 
-    pub fn swap_profile_order(&mut self, idx: usize, new_idx: usize) {
-        if self.verify_index_ranges(idx) || self.verify_index_ranges(idx) {
-            return;
-        }
+    pub fn swap_profile_order(&mut self, idx: usize, new_idx: usize) -> Result<(), ControlError> {
+        self.verify_index(idx)?;
+        self.verify_index(new_idx)?;
 
         if self.profiles_info.active_profile == idx {
             self.profiles_info.active_profile = new_idx;
@@ -300,136 +909,330 @@ impl Instance {
         self.config.profiles[new_idx] = tmp;
         serialize_config(&self.config, &self.config_path);
         self.profiles_info.profiles = parse_profiles(&self.config, &self.profiles_path);
+        self.publish_changes();
+        Ok(())
     }
 
-    pub fn update_profile_full(&mut self, idx: usize, profile: Profile) {
-        self.update_profile(idx, profile);
+    /// Replaces the profile and applies it if it's active. Profiles that
+    /// cannot be applied on this system are rejected, the diagnostics are
+    /// returned either way
+    pub fn update_profile_full(
+        &mut self,
+        idx: usize,
+        profile: Profile,
+    ) -> Result<Vec<ProfileDiagnostic>, ControlError> {
+        self.verify_index(idx)?;
+
+        let diagnostics = profile.validate(&SystemInfo::obtain());
+        for diagnostic in diagnostics.iter() {
+            warn!("Profile {}: {diagnostic}", profile.profile_name);
+        }
+
+        if diagnostics.iter().any(ProfileDiagnostic::is_error) {
+            error!(
+                "Profile {} cannot be applied on this system, ignoring...",
+                profile.profile_name
+            );
+            return Err(ControlError::InvalidProfile(diagnostics));
+        }
+
+        self.update_profile(idx, profile)?;
 
         if idx == self.profiles_info.active_profile {
             self.update_full();
         }
+
+        Ok(diagnostics)
     }
     pub fn update_profile_reduced(
         &mut self,
         idx: usize,
         profile: Profile,
         reduced_update: ReducedUpdate,
-    ) {
-        self.update_profile(idx, profile);
+    ) -> Result<(), ControlError> {
+        self.update_profile(idx, profile)?;
 
         if idx == self.profiles_info.active_profile {
             self.update_reduced(reduced_update);
         }
+        Ok(())
+    }
+
+    fn update_picked_profile(&mut self) {
+        self.expire_profile_override();
+
+        let (idx, reason) = self.pick_profile();
+        self.profiles_info.active_profile = idx;
+        self.pick_reason = reason;
     }
 
     /// Returns the index of the profile that should be selcted at the moment
-    /// according to all settings and overrides
-    fn pick_profile(&self) -> usize {
+    /// according to all settings, overrides and rules, along with the reason
+    /// of the pick
+    fn pick_profile(&self) -> (usize, ProfilePickReason) {
         if let Some(ref temporary_override) = self.temporary_override {
             debug!("Picking temporary profile override");
-            self.profiles_info
-                .find_profile_index_by_name(temporary_override)
-        } else if let Some(ref profile_override) = self.config.profile_override {
+            return (
+                self.profiles_info
+                    .find_profile_index_by_name(&temporary_override.profile_name),
+                ProfilePickReason::TemporaryOverride,
+            );
+        }
+
+        if let Some(ref profile_override) = self.config.profile_override {
             debug!("Picking settings profile override");
-            self.profiles_info
-                .find_profile_index_by_name(profile_override)
-        } else if helpers::system_on_ac() {
+            return (
+                self.profiles_info
+                    .find_profile_index_by_name(profile_override),
+                ProfilePickReason::PersistentOverride,
+            );
+        }
+
+        for (rule_idx, rule) in self.config.profile_rules.iter().enumerate() {
+            if !rule.matches() {
+                continue;
+            }
+
+            if let Some(idx) = self
+                .profiles_info
+                .try_find_profile_index_by_name(&rule.profile)
+            {
+                debug!("Picking profile {} from rule No {rule_idx}", rule.profile);
+                return (idx, ProfilePickReason::Rule(rule_idx));
+            } else {
+                warn!(
+                    "Profile rule No {rule_idx} matched but profile {} does not exist, ignoring...",
+                    rule.profile
+                );
+            }
+        }
+
+        if helpers::system_on_ac() {
             debug!("Picking AC profile");
-            self.profiles_info
-                .find_profile_index_by_name(&self.config.ac_profile)
+            (
+                self.profiles_info
+                    .find_profile_index_by_name(&self.config.ac_profile),
+                ProfilePickReason::AC,
+            )
         } else {
             debug!("Picking BAT profile");
-            self.profiles_info
-                .find_profile_index_by_name(&self.config.bat_profile)
+            (
+                self.profiles_info
+                    .find_profile_index_by_name(&self.config.bat_profile),
+                ProfilePickReason::Battery,
+            )
         }
     }
 
-    fn update_profile(&mut self, idx: usize, profile: Profile) {
-        if self.verify_index_ranges(idx) {
-            return;
-        }
+    fn update_profile(&mut self, idx: usize, mut profile: Profile) -> Result<(), ControlError> {
+        self.verify_index(idx)?;
 
         debug!("Updating profile No {idx}");
         trace!("New profile: {profile:#?}");
 
+        profile.profile_id = self.profiles_info.profiles[idx].profile_id.clone();
         self.profiles_info.profiles[idx] = profile;
         // We actually need to update the underlying files
         serialize_profiles(&self.profiles_info.profiles, &self.profiles_path);
+        self.publish_changes();
+        Ok(())
     }
 
-    fn verify_index_ranges(&self, idx: usize) -> bool {
+    fn verify_index(&self, idx: usize) -> Result<(), ControlError> {
         if idx >= self.config.profiles.len() || idx >= self.profiles_info.profiles.len() {
             error!("Profile with requested index is outside of bounds, ignoring...");
-            true
+            Err(ControlError::IndexOutOfRange(idx))
         } else {
-            false
+            Ok(())
         }
     }
 }
 
-pub fn parse_config(path: &Path) -> Config {
-    let content = fs::read_to_string(path).expect("Could not read config");
+/// Profile names are used as file names
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !name.trim().is_empty() && !name.contains(['/', '\0']) && !name.starts_with('.')
+}
 
-    let config = Config::parse_or_default(&content);
+/// Reads the config, migrating it if needed. A config that cannot be read
+/// is moved aside and replaced with the default one
+pub fn parse_config(path: &Path) -> Config {
+    let config = match fs::read_to_string(path)
+        .map_err(|error| format!("Could not read config: {error}"))
+        .and_then(|content| Config::parse(&content))
+    {
+        Ok(config) => config,
+        Err(error) => {
+            set_aside_broken_file(path, error);
+            Config::create_default()
+        }
+    };
 
-    fs::write(path, toml::to_string_pretty(&config).unwrap()).expect("Could not write to config");
+    serialize_config(&config, path);
 
     config
 }
 
+/// Reads the profiles of the config, migrating them if needed. Profiles that
+/// cannot be read are moved aside and replaced with generated ones
 fn parse_profiles(config: &Config, path: &Path) -> Vec<Profile> {
     let mut profiles = Vec::new();
     for profile_name in config.profiles.iter() {
         let path = path.join(format!("{profile_name}.toml"));
-        let mut file = fs::File::open(&path).expect("Could not read file");
-        let mut contents = String::new();
+        let contents = fs::read_to_string(&path);
+
+        let parsed = match contents {
+            Ok(ref contents) => Profile::parse(contents, profile_name),
+            Err(ref error) => Err(format!("Could not read profile file: {error}")),
+        };
+
+        let mut profile = match parsed {
+            Ok(profile) => profile,
+            Err(error) => {
+                set_aside_broken_file(&path, error);
+                profiles_generator::create_fallback(
+                    profile_name,
+                    contents.as_deref().ok(),
+                    &SystemInfo::obtain(),
+                )
+            }
+        };
 
-        file.read_to_string(&mut contents)
-            .expect("Could not read file");
+        profile.profile_name = profile_name.clone();
+        profiles.push(profile);
+    }
+
+    assign_profile_ids(&mut profiles, &[]);
+    serialize_profiles(&profiles, path);
+
+    profiles
+}
+
+/// Gives every profile a unique ID. Profiles without one, e.g. ones written
+/// by hand, keep the ID of the profile of the same name in `current` if there
+/// is one. Copied profile files share the ID of the original, the copy gets a
+/// new one. Returns the indices of the profiles whose ID changed
+fn assign_profile_ids(profiles: &mut [Profile], current: &[Profile]) -> Vec<usize> {
+    let mut changed = Vec::new();
+
+    for (idx, profile) in profiles.iter_mut().enumerate() {
+        if profile.profile_id.is_empty() {
+            if let Some(known) = current
+                .iter()
+                .find(|known| known.profile_name == profile.profile_name)
+            {
+                profile.profile_id = known.profile_id.clone();
+                changed.push(idx);
+            }
+        }
+    }
+
+    for idx in 0..profiles.len() {
+        let id = &profiles[idx].profile_id;
+        let sharing = (0..profiles.len())
+            .filter(|other| profiles[*other].profile_id == *id)
+            .collect::<Vec<_>>();
+        // The profile that already had the ID keeps it, so that it does not
+        // change under clients
+        let known_name = current
+            .iter()
+            .find(|known| known.profile_id == *id)
+            .map(|known| &known.profile_name);
+        let owner = sharing
+            .iter()
+            .copied()
+            .find(|other| Some(&profiles[*other].profile_name) == known_name)
+            .or(sharing.first().copied());
+
+        if id.is_empty() || owner != Some(idx) {
+            profiles[idx].profile_id = profiles_generator::new_profile_id();
+            if !changed.contains(&idx) {
+                changed.push(idx);
+            }
+        }
+    }
+
+    changed
+}
 
-        let mut profile = Profile::parse_or_default(&contents, profile_name);
+/// Moves the file aside if it exists and records why it could not be loaded
+fn set_aside_broken_file(path: &Path, error: String) {
+    error!("Could not load {}: {error}", path.display());
+
+    let quarantined_to = if path.exists() {
+        match persistence::quarantine(path) {
+            Ok(quarantined_to) => {
+                warn!(
+                    "Moved {} to {}, replacing it",
+                    path.display(),
+                    quarantined_to.display()
+                );
+                Some(quarantined_to)
+            }
+            Err(error) => {
+                error!("Could not move {} aside: {error}", path.display());
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    load_errors::record_load_error(LoadError {
+        path: path.to_path_buf(),
+        error,
+        quarantined_to,
+    });
+}
 
-        fs::write(path, toml::to_string_pretty(&profile).unwrap())
-            .expect("Could not write to profile");
+/// Reads the config and its profiles without writing to them. Returns an
+/// error if the files are not valid or refer to profiles that do
+/// not exist
+fn read_config_files(
+    config_path: &Path,
+    profiles_path: &Path,
+) -> Result<(Config, Vec<Profile>), String> {
+    let content = fs::read_to_string(config_path)
+        .map_err(|error| format!("Could not read config: {error}"))?;
+    let config = Config::parse(&content)?;
+
+    let mut referenced = vec![&config.ac_profile, &config.bat_profile];
+    referenced.extend(config.profile_override.iter());
+    referenced.extend(config.profile_rules.iter().map(|r| &r.profile));
+    if let Some(missing) = referenced.iter().find(|p| !config.profiles.contains(p)) {
+        return Err(format!(
+            "Config refers to profile {missing} which is not listed"
+        ));
+    }
+
+    let mut profiles = Vec::new();
+    for profile_name in config.profiles.iter() {
+        let path = profiles_path.join(format!("{profile_name}.toml"));
+        let content = fs::read_to_string(&path)
+            .map_err(|error| format!("Could not read profile {profile_name}: {error}"))?;
+        let mut profile = Profile::parse(&content, profile_name)
+            .map_err(|error| format!("Profile {profile_name}: {error}"))?;
 
         profile.profile_name = profile_name.clone();
         profiles.push(profile);
     }
 
-    profiles
+    Ok((config, profiles))
 }
 
 pub fn serialize_config(config: &Config, path: &Path) {
-    fs::write(
+    persistence::write_atomically(
         path,
-        toml::to_string_pretty(config).expect("Could not serialize config"),
+        &toml::to_string_pretty(config).expect("Could not serialize config"),
     )
     .expect("Could not write to config");
 }
 
-fn serialize_profiles_clean(profiles: &[Profile], path: &Path) {
-    for entry in fs::read_dir(path).unwrap() {
-        let entry = entry.unwrap();
-        let path = entry.path();
-
-        if path.is_dir() {
-            fs::remove_dir_all(&path).unwrap();
-        } else {
-            fs::remove_file(&path).unwrap();
-        }
-    }
-
-    serialize_profiles(profiles, path);
-}
-
 fn serialize_profiles(profiles: &[Profile], path: &Path) {
     for profile in profiles.iter() {
         let path = path.join(format!("{}.toml", profile.profile_name));
-        let mut file = fs::File::create(&path).expect("Could not read file");
-        file.write_all(
-            toml::to_string_pretty(profile)
-                .expect("Could not serialize profile")
-                .as_bytes(),
+        persistence::write_atomically(
+            &path,
+            &toml::to_string_pretty(profile).expect("Could not serialize profile"),
         )
         .expect("Could not write to profile file");
     }
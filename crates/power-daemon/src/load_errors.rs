@@ -0,0 +1,37 @@
+use std::{fmt::Display, path::PathBuf, sync::Mutex};
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+/// A config or profile file that could not be loaded and was replaced
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub error: String,
+    /// Where the broken file was moved to, None if there was no file or it
+    /// could not be moved
+    pub quarantined_to: Option<PathBuf>,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)?;
+        if let Some(ref quarantined_to) = self.quarantined_to {
+            write!(f, " (moved to {})", quarantined_to.display())?;
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    /// Files that could not be loaded since the daemon started
+    static ref LOAD_ERRORS: Mutex<Vec<LoadError>> = Mutex::new(Vec::new());
+}
+
+pub(crate) fn record_load_error(error: LoadError) {
+    LOAD_ERRORS.lock().unwrap().push(error);
+}
+
+pub fn load_errors() -> Vec<LoadError> {
+    LOAD_ERRORS.lock().unwrap().clone()
+}
@@ -0,0 +1,324 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::{persistence, profiles_generator, Config, DefaultProfileType, Profile, SystemInfo};
+
+/// Version of the profile files written by this version of the daemon.
+/// Files without a version are version 0
+pub const PROFILE_SCHEMA_VERSION: u32 = 2;
+/// Version of the config file written by this version of the daemon. Files
+/// without a version are version 0
+pub const CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// Turns a file of version `from` into one of version `from + 1`
+struct MigrationStep {
+    from: u32,
+    description: &'static str,
+    migrate: fn(&mut Table),
+}
+
+/// Every profile step, ordered by version. Renaming or retyping a setting
+/// needs a new step and a bump of `PROFILE_SCHEMA_VERSION`
+const PROFILE_MIGRATIONS: &[MigrationStep] = &[
+    MigrationStep {
+        from: 0,
+        description: "Add the settings groups that unversioned profiles lack",
+        migrate: add_missing_profile_groups,
+    },
+    MigrationStep {
+        from: 1,
+        description: "Give the profile a stable ID",
+        migrate: add_profile_id,
+    },
+];
+
+/// Every config step, ordered by version
+const CONFIG_MIGRATIONS: &[MigrationStep] = &[MigrationStep {
+    from: 0,
+    description: "Add the options that unversioned configs lack",
+    migrate: add_missing_config_options,
+}];
+
+/// What migrating a file to the current schema version changes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    /// Descriptions of the steps that were run
+    pub steps: Vec<String>,
+    /// Every value that was added, removed or changed, e.g.
+    /// "kernel_settings.vm_writeback: unset -> 45"
+    pub changes: Vec<String>,
+}
+
+impl MigrationReport {
+    pub fn is_migrated(&self) -> bool {
+        self.from_version != self.to_version
+    }
+}
+
+/// Migration of a single file that `migrate_files` went through
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileMigration {
+    pub path: PathBuf,
+    pub result: Result<MigrationReport, String>,
+}
+
+impl Display for FileMigration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.result {
+            Ok(ref report) if report.is_migrated() => {
+                writeln!(
+                    f,
+                    "{}: version {} -> {}",
+                    self.path.display(),
+                    report.from_version,
+                    report.to_version
+                )?;
+                for step in report.steps.iter() {
+                    writeln!(f, "    {step}")?;
+                }
+                for change in report.changes.iter() {
+                    writeln!(f, "        {change}")?;
+                }
+                Ok(())
+            }
+            Ok(ref report) => writeln!(
+                f,
+                "{}: up to date (version {})",
+                self.path.display(),
+                report.to_version
+            ),
+            Err(ref error) => writeln!(f, "{}: {error}", self.path.display()),
+        }
+    }
+}
+
+/// Parses a profile file of any supported version, migrating it to the
+/// current one
+pub fn migrate_profile(contents: &str) -> Result<(Profile, MigrationReport), String> {
+    let table = contents
+        .parse::<Table>()
+        .map_err(|error| format!("Could not parse profile: {error}"))?;
+
+    migrate_profile_table(table)
+}
+
+pub(crate) fn migrate_profile_table(table: Table) -> Result<(Profile, MigrationReport), String> {
+    let (mut table, report) =
+        run_steps(table, PROFILE_MIGRATIONS, PROFILE_SCHEMA_VERSION, "Profile")?;
+    // Profiles written by hand may lack the ID whatever their version
+    add_profile_id(&mut table);
+
+    let profile =
+        Table::try_into(table).map_err(|error| format!("Could not parse profile: {error}"))?;
+
+    Ok((profile, report))
+}
+
+/// Parses a config file of any supported version, migrating it to the current
+/// one
+pub fn migrate_config(contents: &str) -> Result<(Config, MigrationReport), String> {
+    let table = contents
+        .parse::<Table>()
+        .map_err(|error| format!("Could not parse config: {error}"))?;
+
+    let (table, report) = run_steps(table, CONFIG_MIGRATIONS, CONFIG_SCHEMA_VERSION, "Config")?;
+
+    let config =
+        Table::try_into(table).map_err(|error| format!("Could not parse config: {error}"))?;
+
+    Ok((config, report))
+}
+
+/// Migrates the config and the profiles it lists to the current schema
+/// version. Nothing is written if `write` is false, so that the reports tell
+/// what would change
+pub fn migrate_files(config_path: &Path, profiles_path: &Path, write: bool) -> Vec<FileMigration> {
+    let mut migrations = Vec::new();
+
+    let config = fs::read_to_string(config_path)
+        .map_err(|error| format!("Could not read config: {error}"))
+        .and_then(|contents| migrate_config(&contents));
+
+    let config = match config {
+        Ok((config, report)) => {
+            let result = write_if_migrated(write, config_path, &config, report);
+            migrations.push(FileMigration {
+                path: config_path.to_path_buf(),
+                result,
+            });
+            config
+        }
+        Err(error) => {
+            migrations.push(FileMigration {
+                path: config_path.to_path_buf(),
+                result: Err(error),
+            });
+            return migrations;
+        }
+    };
+
+    for profile_name in config.profiles.iter() {
+        let path = profiles_path.join(format!("{profile_name}.toml"));
+
+        let result = fs::read_to_string(&path)
+            .map_err(|error| format!("Could not read profile: {error}"))
+            .and_then(|contents| migrate_profile(&contents))
+            .and_then(|(profile, report)| write_if_migrated(write, &path, &profile, report));
+
+        migrations.push(FileMigration { path, result });
+    }
+
+    migrations
+}
+
+fn write_if_migrated(
+    write: bool,
+    path: &Path,
+    migrated: &impl Serialize,
+    report: MigrationReport,
+) -> Result<MigrationReport, String> {
+    if write && report.is_migrated() {
+        info!(
+            "Migrating {} from version {} to {}",
+            path.display(),
+            report.from_version,
+            report.to_version
+        );
+
+        let content = toml::to_string_pretty(migrated)
+            .map_err(|error| format!("Could not serialize migrated file: {error}"))?;
+        persistence::write_atomically(path, &content)
+            .map_err(|error| format!("Could not write migrated file: {error}"))?;
+    }
+
+    Ok(report)
+}
+
+fn run_steps(
+    mut table: Table,
+    steps: &[MigrationStep],
+    current_version: u32,
+    kind: &str,
+) -> Result<(Table, MigrationReport), String> {
+    let from_version = match table.get("schema_version") {
+        None => 0,
+        Some(Value::Integer(version)) => u32::try_from(*version)
+            .map_err(|_| format!("{kind} has an invalid schema version {version}"))?,
+        Some(value) => return Err(format!("{kind} has an invalid schema version {value}")),
+    };
+
+    if from_version > current_version {
+        return Err(format!(
+            "{kind} has schema version {from_version}, but only versions up to {current_version} are supported"
+        ));
+    }
+
+    let original = table.clone();
+    let mut report = MigrationReport {
+        from_version,
+        to_version: current_version,
+        steps: Vec::new(),
+        changes: Vec::new(),
+    };
+
+    for step in steps.iter().filter(|step| step.from >= from_version) {
+        (step.migrate)(&mut table);
+        table.insert("schema_version".to_string(), (step.from + 1).into());
+        report.steps.push(format!(
+            "{} -> {}: {}",
+            step.from,
+            step.from + 1,
+            step.description
+        ));
+    }
+
+    if report.is_migrated() {
+        compare_tables("", &original, &table, &mut report.changes);
+    }
+
+    Ok((table, report))
+}
+
+/// Unversioned profiles were merged with their base profile whenever they
+/// lacked settings. Only whole groups are added now, settings that are
+/// missing inside a group stay unset
+fn add_missing_profile_groups(table: &mut Table) {
+    let base_profile = table
+        .get("base_profile")
+        .cloned()
+        .and_then(|value| value.try_into::<DefaultProfileType>().ok());
+    let name = table
+        .get("profile_name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    let defaults = match base_profile {
+        Some(profile_type) => {
+            profiles_generator::create_default(&name, profile_type, &SystemInfo::obtain())
+        }
+        None => profiles_generator::create_empty(&name),
+    };
+
+    add_missing_keys(table, &defaults);
+}
+
+/// The ID is left empty, it gets assigned when the profile is loaded so that
+/// migrating the same file twice gives the same result
+fn add_profile_id(table: &mut Table) {
+    table
+        .entry("profile_id")
+        .or_insert_with(|| String::new().into());
+}
+
+fn add_missing_config_options(table: &mut Table) {
+    add_missing_keys(table, &Config::create_default());
+}
+
+fn add_missing_keys(table: &mut Table, defaults: &impl Serialize) {
+    let Ok(defaults) = Table::try_from(defaults) else {
+        return;
+    };
+
+    for (key, value) in defaults {
+        table.entry(key).or_insert(value);
+    }
+}
+
+/// Lists the leaf values that differ between the tables
+fn compare_tables(prefix: &str, from: &Table, to: &Table, changes: &mut Vec<String>) {
+    let mut keys: Vec<&String> = from.keys().chain(to.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    for key in keys {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match (from.get(key), to.get(key)) {
+            (Some(Value::Table(from)), Some(Value::Table(to))) => {
+                compare_tables(&path, from, to, changes)
+            }
+            (Some(Value::Table(from)), None) => compare_tables(&path, from, &Table::new(), changes),
+            (None, Some(Value::Table(to))) => compare_tables(&path, &Table::new(), to, changes),
+            (from, to) if from != to => changes.push(format!(
+                "{path}: {} -> {}",
+                from.map_or("unset".to_string(), Value::to_string),
+                to.map_or("unset".to_string(), Value::to_string)
+            )),
+            _ => {}
+        }
+    }
+}
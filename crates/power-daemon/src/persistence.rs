@@ -0,0 +1,123 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, Metadata},
+    io::{self, Write},
+    os::unix::fs::{fchown, MetadataExt},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, warn};
+
+/// Path of the copy of the previous generation of `path`, e.g.
+/// "Powersave.toml.bak"
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// Replaces the content of `path` so that a crash leaves either the old or
+/// the new content behind, never a partially written file. The old content
+/// is kept as the backup of the file, replacing the previous backup
+pub(crate) fn write_atomically(path: &Path, content: &str) -> io::Result<()> {
+    // Rewriting unchanged files would only rotate the backup away
+    if fs::read_to_string(path).is_ok_and(|current| current == content) {
+        return Ok(());
+    }
+
+    let temp_path = temp_path(path);
+    let original = fs::metadata(path).ok();
+    let result = write_synced(&temp_path, content, original.as_ref()).and_then(|()| {
+        if path.exists() {
+            keep_backup(path);
+        }
+        fs::rename(&temp_path, path)?;
+        sync_parent(path)
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Removes `path` by turning it into its backup, so that it can still be
+/// recovered by hand. Files that are already gone are left alone
+pub(crate) fn remove_with_backup(path: &Path) -> io::Result<()> {
+    debug!("Moving {} to its backup", path.display());
+
+    match fs::rename(path, backup_path(path)) {
+        Ok(()) => sync_parent(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            debug!("{} does not exist, nothing to remove", path.display());
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Moves a file that could not be loaded aside, so that it can be fixed by
+/// hand while a replacement is written in its place. Returns the new path
+pub(crate) fn quarantine(path: &Path) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let quarantined_path = with_suffix(path, &format!(".broken-{timestamp}"));
+
+    fs::rename(path, &quarantined_path)?;
+    sync_parent(path)?;
+
+    Ok(quarantined_path)
+}
+
+/// Hard links the current file as its backup. Failing to do so is not a
+/// reason to not write the new content
+fn keep_backup(path: &Path) {
+    let backup_path = backup_path(path);
+
+    let result = match fs::remove_file(&backup_path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => fs::hard_link(path, &backup_path),
+    };
+
+    if let Err(error) = result {
+        warn!("Could not back up {}: {error}", path.display());
+    }
+}
+
+/// Writes a new file, with the mode and owner of `original` if given
+fn write_synced(path: &Path, content: &str, original: Option<&Metadata>) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    // Before writing, so that the content is never more accessible than it
+    // was
+    if let Some(original) = original {
+        file.set_permissions(original.permissions())?;
+        fchown(&file, Some(original.uid()), Some(original.gid()))?;
+    }
+    file.write_all(content.as_bytes())?;
+    file.sync_all()
+}
+
+/// Makes the renames in the directory of `path` durable
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+/// Hidden file next to `path`. It does not end in .toml, so that it is never
+/// mistaken for a profile
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(suffix);
+    PathBuf::from(path)
+}
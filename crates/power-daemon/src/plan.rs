@@ -0,0 +1,64 @@
+use std::{cell::RefCell, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+/// A single change that applying a profile would make to the system
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum PlannedAction {
+    /// Write `value` to a sysfs/procfs path
+    Write { path: String, value: String },
+    /// Run a command line
+    Command { command: String },
+    /// Unload and load back a kernel module with new parameters
+    ModuleReload { module: String, parameters: String },
+}
+
+impl Display for PlannedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlannedAction::Write { path, value } => write!(f, "write \"{value}\" to {path}"),
+            PlannedAction::Command { command } => write!(f, "run {command}"),
+            PlannedAction::ModuleReload { module, parameters } => {
+                write!(f, "reload module {module} with parameters \"{parameters}\"")
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// Actions recorded on this thread, None when changes should be applied
+    /// to the system
+    static PLANNED_ACTIONS: RefCell<Option<Vec<PlannedAction>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` on the current thread recording every action it would make
+/// instead of executing them
+pub fn record_actions(f: impl FnOnce()) -> Vec<PlannedAction> {
+    let previous = PLANNED_ACTIONS.with(|p| p.borrow_mut().replace(Vec::new()));
+
+    f();
+
+    PLANNED_ACTIONS.with(|p| {
+        let mut planned = p.borrow_mut();
+        let actions = planned.take().unwrap_or_default();
+        *planned = previous;
+        actions
+    })
+}
+
+pub fn is_planning() -> bool {
+    PLANNED_ACTIONS.with(|p| p.borrow().is_some())
+}
+
+/// Records the action if the current thread is planning. Returns true if the
+/// action was recorded and therefore should not be executed
+pub(crate) fn record_if_planning(action: impl FnOnce() -> PlannedAction) -> bool {
+    PLANNED_ACTIONS.with(|p| {
+        if let Some(ref mut planned) = *p.borrow_mut() {
+            planned.push(action());
+            true
+        } else {
+            false
+        }
+    })
+}
@@ -5,18 +5,30 @@ use log::{debug, error, info, warn};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 
+use crate::sysfs::battery::{iterate_batteries, Battery};
 use crate::sysfs::rapl::{iterate_rapl_interfaces, IntelRaplInterface, InterfaceType};
 use crate::{
+    diff::{self, SettingChange},
+    drift::{self, Drift},
     helpers::{
-        command_exists, run_command, run_graphical_command, run_graphical_command_in_background,
-        WhiteBlackList,
+        command_exists, reload_kernel_modules, run_command, run_graphical_command,
+        run_graphical_command_in_background, WhiteBlackList,
     },
+    migration,
+    plan::{self, PlannedAction},
     profiles_generator::{self, DefaultProfileType},
+    report::{
+        record_outcome, record_unsupported, ApplyOutcome, ApplyReport, GroupReport, SettingsGroup,
+    },
     sysfs::{
         gpu::*,
         reading::file_content_to_string,
+        root::SysRoot,
+        snapshot::restore_unset,
         writing::{write_all_cores, write_bool, write_str, write_u32},
     },
+    transaction::apply_group,
+    validation::{self, ProfileDiagnostic},
     ReducedUpdate, SystemInfo,
 };
 
@@ -41,10 +53,19 @@ impl ProfilesInfo {
     pub fn try_find_profile_index_by_name(&self, name: &str) -> Option<usize> {
         self.profiles.iter().position(|p| p.profile_name == name)
     }
+    pub fn try_find_profile_index_by_id(&self, id: &str) -> Option<usize> {
+        self.profiles.iter().position(|p| p.profile_id == id)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
 pub struct Profile {
+    /// Version of the format of the profile, see `migration`
+    pub schema_version: u32,
+    /// Identifies the profile across renames and reorders, unlike its name or
+    /// its position in the config. Assigned when the profile is loaded if
+    /// empty
+    pub profile_id: String,
     /// Name of the profile. Should match the profile filename
     pub profile_name: String,
     pub base_profile: Option<DefaultProfileType>,
@@ -64,123 +85,212 @@ pub struct Profile {
     pub audio_settings: AudioSettings,
     pub gpu_settings: GpuSettings,
     pub rapl_settings: IntelRaplSettings,
+    pub battery_settings: BatterySettings,
 }
 
 impl Profile {
-    pub fn apply_all(&self) {
+    pub fn apply_all(&self) -> ApplyReport {
         info!("Applying profile: {}", self.profile_name);
 
-        let settings_functions: Vec<Box<dyn FnOnce() + Send>> = vec![
-            Box::new(|| self.sleep_settings.apply()),
+        let settings_functions: Vec<Box<dyn FnOnce() -> Vec<GroupReport> + Send>> = vec![
+            Box::new(|| vec![restore_unset(self.sleep_settings.apply())]),
             Box::new(|| {
-                self.cpu_settings.apply();
-                self.cpu_core_settings.apply();
+                vec![
+                    restore_unset(self.cpu_settings.apply()),
+                    restore_unset(self.cpu_core_settings.apply()),
+                ]
             }),
-            Box::new(|| self.screen_settings.apply()),
-            Box::new(|| self.radio_settings.apply()),
-            Box::new(|| self.network_settings.apply()),
-            Box::new(|| self.aspm_settings.apply()),
-            Box::new(|| self.pci_settings.apply()),
-            Box::new(|| self.usb_settings.apply()),
-            Box::new(|| self.sata_settings.apply()),
-            Box::new(|| self.kernel_settings.apply()),
-            Box::new(|| self.firmware_settings.apply()),
-            Box::new(|| self.audio_settings.apply()),
-            Box::new(|| self.gpu_settings.apply()),
-            Box::new(|| self.rapl_settings.apply()),
+            Box::new(|| vec![restore_unset(self.screen_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.radio_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.network_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.aspm_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.pci_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.usb_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.sata_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.kernel_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.firmware_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.audio_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.gpu_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.rapl_settings.apply())]),
+            Box::new(|| vec![restore_unset(self.battery_settings.apply())]),
         ];
 
-        settings_functions.into_par_iter().for_each(|f| f());
+        let groups: Vec<Vec<GroupReport>> =
+            settings_functions.into_par_iter().map(|f| f()).collect();
+
+        let report = ApplyReport {
+            profile_name: self.profile_name.clone(),
+            groups: groups.into_iter().flatten().collect(),
+        };
+
+        if report.has_failures() {
+            warn!(
+                "Some settings of profile {} could not be applied",
+                self.profile_name
+            );
+        }
+        let rolled_back = report.rolled_back_groups();
+        if !rolled_back.is_empty() {
+            warn!("Rolled back settings groups {rolled_back:?}");
+        }
+
+        report
     }
 
-    pub fn apply_reduced(&self, reduced_update: &ReducedUpdate) {
-        debug!("Applying reduced amount of settings: {reduced_update:?}");
+    /// Returns the ordered list of actions that applying this profile would
+    /// make, without changing anything on the system
+    pub fn plan(&self) -> Vec<PlannedAction> {
+        plan::record_actions(|| {
+            self.sleep_settings.apply();
+            self.cpu_settings.apply();
+            self.cpu_core_settings.apply();
+            self.screen_settings.apply();
+            self.radio_settings.apply();
+            self.network_settings.apply();
+            self.aspm_settings.apply();
+            self.pci_settings.apply();
+            self.usb_settings.apply();
+            self.sata_settings.apply();
+            self.kernel_settings.apply();
+            self.firmware_settings.apply();
+            self.audio_settings.apply();
+            self.gpu_settings.apply();
+            self.rapl_settings.apply();
+            self.battery_settings.apply();
+        })
+    }
 
-        match reduced_update {
-            ReducedUpdate::None => {}
-            ReducedUpdate::Sleep => {
-                self.sleep_settings.apply();
-            }
-            ReducedUpdate::CPU => {
-                self.cpu_settings.apply();
-                self.cpu_core_settings.apply();
-            }
-            ReducedUpdate::CPUCores => self.cpu_core_settings.apply(),
-            ReducedUpdate::SingleCPUCore(idx) => {
-                if let Some(ref cores) = self.cpu_core_settings.cores {
-                    cores[*idx as usize].apply()
-                }
-            }
-            ReducedUpdate::MultipleCPUCores(tochange) => {
-                if let Some(ref cores) = self.cpu_core_settings.cores {
-                    for idx in tochange.iter() {
-                        cores[*idx as usize].apply()
-                    }
-                }
-            }
-            ReducedUpdate::Screen => self.screen_settings.apply(),
-            ReducedUpdate::Radio => self.radio_settings.apply(),
-            ReducedUpdate::Network => self.network_settings.apply(),
-            ReducedUpdate::ASPM => self.aspm_settings.apply(),
-            ReducedUpdate::PCI => self.pci_settings.apply(),
-            ReducedUpdate::USB => self.usb_settings.apply(),
-            ReducedUpdate::SATA => self.sata_settings.apply(),
-            ReducedUpdate::Kernel => self.kernel_settings.apply(),
-            ReducedUpdate::Firmware => self.firmware_settings.apply(),
-            ReducedUpdate::Audio => self.audio_settings.apply(),
-            ReducedUpdate::Gpu => self.gpu_settings.apply(),
-            ReducedUpdate::Rapl => self.rapl_settings.apply(),
+    /// Applies only the settings of `group`
+    pub fn apply_settings_group(&self, group: SettingsGroup) -> GroupReport {
+        match group {
+            SettingsGroup::Sleep => self.sleep_settings.apply(),
+            SettingsGroup::CPU => self.cpu_settings.apply(),
+            SettingsGroup::CPUCores => self.cpu_core_settings.apply(),
+            SettingsGroup::Screen => self.screen_settings.apply(),
+            SettingsGroup::Radio => self.radio_settings.apply(),
+            SettingsGroup::Network => self.network_settings.apply(),
+            SettingsGroup::ASPM => self.aspm_settings.apply(),
+            SettingsGroup::PCI => self.pci_settings.apply(),
+            SettingsGroup::USB => self.usb_settings.apply(),
+            SettingsGroup::SATA => self.sata_settings.apply(),
+            SettingsGroup::Kernel => self.kernel_settings.apply(),
+            SettingsGroup::Firmware => self.firmware_settings.apply(),
+            SettingsGroup::Audio => self.audio_settings.apply(),
+            SettingsGroup::Gpu => self.gpu_settings.apply(),
+            SettingsGroup::Rapl => self.rapl_settings.apply(),
+            SettingsGroup::Battery => self.battery_settings.apply(),
         }
     }
 
-    pub fn parse_or_default(contents: &str, profile_name: &str) -> Profile {
-        match toml::from_str(contents) {
-            Ok(p) => p,
-            Err(_) => {
-                #[derive(Deserialize)]
-                struct ProfileTypeOnly {
-                    pub base_profile: Option<DefaultProfileType>,
-                }
+    /// Checks the profile against the hardware it is going to be applied on
+    pub fn validate(&self, system_info: &SystemInfo) -> Vec<ProfileDiagnostic> {
+        validation::validate_profile(self, system_info)
+    }
 
-                warn!("Could not parse profile {profile_name}. Attempting to migrate to newer version.");
+    /// Lists the settings whose value differs in `other`
+    pub fn diff(&self, other: &Profile) -> Vec<SettingChange> {
+        diff::diff_profiles(self, other)
+    }
 
-                let profile_type = toml::from_str::<ProfileTypeOnly>(contents)
-                    .expect("Could not parse base profile type")
-                    .base_profile;
+    /// Lists the settings of this profile that have a different value on the
+    /// live system. Settings that this profile leaves unset or that are not
+    /// part of `SystemInfo` are not compared
+    pub fn diff_live(&self, system_info: &SystemInfo) -> Vec<SettingChange> {
+        self.diff(&diff::live_profile(system_info))
+            .into_iter()
+            .filter(|change| change.from.is_some() && change.to.is_some())
+            .collect()
+    }
 
-                let base_profile = toml::to_string(&if let Some(profile_type) = profile_type {
-                    profiles_generator::create_default(
-                        profile_name,
-                        profile_type,
-                        &SystemInfo::obtain(),
-                    )
-                } else {
-                    profiles_generator::create_empty(profile_name)
+    /// Compares the values this profile sets with the ones currently on the
+    /// system
+    pub fn detect_drift(&self) -> Vec<Drift> {
+        SettingsGroup::ALL
+            .into_iter()
+            .flat_map(|group| {
+                drift::detect_group_drift(group, || {
+                    self.apply_settings_group(group);
                 })
-                .expect("Could not merge default profile and user profile");
+            })
+            .collect()
+    }
 
-                let merged = serde_toml_merge::merge(
-                    base_profile.parse().unwrap(),
-                    contents.parse().unwrap(),
-                )
-                .unwrap();
+    pub fn apply_reduced(&self, reduced_update: &ReducedUpdate) -> ApplyReport {
+        debug!("Applying reduced amount of settings: {reduced_update:?}");
 
-                debug!("Merged profile {merged:?}");
+        // Restoring unset nodes requires the whole group to be applied, so
+        // single cores are left as is
+        let groups = match reduced_update {
+            ReducedUpdate::None => vec![],
+            ReducedUpdate::Sleep => vec![restore_unset(self.sleep_settings.apply())],
+            ReducedUpdate::CPU => vec![
+                restore_unset(self.cpu_settings.apply()),
+                restore_unset(self.cpu_core_settings.apply()),
+            ],
+            ReducedUpdate::CPUCores => vec![restore_unset(self.cpu_core_settings.apply())],
+            ReducedUpdate::SingleCPUCore(idx) => {
+                vec![apply_group(SettingsGroup::CPUCores, || {
+                    if let Some(ref cores) = self.cpu_core_settings.cores {
+                        apply_core(cores, *idx)
+                    }
+                })]
+            }
+            ReducedUpdate::MultipleCPUCores(tochange) => {
+                vec![apply_group(SettingsGroup::CPUCores, || {
+                    if let Some(ref cores) = self.cpu_core_settings.cores {
+                        for idx in tochange.iter() {
+                            apply_core(cores, *idx)
+                        }
+                    }
+                })]
+            }
+            ReducedUpdate::Screen => vec![restore_unset(self.screen_settings.apply())],
+            ReducedUpdate::Radio => vec![restore_unset(self.radio_settings.apply())],
+            ReducedUpdate::Network => vec![restore_unset(self.network_settings.apply())],
+            ReducedUpdate::ASPM => vec![restore_unset(self.aspm_settings.apply())],
+            ReducedUpdate::PCI => vec![restore_unset(self.pci_settings.apply())],
+            ReducedUpdate::USB => vec![restore_unset(self.usb_settings.apply())],
+            ReducedUpdate::SATA => vec![restore_unset(self.sata_settings.apply())],
+            ReducedUpdate::Kernel => vec![restore_unset(self.kernel_settings.apply())],
+            ReducedUpdate::Firmware => vec![restore_unset(self.firmware_settings.apply())],
+            ReducedUpdate::Audio => vec![restore_unset(self.audio_settings.apply())],
+            ReducedUpdate::Gpu => vec![restore_unset(self.gpu_settings.apply())],
+            ReducedUpdate::Rapl => vec![restore_unset(self.rapl_settings.apply())],
+            ReducedUpdate::Battery => vec![restore_unset(self.battery_settings.apply())],
+        };
 
-                let profile =
-                    Profile::deserialize(merged).expect("Could not parse updated profile");
+        ApplyReport {
+            profile_name: self.profile_name.clone(),
+            groups,
+        }
+    }
+
+    /// Parses a profile file, migrating profiles of older versions
+    pub fn parse(contents: &str, profile_name: &str) -> Result<Profile, String> {
+        let (profile, report) = migration::migrate_profile(contents)?;
 
-                profile
+        if report.is_migrated() {
+            info!(
+                "Migrated profile {profile_name} from version {} to {}",
+                report.from_version, report.to_version
+            );
+            for change in report.changes.iter() {
+                debug!("{change}");
             }
         }
+
+        Ok(profile)
     }
 
+    /// The profile as it was generated, keeping its ID
     pub fn get_original_values(&self, system_info: &SystemInfo) -> Profile {
-        if let Some(base_profile_type) = self.base_profile {
+        let mut original = if let Some(base_profile_type) = self.base_profile {
             profiles_generator::create_default(&self.profile_name, base_profile_type, system_info)
         } else {
             profiles_generator::create_empty(&self.profile_name)
-        }
+        };
+        original.profile_id = self.profile_id.clone();
+        original
     }
 }
 
@@ -197,37 +307,42 @@ lazy_static! {
 }
 
 impl SleepSettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying Sleep settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Sleep, || {
+            info!(
+                "Applying Sleep settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if let Some(turn_off_screen_after) = self.turn_off_screen_after {
-            if command_exists("xset") {
-                let time_in_secs = turn_off_screen_after * 60;
-                run_graphical_command(&format!(
-                    "xset dpms {time_in_secs} {time_in_secs} {time_in_secs}"
-                ));
-            } else {
-                error!("Attempted to set screen turn off timeout when xset is not installed");
+            if let Some(turn_off_screen_after) = self.turn_off_screen_after {
+                if command_exists("xset") {
+                    let time_in_secs = turn_off_screen_after * 60;
+                    run_graphical_command(&format!(
+                        "xset dpms {time_in_secs} {time_in_secs} {time_in_secs}"
+                    ));
+                } else {
+                    error!("Attempted to set screen turn off timeout when xset is not installed");
+                    record_unsupported("Screen turn off timeout requires xset");
+                }
+            } else if command_exists("xset") {
+                run_graphical_command("xset -dpms");
             }
-        } else if command_exists("xset") {
-            run_graphical_command("xset -dpms");
-        }
 
-        Self::kill_previous_autolock_instance();
+            Self::kill_previous_autolock_instance();
 
-        if let Some(suspend_after) = self.suspend_after {
-            if command_exists("xautolock") {
-                *AUTOLOCK_INSTANCE.lock().unwrap() = run_graphical_command_in_background(&format!(
-                    "xautolock -time {suspend_after} -locker 'systemctl suspend'"
-                ))
-                .into();
-            } else {
-                error!("Attempted to set suspend time when xautolock is not installed");
+            if let Some(suspend_after) = self.suspend_after {
+                if command_exists("xautolock") {
+                    if let Some(instance) = run_graphical_command_in_background(&format!(
+                        "xautolock -time {suspend_after} -locker 'systemctl suspend'"
+                    )) {
+                        *AUTOLOCK_INSTANCE.lock().unwrap() = Some(instance);
+                    }
+                } else {
+                    error!("Attempted to set suspend time when xautolock is not installed");
+                    record_unsupported("Suspend time requires xautolock");
+                }
             }
-        }
+        })
     }
 
     fn kill_previous_autolock_instance() {
@@ -240,6 +355,11 @@ impl SleepSettings {
                 run_graphical_command("xautolock -exit");
             }
 
+            // The previous instance was not killed, waiting would block forever
+            if plan::is_planning() {
+                return;
+            }
+
             instance
                 .wait()
                 .expect("Could not wait for xautolock process to exit after killing.");
@@ -279,95 +399,130 @@ pub struct CPUSettings {
 }
 
 impl CPUSettings {
-    pub fn apply(&self) {
-        info!("Applying CPU settings on {:?}", std::thread::current().id());
-
-        if let Some(ref mode) = self.mode {
-            if fs::metadata("/sys/devices/system/cpu/intel_pstate").is_ok() {
-                write_str("/sys/devices/system/cpu/intel_pstate/status", mode)
-            } else if fs::metadata("/sys/devices/system/cpu/amd_pstate").is_ok() {
-                write_str("/sys/devices/system/cpu/amd_pstate/status", mode);
-            } else {
-                error!("Scaling driver operation mode is only supported on intel_pstate and amd_pstate drivers.")
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::CPU, || {
+            info!("Applying CPU settings on {:?}", std::thread::current().id());
+
+            if let Some(ref mode) = self.mode {
+                if fs::metadata(SysRoot::path("/sys/devices/system/cpu/intel_pstate")).is_ok() {
+                    write_str(
+                        SysRoot::path("/sys/devices/system/cpu/intel_pstate/status"),
+                        mode,
+                    )
+                } else if fs::metadata(SysRoot::path("/sys/devices/system/cpu/amd_pstate")).is_ok()
+                {
+                    write_str(
+                        SysRoot::path("/sys/devices/system/cpu/amd_pstate/status"),
+                        mode,
+                    );
+                } else {
+                    error!("Scaling driver operation mode is only supported on intel_pstate and amd_pstate drivers.");
+                    record_unsupported(
+                        "Scaling driver operation mode requires intel_pstate or amd_pstate",
+                    );
+                }
             }
-        }
 
-        // Governor and hwp_dynaamic_boost needs to run before epp options because those determine if epp is changable
-        if let Some(hwp_dynamic_boost) = self.hwp_dyn_boost {
-            if fs::metadata("/sys/devices/system/cpu/intel_pstate").is_ok() {
-                write_bool(
-                    "/sys/devices/system/cpu/intel_pstate/hwp_dynamic_boost",
-                    hwp_dynamic_boost,
-                );
-            } else {
-                error!("HWP dynamic boost is currently only supported for intel CPUs with intel_pstate");
+            // Governor and hwp_dynaamic_boost needs to run before epp options because those determine if epp is changable
+            if let Some(hwp_dynamic_boost) = self.hwp_dyn_boost {
+                if fs::metadata(SysRoot::path("/sys/devices/system/cpu/intel_pstate")).is_ok() {
+                    write_bool(
+                        SysRoot::path("/sys/devices/system/cpu/intel_pstate/hwp_dynamic_boost"),
+                        hwp_dynamic_boost,
+                    );
+                } else {
+                    error!("HWP dynamic boost is currently only supported for intel CPUs with intel_pstate");
+                    record_unsupported("HWP dynamic boost requires intel_pstate");
+                }
             }
-        }
 
-        if let Some(ref governor) = self.governor {
-            write_all_cores("cpufreq/scaling_governor", governor);
-        }
+            if let Some(ref governor) = self.governor {
+                write_all_cores("cpufreq/scaling_governor", governor);
+            }
 
-        if let Some(ref epp) = self.energy_perf_ratio {
-            if fs::metadata("/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference")
+            if let Some(ref epp) = self.energy_perf_ratio {
+                if fs::metadata(SysRoot::path(
+                    "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference",
+                ))
                 .is_ok()
-            {
-                write_all_cores("cpufreq/energy_performance_preference", epp);
-            } else if fs::metadata("/sys/devices/system/cpu/cpu0/power/energy_perf_bias").is_ok() {
-                debug!(
-                    "System does not have EPP but EPB is present, translating and setting EPB..."
-                );
-                write_all_cores("power/energy_perf_bias", &Self::translate_epb_to_epp(epp));
-            } else {
-                warn!("System does not have EPP or EPB but configuration attempted to set anyways. Ignoring...");
+                {
+                    write_all_cores("cpufreq/energy_performance_preference", epp);
+                } else if fs::metadata(SysRoot::path(
+                    "/sys/devices/system/cpu/cpu0/power/energy_perf_bias",
+                ))
+                .is_ok()
+                {
+                    debug!(
+                        "System does not have EPP but EPB is present, translating and setting EPB..."
+                    );
+                    write_all_cores("power/energy_perf_bias", &Self::translate_epp_to_epb(epp));
+                } else {
+                    warn!("System does not have EPP or EPB but configuration attempted to set anyways. Ignoring...");
+                    record_unsupported("Energy performance preference requires EPP or EPB");
+                }
             }
-        }
 
-        if let Some(boost) = self.boost {
-            if fs::metadata("/sys/devices/system/cpu/intel_pstate/no_turbo").is_ok() {
-                // using intel turbo
-                write_bool("/sys/devices/system/cpu/intel_pstate/no_turbo", !boost);
-            } else if fs::metadata("/sys/devices/system/cpu/cpufreq/boost").is_ok() {
-                // using amd precission boost
-                write_bool("/sys/devices/system/cpu/cpufreq/boost", boost);
-            } else {
-                error!("CPU boost technology is unsupported by your CPU/driver")
+            if let Some(boost) = self.boost {
+                if fs::metadata(SysRoot::path(
+                    "/sys/devices/system/cpu/intel_pstate/no_turbo",
+                ))
+                .is_ok()
+                {
+                    // using intel turbo
+                    write_bool(
+                        SysRoot::path("/sys/devices/system/cpu/intel_pstate/no_turbo"),
+                        !boost,
+                    );
+                } else if fs::metadata(SysRoot::path("/sys/devices/system/cpu/cpufreq/boost"))
+                    .is_ok()
+                {
+                    // using amd precission boost
+                    write_bool(
+                        SysRoot::path("/sys/devices/system/cpu/cpufreq/boost"),
+                        boost,
+                    );
+                } else {
+                    error!("CPU boost technology is unsupported by your CPU/driver");
+                    record_unsupported("CPU boost is unsupported by the CPU/driver");
+                }
             }
-        }
 
-        if let Some(min_frequency) = self.min_freq {
-            write_all_cores(
-                "cpufreq/scaling_min_freq",
-                (min_frequency * 1000).to_string().as_str(),
-            );
-        }
-        if let Some(max_frequency) = self.max_freq {
-            write_all_cores(
-                "cpufreq/scaling_max_freq",
-                (max_frequency * 1000).to_string().as_str(),
-            );
-        }
-
-        if let Some(min_perf_pct) = self.min_perf_pct {
-            if fs::metadata("/sys/devices/system/cpu/intel_pstate").is_ok() {
-                write_u32(
-                    "/sys/devices/system/cpu/intel_pstate/min_perf_pct",
-                    min_perf_pct as u32,
+            if let Some(min_frequency) = self.min_freq {
+                write_all_cores(
+                    "cpufreq/scaling_min_freq",
+                    (min_frequency * 1000).to_string().as_str(),
                 );
-            } else {
-                error!("Min/Max scaling perf percentage is currently only supported for intel CPUs with intel_pstate");
             }
-        }
-        if let Some(max_perf_pct) = self.max_perf_pct {
-            if fs::metadata("/sys/devices/system/cpu/intel_pstate").is_ok() {
-                write_u32(
-                    "/sys/devices/system/cpu/intel_pstate/max_perf_pct",
-                    max_perf_pct as u32,
+            if let Some(max_frequency) = self.max_freq {
+                write_all_cores(
+                    "cpufreq/scaling_max_freq",
+                    (max_frequency * 1000).to_string().as_str(),
                 );
-            } else {
-                error!("Min/Max scaling perf percentage is currently only supported for intel CPUs with intel_pstate");
             }
-        }
+
+            if let Some(min_perf_pct) = self.min_perf_pct {
+                if fs::metadata(SysRoot::path("/sys/devices/system/cpu/intel_pstate")).is_ok() {
+                    write_u32(
+                        SysRoot::path("/sys/devices/system/cpu/intel_pstate/min_perf_pct"),
+                        min_perf_pct as u32,
+                    );
+                } else {
+                    error!("Min/Max scaling perf percentage is currently only supported for intel CPUs with intel_pstate");
+                    record_unsupported("Min/Max scaling perf percentage requires intel_pstate");
+                }
+            }
+            if let Some(max_perf_pct) = self.max_perf_pct {
+                if fs::metadata(SysRoot::path("/sys/devices/system/cpu/intel_pstate")).is_ok() {
+                    write_u32(
+                        SysRoot::path("/sys/devices/system/cpu/intel_pstate/max_perf_pct"),
+                        max_perf_pct as u32,
+                    );
+                } else {
+                    error!("Min/Max scaling perf percentage is currently only supported for intel CPUs with intel_pstate");
+                    record_unsupported("Min/Max scaling perf percentage requires intel_pstate");
+                }
+            }
+        })
     }
 
     pub fn translate_epp_to_epb(epp: &str) -> String {
@@ -421,24 +576,39 @@ pub struct CoreSetting {
 }
 
 impl CPUCoreSettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying CPU core settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::CPUCores, || {
+            info!(
+                "Applying CPU core settings on {:?}",
+                std::thread::current().id()
+            );
 
-        // In the UI, when disabling a core and then resetting the core override self.online would be set to None
-        // But the user likely would have meant to return cpu back to the default values in the profile.
-        // Given the way per-core settings work (first apply settings to all cores then individual overrides),
-        // it's logical to also remove all the core-disabling overrides first and then maybe disable individual cores
-        // Could this be fixed in the UI? Yes. Would it be better architecture-wise? Yes. But it's way easier to just to this
-        write_all_cores("online", "1");
-        if self.cores.is_none() {
-            return;
-        }
+            // In the UI, when disabling a core and then resetting the core override self.online would be set to None
+            // But the user likely would have meant to return cpu back to the default values in the profile.
+            // Given the way per-core settings work (first apply settings to all cores then individual overrides),
+            // it's logical to also remove all the core-disabling overrides first and then maybe disable individual cores
+            // Could this be fixed in the UI? Yes. Would it be better architecture-wise? Yes. But it's way easier to just to this
+            write_all_cores("online", "1");
+            if self.cores.is_none() {
+                return;
+            }
 
-        for core in self.cores.as_ref().unwrap().iter() {
-            core.apply();
+            for core in self.cores.as_ref().unwrap().iter() {
+                core.apply();
+            }
+        })
+    }
+}
+
+fn apply_core(cores: &[CoreSetting], idx: u32) {
+    match cores.get(idx as usize) {
+        Some(core) => core.apply(),
+        None => {
+            error!(
+                "Requested to update core setting No {idx} but the profile only has {}, ignoring...",
+                cores.len()
+            );
+            record_unsupported(&format!("Core setting No {idx} does not exist"));
         }
     }
 }
@@ -447,54 +617,67 @@ impl CoreSetting {
     pub fn apply(&self) {
         if let Some(online) = self.online {
             write_bool(
-                format!("/sys/devices/system/cpu/cpu{}/online", self.cpu_id),
+                SysRoot::path(format!("/sys/devices/system/cpu/cpu{}/online", self.cpu_id)),
                 online,
             );
+
+            // Offline cores lose their cpufreq nodes, writing to them would
+            // only fail and roll back the whole group
+            if !online {
+                return;
+            }
         }
 
         if let Some(ref epp) = self.epp {
-            if fs::metadata("/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference")
-                .is_ok()
+            if fs::metadata(SysRoot::path(
+                "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference",
+            ))
+            .is_ok()
             {
-                let path = format!(
+                let path = SysRoot::path(format!(
                     "/sys/devices/system/cpu/cpu{}/cpufreq/energy_performance_preference",
                     self.cpu_id
-                );
+                ));
                 write_str(path, epp);
-            } else if fs::metadata("/sys/devices/system/cpu/cpu0/power/energy_perf_bias").is_ok() {
+            } else if fs::metadata(SysRoot::path(
+                "/sys/devices/system/cpu/cpu0/power/energy_perf_bias",
+            ))
+            .is_ok()
+            {
                 debug!(
                     "System does not have EPP but EPB is present, translating and setting EPB..."
                 );
-                let path = format!(
+                let path = SysRoot::path(format!(
                     "/sys/devices/system/cpu/cpu{}/power/energy_perf_bias",
                     self.cpu_id
-                );
+                ));
                 write_str(path, &CPUSettings::translate_epp_to_epb(epp));
             } else {
                 warn!("System does not have EPP or EPB but configuration attempted to set anyways. Ignoring...");
+                record_unsupported("Energy performance preference requires EPP or EPB");
             }
         }
 
         if let Some(ref governor) = self.governor {
-            let path = format!(
+            let path = SysRoot::path(format!(
                 "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_governor",
                 self.cpu_id
-            );
+            ));
             write_str(path, governor);
         }
 
         if let Some(min_frequency) = self.min_frequency {
-            let path = format!(
+            let path = SysRoot::path(format!(
                 "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_min_freq",
                 self.cpu_id
-            );
+            ));
             write_u32(path, min_frequency * 1000);
         }
         if let Some(max_frequency) = self.max_frequency {
-            let path = format!(
+            let path = SysRoot::path(format!(
                 "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_max_freq",
                 self.cpu_id
-            );
+            ));
             write_u32(path, max_frequency * 1000);
         }
     }
@@ -508,21 +691,23 @@ pub struct ScreenSettings {
 }
 
 impl ScreenSettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying Screen settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Screen, || {
+            info!(
+                "Applying Screen settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if let Some(ref resolution) = self.resolution {
-            Self::try_run_xrandr(&format!("xrandr --mode {}", resolution));
-        }
-        if let Some(ref refresh_rate) = self.refresh_rate {
-            Self::try_run_xrandr(&format!("xrandr -r {}", refresh_rate));
-        }
-        if let Some(brightness) = self.brightness {
-            Self::try_set_brightness(&format!("brightnessctl s {}%", brightness));
-        }
+            if let Some(ref resolution) = self.resolution {
+                Self::try_run_xrandr(&format!("xrandr --mode {}", resolution));
+            }
+            if let Some(ref refresh_rate) = self.refresh_rate {
+                Self::try_run_xrandr(&format!("xrandr -r {}", refresh_rate));
+            }
+            if let Some(brightness) = self.brightness {
+                Self::try_set_brightness(&format!("brightnessctl s {}%", brightness));
+            }
+        })
     }
 
     pub fn try_run_xrandr(command: &str) {
@@ -530,6 +715,7 @@ impl ScreenSettings {
             run_graphical_command(command);
         } else {
             error!("xrandr is not present in the system. Ignoring settings utilizing it...");
+            record_unsupported("Resolution and refresh rate require xrandr");
         }
     }
 
@@ -538,6 +724,7 @@ impl ScreenSettings {
             run_command(command);
         } else {
             error!("brightnessctl is not present in the system. Install it if you want brightness configuration. Ignoring settings utilizing it...");
+            record_unsupported("Brightness requires brightnessctl");
         }
     }
 }
@@ -550,30 +737,32 @@ pub struct RadioSettings {
 }
 
 impl RadioSettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying Radio settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Radio, || {
+            info!(
+                "Applying Radio settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if let Some(wifi) = self.block_wifi {
-            run_command(&format!(
-                "rfkill {} wifi",
-                if wifi { "block" } else { "unblock" },
-            ))
-        }
-        if let Some(nfc) = self.block_nfc {
-            run_command(&format!(
-                "rfkill {} nfc",
-                if nfc { "block" } else { "unblock" },
-            ))
-        }
-        if let Some(bt) = self.block_bt {
-            run_command(&format!(
-                "rfkill {} bluetooth",
-                if bt { "block" } else { "unblock" },
-            ))
-        }
+            if let Some(wifi) = self.block_wifi {
+                run_command(&format!(
+                    "rfkill {} wifi",
+                    if wifi { "block" } else { "unblock" },
+                ))
+            }
+            if let Some(nfc) = self.block_nfc {
+                run_command(&format!(
+                    "rfkill {} nfc",
+                    if nfc { "block" } else { "unblock" },
+                ))
+            }
+            if let Some(bt) = self.block_bt {
+                run_command(&format!(
+                    "rfkill {} bluetooth",
+                    if bt { "block" } else { "unblock" },
+                ))
+            }
+        })
     }
 }
 
@@ -598,28 +787,32 @@ pub struct NetworkSettings {
 }
 
 impl NetworkSettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying Network settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Network, || {
+            info!(
+                "Applying Network settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if let Some(disable_ethernet) = self.disable_ethernet {
-            Self::toggle_all_ethernet_cards(disable_ethernet);
-        }
+            if let Some(disable_ethernet) = self.disable_ethernet {
+                Self::toggle_all_ethernet_cards(disable_ethernet);
+            }
 
-        if !self.all_kernel_module_settings_are_none() {
-            self.apply_kernel_module_settings();
-        }
+            if !self.all_kernel_module_settings_are_none() {
+                self.apply_kernel_module_settings();
+            }
+        })
     }
 
     fn toggle_all_ethernet_cards(disable: bool) {
         if !command_exists("ifconfig") {
             error!("ifconfig is not present in the system, ignoring ethernet settings...");
+            record_unsupported("Ethernet settings require ifconfig");
             return;
         }
 
-        let entries = fs::read_dir("/sys/class/net").expect("Could not read sysfs path");
+        let entries =
+            fs::read_dir(SysRoot::path("/sys/class/net")).expect("Could not read sysfs path");
         for entry in entries.flatten() {
             let path = entry.path();
             let name = entry.file_name();
@@ -649,14 +842,15 @@ impl NetworkSettings {
     }
 
     fn apply_kernel_module_settings(&self) {
-        let uses_iwlmvm = if fs::metadata("/sys/module/iwlmvm").is_ok() {
+        let uses_iwlmvm = if fs::metadata(SysRoot::path("/sys/module/iwlmvm")).is_ok() {
             debug!("Identified that the system uses iwlmvm");
             true
-        } else if fs::metadata("/sys/module/iwldvm").is_ok() {
+        } else if fs::metadata(SysRoot::path("/sys/module/iwldvm")).is_ok() {
             debug!("Identified that the system uses iwldvm");
             false
         } else {
             error!("Could not identify spuported wifi firmware module. Expected either iwlmvm or iwldvm, neither found. Ignoring network kernel module settings...");
+            record_unsupported("Wifi driver settings require iwlmvm or iwldvm");
             return;
         };
 
@@ -702,10 +896,10 @@ impl NetworkSettings {
 
         let firmware_name = if uses_iwlmvm { "iwlmvm" } else { "iwldvm" };
 
-        run_command(&format!("modprobe -r {firmware_name}"));
-        run_command("modprobe -r iwlwifi");
-        run_command(&format!("modprobe {firmware_name} {firmware_parameters}"));
-        run_command(&format!("modprobe iwlwifi {driver_parameters}"));
+        reload_kernel_modules(&[
+            (firmware_name, firmware_parameters.as_str()),
+            ("iwlwifi", driver_parameters.as_str()),
+        ]);
     }
 }
 
@@ -716,15 +910,20 @@ pub struct ASPMSettings {
 }
 
 impl ASPMSettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying ASPM settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::ASPM, || {
+            info!(
+                "Applying ASPM settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if let Some(ref mode) = self.mode {
-            write_str("/sys/module/pcie_aspm/parameters/policy", mode);
-        }
+            if let Some(ref mode) = self.mode {
+                write_str(
+                    SysRoot::path("/sys/module/pcie_aspm/parameters/policy"),
+                    mode,
+                );
+            }
+        })
     }
 }
 
@@ -737,33 +936,36 @@ pub struct PCISettings {
 }
 
 impl PCISettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying PCI PM settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::PCI, || {
+            info!(
+                "Applying PCI PM settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if self.enable_power_management.is_none() {
-            return;
-        }
+            if self.enable_power_management.is_none() {
+                return;
+            }
 
-        let entries = fs::read_dir("/sys/bus/pci/devices").expect("Could not read sysfs directory");
+            let entries = fs::read_dir(SysRoot::path("/sys/bus/pci/devices"))
+                .expect("Could not read sysfs directory");
 
-        for entry in entries {
-            let entry = entry.expect("Could not read sysfs entry");
-            let path = entry.path();
+            for entry in entries {
+                let entry = entry.expect("Could not read sysfs entry");
+                let path = entry.path();
 
-            let enable_pm = WhiteBlackList::should_enable_item(
-                &self.whiteblacklist,
-                path.file_name().unwrap().to_str().unwrap(),
-                self.enable_power_management.unwrap(),
-            );
+                let enable_pm = WhiteBlackList::should_enable_item(
+                    &self.whiteblacklist,
+                    path.file_name().unwrap().to_str().unwrap(),
+                    self.enable_power_management.unwrap(),
+                );
 
-            write_str(
-                path.join("power/control"),
-                if enable_pm { "auto" } else { "on" },
-            );
-        }
+                write_str(
+                    path.join("power/control"),
+                    if enable_pm { "auto" } else { "on" },
+                );
+            }
+        })
     }
 }
 
@@ -776,50 +978,53 @@ pub struct USBSettings {
 }
 
 impl USBSettings {
-    pub fn apply(&self) {
-        info!("Applying USB settings on {:?}", std::thread::current().id());
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::USB, || {
+            info!("Applying USB settings on {:?}", std::thread::current().id());
 
-        let entries = fs::read_dir("/sys/bus/usb/devices").expect("Could not read sysfs directory");
+            if self.enable_pm.is_none() {
+                return;
+            }
 
-        if self.enable_pm.is_none() {
-            return;
-        }
+            let entries = fs::read_dir(SysRoot::path("/sys/bus/usb/devices"))
+                .expect("Could not read sysfs directory");
 
-        for entry in entries {
-            let entry = entry.expect("Could not read sysfs entry");
-            let path = entry.path();
+            for entry in entries {
+                let entry = entry.expect("Could not read sysfs entry");
+                let path = entry.path();
 
-            // Those are hubs I believe, and they do not have product/vendor info so we skip them
-            if let Some(file_name) = path.file_name() {
-                if file_name.to_string_lossy().contains(":") {
+                // Those are hubs I believe, and they do not have product/vendor info so we skip them
+                if let Some(file_name) = path.file_name() {
+                    if file_name.to_string_lossy().contains(":") {
+                        continue;
+                    }
+                } else {
                     continue;
                 }
-            } else {
-                continue;
-            }
 
-            let vendor_id = file_content_to_string(path.join("idVendor"));
-            let product_id = file_content_to_string(path.join("idProduct"));
+                let vendor_id = file_content_to_string(path.join("idVendor"));
+                let product_id = file_content_to_string(path.join("idProduct"));
 
-            if let Some(enable_power_management) = self.enable_pm {
-                let enable_pm = WhiteBlackList::should_enable_item(
-                    &self.whiteblacklist,
-                    &format!("{vendor_id}:{product_id}"),
-                    enable_power_management,
-                );
+                if let Some(enable_power_management) = self.enable_pm {
+                    let enable_pm = WhiteBlackList::should_enable_item(
+                        &self.whiteblacklist,
+                        &format!("{vendor_id}:{product_id}"),
+                        enable_power_management,
+                    );
 
-                write_str(
-                    path.join("power/control"),
-                    if enable_pm { "auto" } else { "on" },
-                );
+                    write_str(
+                        path.join("power/control"),
+                        if enable_pm { "auto" } else { "on" },
+                    );
 
-                if enable_pm {
-                    if let Some(auto_suspend_ms) = self.autosuspend_delay_ms {
-                        write_u32(path.join("power/autosuspend_delay_ms"), auto_suspend_ms);
+                    if enable_pm {
+                        if let Some(auto_suspend_ms) = self.autosuspend_delay_ms {
+                            write_u32(path.join("power/autosuspend_delay_ms"), auto_suspend_ms);
+                        }
                     }
                 }
             }
-        }
+        })
     }
 }
 
@@ -829,30 +1034,32 @@ pub struct SATASettings {
 }
 
 impl SATASettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying SATA settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::SATA, || {
+            info!(
+                "Applying SATA settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if self.active_link_pm_policy.is_none() {
-            return;
-        }
+            if self.active_link_pm_policy.is_none() {
+                return;
+            }
 
-        let pm_policy = self.active_link_pm_policy.as_ref().unwrap();
+            let pm_policy = self.active_link_pm_policy.as_ref().unwrap();
 
-        let entries = match fs::read_dir("/sys/class/scsi_host/") {
-            Ok(itr) => itr,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
-            Err(e) => panic!("Could not read sysfs directory: {e:?}"),
-        };
+            let entries = match fs::read_dir(SysRoot::path("/sys/class/scsi_host/")) {
+                Ok(itr) => itr,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+                Err(e) => panic!("Could not read sysfs directory: {e:?}"),
+            };
 
-        for entry in entries {
-            let entry = entry.expect("Could not read sysfs entry");
-            let path = entry.path();
+            for entry in entries {
+                let entry = entry.expect("Could not read sysfs entry");
+                let path = entry.path();
 
-            write_str(path.join("link_power_management_policy"), pm_policy);
-        }
+                write_str(path.join("link_power_management_policy"), pm_policy);
+            }
+        })
     }
 }
 
@@ -864,21 +1071,26 @@ pub struct KernelSettings {
 }
 
 impl KernelSettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying Kernel settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Kernel, || {
+            info!(
+                "Applying Kernel settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if let Some(disable_wd) = self.disable_nmi_watchdog {
-            write_bool("/proc/sys/kernel/nmi_watchdog", !disable_wd);
-        }
-        if let Some(vm_writeback) = self.vm_writeback {
-            write_u32("/proc/sys/vm/dirty_writeback_centisecs", vm_writeback * 100);
-        }
-        if let Some(lm) = self.laptop_mode {
-            write_u32("/proc/sys/vm/laptop_mode", lm);
-        }
+            if let Some(disable_wd) = self.disable_nmi_watchdog {
+                write_bool(SysRoot::path("/proc/sys/kernel/nmi_watchdog"), !disable_wd);
+            }
+            if let Some(vm_writeback) = self.vm_writeback {
+                write_u32(
+                    SysRoot::path("/proc/sys/vm/dirty_writeback_centisecs"),
+                    vm_writeback * 100,
+                );
+            }
+            if let Some(lm) = self.laptop_mode {
+                write_u32(SysRoot::path("/proc/sys/vm/laptop_mode"), lm);
+            }
+        })
     }
 }
 
@@ -889,10 +1101,15 @@ pub struct FirmwareSettings {
 }
 
 impl FirmwareSettings {
-    pub fn apply(&self) {
-        if let Some(ref profile) = self.platform_profile {
-            write_str("/sys/firmware/acpi/platform_profile", profile);
-        }
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Firmware, || {
+            if let Some(ref profile) = self.platform_profile {
+                write_str(
+                    SysRoot::path("/sys/firmware/acpi/platform_profile"),
+                    profile,
+                );
+            }
+        })
     }
 }
 
@@ -902,16 +1119,27 @@ pub struct AudioSettings {
 }
 
 impl AudioSettings {
-    pub fn apply(&self) {
-        if let Some(ref time) = self.idle_timeout {
-            if fs::metadata("/sys/module/snd_hda_intel/").is_ok() {
-                write_u32("/sys/module/snd_hda_intel/parameters/power_save", *time);
-            } else if fs::metadata("/sys/module/snd_ac97_codec/").is_ok() {
-                write_u32("/sys/module/snd_ac97_codec/parameters/power_save", *time);
-            } else {
-                error!("Attempted to set audio idle timeout but only snd_hda_intel and snd_ac97_codec modules are supported for this feature.");
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Audio, || {
+            if let Some(ref time) = self.idle_timeout {
+                if fs::metadata(SysRoot::path("/sys/module/snd_hda_intel/")).is_ok() {
+                    write_u32(
+                        SysRoot::path("/sys/module/snd_hda_intel/parameters/power_save"),
+                        *time,
+                    );
+                } else if fs::metadata(SysRoot::path("/sys/module/snd_ac97_codec/")).is_ok() {
+                    write_u32(
+                        SysRoot::path("/sys/module/snd_ac97_codec/parameters/power_save"),
+                        *time,
+                    );
+                } else {
+                    error!("Attempted to set audio idle timeout but only snd_hda_intel and snd_ac97_codec modules are supported for this feature.");
+                    record_unsupported(
+                        "Audio idle timeout requires snd_hda_intel or snd_ac97_codec",
+                    );
+                }
             }
-        }
+        })
     }
 }
 
@@ -930,17 +1158,19 @@ pub struct GpuSettings {
 }
 
 impl GpuSettings {
-    pub fn apply(&self) {
-        if self.intel_min.is_some() || self.intel_max.is_some() || self.intel_boost.is_some() {
-            self.apply_intel_settings();
-        }
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Gpu, || {
+            if self.intel_min.is_some() || self.intel_max.is_some() || self.intel_boost.is_some() {
+                self.apply_intel_settings();
+            }
 
-        if self.amd_dpm_perf_level.is_some()
-            || self.amd_dpm_power_state.is_some()
-            || self.amd_power_profile.is_some()
-        {
-            self.apply_amd_settings();
-        }
+            if self.amd_dpm_perf_level.is_some()
+                || self.amd_dpm_power_state.is_some()
+                || self.amd_power_profile.is_some()
+            {
+                self.apply_amd_settings();
+            }
+        })
     }
 
     fn apply_intel_settings(&self) {
@@ -1001,33 +1231,35 @@ pub struct IntelRaplSettings {
 }
 
 impl IntelRaplSettings {
-    pub fn apply(&self) {
-        info!(
-            "Applying RAPL settings on {:?}",
-            std::thread::current().id()
-        );
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Rapl, || {
+            info!(
+                "Applying RAPL settings on {:?}",
+                std::thread::current().id()
+            );
 
-        if let Some(interfaces) = iterate_rapl_interfaces() {
-            for interface in interfaces {
-                match interface.interface_type {
-                    InterfaceType::Package => {
-                        if let Some(ref int) = self.package {
-                            int.apply(interface);
+            if let Some(interfaces) = iterate_rapl_interfaces() {
+                for interface in interfaces {
+                    match interface.interface_type {
+                        InterfaceType::Package => {
+                            if let Some(ref int) = self.package {
+                                int.apply(interface);
+                            }
                         }
-                    }
-                    InterfaceType::Core => {
-                        if let Some(ref int) = self.core {
-                            int.apply(interface);
+                        InterfaceType::Core => {
+                            if let Some(ref int) = self.core {
+                                int.apply(interface);
+                            }
                         }
-                    }
-                    InterfaceType::Uncore => {
-                        if let Some(ref int) = self.core {
-                            int.apply(interface);
+                        InterfaceType::Uncore => {
+                            if let Some(ref int) = self.core {
+                                int.apply(interface);
+                            }
                         }
                     }
                 }
             }
-        }
+        })
     }
 }
 
@@ -1057,3 +1289,86 @@ impl IntelRaplInterfaceSettings {
         }
     }
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct BatterySettings {
+    /// Percentage below which the batteries start charging
+    pub charge_start_threshold: Option<u8>,
+    /// Percentage at which the batteries stop charging
+    pub charge_end_threshold: Option<u8>,
+}
+
+impl BatterySettings {
+    pub fn apply(&self) -> GroupReport {
+        apply_group(SettingsGroup::Battery, || {
+            info!(
+                "Applying Battery settings on {:?}",
+                std::thread::current().id()
+            );
+
+            if self.charge_start_threshold.is_none() && self.charge_end_threshold.is_none() {
+                return;
+            }
+
+            if let Some(reason) = validation::charge_thresholds_problem(self) {
+                error!("Not applying charge thresholds: {reason}");
+                record_outcome(|| ApplyOutcome::Failed {
+                    target: "charge thresholds".to_string(),
+                    errno: None,
+                    error: reason,
+                });
+                return;
+            }
+
+            for battery in iterate_batteries() {
+                let Some(current_end) = battery.end_threshold else {
+                    warn!(
+                        "Battery {} does not support charge thresholds, ignoring...",
+                        battery.name
+                    );
+                    record_unsupported(&format!(
+                        "Battery {} does not support charge thresholds",
+                        battery.name
+                    ));
+                    continue;
+                };
+
+                match (self.charge_start_threshold, self.charge_end_threshold) {
+                    (Some(start), None) => {
+                        Self::set_start_threshold(&battery, start);
+                    }
+                    (None, Some(end)) => {
+                        battery.set_end_threshold(end as u32);
+                    }
+                    (Some(start), Some(end)) => {
+                        // The kernel rejects a start threshold above the end
+                        // threshold, so the order of the writes matters
+                        if start as u32 >= current_end {
+                            battery.set_end_threshold(end as u32);
+                            Self::set_start_threshold(&battery, start);
+                        } else {
+                            Self::set_start_threshold(&battery, start);
+                            battery.set_end_threshold(end as u32);
+                        }
+                    }
+                    (None, None) => unreachable!(),
+                }
+            }
+        })
+    }
+
+    fn set_start_threshold(battery: &Battery, start: u8) {
+        if battery.start_threshold.is_some() {
+            battery.set_start_threshold(start as u32);
+        } else {
+            debug!(
+                "Battery {} only supports an end threshold, ignoring start threshold",
+                battery.name
+            );
+            record_unsupported(&format!(
+                "Battery {} does not support a start threshold",
+                battery.name
+            ));
+        }
+    }
+}
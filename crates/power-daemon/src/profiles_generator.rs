@@ -1,20 +1,24 @@
 use std::{
-    fs::File,
-    io::Write,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash, Hasher},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
 };
 
 use log::{debug, trace};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    migration::PROFILE_SCHEMA_VERSION,
+    persistence::write_atomically,
     profile::{
         ASPMSettings, CPUCoreSettings, CPUSettings, KernelSettings, NetworkSettings, PCISettings,
         Profile, RadioSettings, SATASettings, ScreenSettings, USBSettings,
     },
     systeminfo::{CPUFreqDriver, SystemInfo},
-    AmdGpuInfo, AudioModule, AudioSettings, FirmwareSettings, GpuSettings, IntelRaplSettings,
-    SleepSettings,
+    AmdGpuInfo, AudioModule, AudioSettings, BatterySettings, FirmwareSettings, GpuSettings,
+    IntelRaplSettings, SleepSettings,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
@@ -80,14 +84,11 @@ pub fn create_empty_profile_file_with_name<P: AsRef<Path>>(directory_path: P, na
 
     let path = PathBuf::from(directory_path.as_ref()).join(format!("{name}.toml"));
 
-    let mut file = File::create(path).expect("Could not create profile file");
-
     let content = toml::to_string_pretty(&profile).unwrap();
 
     trace!("{content}");
 
-    file.write_all(content.as_bytes())
-        .expect("Could not write to profile file");
+    write_atomically(&path, &content).expect("Could not write to profile file");
 }
 
 pub fn create_profile_file_with_name<P: AsRef<Path>>(
@@ -101,14 +102,30 @@ pub fn create_profile_file_with_name<P: AsRef<Path>>(
 
     let path = PathBuf::from(directory_path.as_ref()).join(format!("{name}.toml"));
 
-    let mut file = File::create(path).expect("Could not create profile file");
-
     let content = toml::to_string_pretty(&profile).unwrap();
 
     trace!("{content}");
 
-    file.write_all(content.as_bytes())
-        .expect("Could not write to profile file");
+    write_atomically(&path, &content).expect("Could not write to profile file");
+}
+
+/// Profile that replaces one that could not be loaded. It is based on the
+/// same default profile as the broken one if that can still be told
+pub fn create_fallback(name: &str, contents: Option<&str>, system_info: &SystemInfo) -> Profile {
+    #[derive(Deserialize)]
+    struct ProfileTypeOnly {
+        base_profile: Option<DefaultProfileType>,
+    }
+
+    let profile_type = contents
+        .and_then(|contents| toml::from_str::<ProfileTypeOnly>(contents).ok())
+        .and_then(|profile| profile.base_profile)
+        .or_else(|| DefaultProfileType::from_name(name.to_string()));
+
+    match profile_type {
+        Some(profile_type) => create_default(name, profile_type, system_info),
+        None => create_empty(name),
+    }
 }
 
 pub fn create_default(
@@ -117,6 +134,8 @@ pub fn create_default(
     system_info: &SystemInfo,
 ) -> Profile {
     Profile {
+        schema_version: PROFILE_SCHEMA_VERSION,
+        profile_id: new_profile_id(),
         profile_name: String::from(name),
         base_profile: profile_type.into(),
 
@@ -135,11 +154,25 @@ pub fn create_default(
         audio_settings: audio_settings_default(&profile_type, system_info),
         gpu_settings: gpu_settings_default(&profile_type, system_info),
         rapl_settings: IntelRaplSettings::default(),
+        // Thresholds set by the user through other tools are left alone
+        battery_settings: BatterySettings::default(),
     }
 }
 
+/// Random ID for a new profile
+pub fn new_profile_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    SystemTime::now().hash(&mut hasher);
+    COUNTER.fetch_add(1, Ordering::Relaxed).hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
 pub fn create_empty(name: &str) -> Profile {
     Profile {
+        schema_version: PROFILE_SCHEMA_VERSION,
+        profile_id: new_profile_id(),
         profile_name: String::from(name),
         base_profile: None,
 
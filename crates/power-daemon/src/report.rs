@@ -0,0 +1,188 @@
+use std::{cell::RefCell, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+/// A group of settings of a profile that gets applied as a whole
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsGroup {
+    Sleep,
+    CPU,
+    CPUCores,
+    Screen,
+    Radio,
+    Network,
+    ASPM,
+    PCI,
+    USB,
+    SATA,
+    Kernel,
+    Firmware,
+    Audio,
+    Gpu,
+    Rapl,
+    Battery,
+}
+
+impl SettingsGroup {
+    pub const ALL: [SettingsGroup; 16] = [
+        SettingsGroup::Sleep,
+        SettingsGroup::CPU,
+        SettingsGroup::CPUCores,
+        SettingsGroup::Screen,
+        SettingsGroup::Radio,
+        SettingsGroup::Network,
+        SettingsGroup::ASPM,
+        SettingsGroup::PCI,
+        SettingsGroup::USB,
+        SettingsGroup::SATA,
+        SettingsGroup::Kernel,
+        SettingsGroup::Firmware,
+        SettingsGroup::Audio,
+        SettingsGroup::Gpu,
+        SettingsGroup::Rapl,
+        SettingsGroup::Battery,
+    ];
+}
+
+/// Outcome of a single change made while applying a settings group
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ApplyOutcome {
+    /// `target` (a sysfs/procfs path or a command line) was written or ran
+    /// successfully
+    Succeeded { target: String },
+    /// A configured setting could not be applied because the system does not
+    /// support it
+    Unsupported { reason: String },
+    /// Writing to or running `target` failed. `errno` is None for commands
+    /// that exited unsuccessfully
+    Failed {
+        target: String,
+        errno: Option<i32>,
+        error: String,
+    },
+}
+
+impl Display for ApplyOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplyOutcome::Succeeded { target } => write!(f, "ok: {target}"),
+            ApplyOutcome::Unsupported { reason } => write!(f, "unsupported: {reason}"),
+            ApplyOutcome::Failed {
+                target,
+                errno: Some(errno),
+                error,
+            } => write!(f, "failed: {target}: {error} (errno {errno})"),
+            ApplyOutcome::Failed {
+                target,
+                errno: None,
+                error,
+            } => write!(f, "failed: {target}: {error}"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GroupReport {
+    pub group: SettingsGroup,
+    pub outcomes: Vec<ApplyOutcome>,
+    /// True if some change failed and every change of the group was reverted
+    pub rolled_back: bool,
+}
+
+impl GroupReport {
+    pub fn has_failures(&self) -> bool {
+        self.outcomes
+            .iter()
+            .any(|o| matches!(o, ApplyOutcome::Failed { .. }))
+    }
+    pub fn has_unsupported(&self) -> bool {
+        self.outcomes
+            .iter()
+            .any(|o| matches!(o, ApplyOutcome::Unsupported { .. }))
+    }
+}
+
+/// Everything that happened while applying (parts of) a profile
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ApplyReport {
+    pub profile_name: String,
+    pub groups: Vec<GroupReport>,
+}
+
+impl ApplyReport {
+    pub fn has_failures(&self) -> bool {
+        self.groups.iter().any(GroupReport::has_failures)
+    }
+
+    pub fn rolled_back_groups(&self) -> Vec<SettingsGroup> {
+        self.groups
+            .iter()
+            .filter(|g| g.rolled_back)
+            .map(|g| g.group)
+            .collect()
+    }
+
+    /// Updates this report with a more recent one. Groups that were applied
+    /// again replace the old results if both reports are for the same profile
+    pub fn merge(&mut self, newer: ApplyReport) {
+        if self.profile_name != newer.profile_name {
+            *self = newer;
+            return;
+        }
+
+        for group in newer.groups {
+            if let Some(existing) = self.groups.iter_mut().find(|g| g.group == group.group) {
+                *existing = group;
+            } else {
+                self.groups.push(group);
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// Report of the group that is being applied on this thread
+    static RECORDING: RefCell<Option<GroupReport>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` on the current thread collecting the outcome of every change it
+/// makes into a report for `group`
+pub fn record_group(group: SettingsGroup, f: impl FnOnce()) -> GroupReport {
+    let previous = RECORDING.with(|r| {
+        r.borrow_mut().replace(GroupReport {
+            group,
+            outcomes: Vec::new(),
+            rolled_back: false,
+        })
+    });
+
+    f();
+
+    RECORDING.with(|r| {
+        let mut recording = r.borrow_mut();
+        let report = recording.take().unwrap();
+        *recording = previous;
+        report
+    })
+}
+
+/// The group being applied on this thread, if any
+pub(crate) fn current_group() -> Option<SettingsGroup> {
+    RECORDING.with(|r| r.borrow().as_ref().map(|report| report.group))
+}
+
+/// Adds the outcome to the report of the group being applied on this thread,
+/// if any
+pub(crate) fn record_outcome(outcome: impl FnOnce() -> ApplyOutcome) {
+    RECORDING.with(|r| {
+        if let Some(ref mut report) = *r.borrow_mut() {
+            report.outcomes.push(outcome());
+        }
+    })
+}
+
+pub(crate) fn record_unsupported(reason: &str) {
+    record_outcome(|| ApplyOutcome::Unsupported {
+        reason: reason.to_string(),
+    })
+}
@@ -0,0 +1,139 @@
+use std::{
+    fs::{self, DirEntry},
+    path::PathBuf,
+};
+
+use super::{
+    reading::{try_file_content_to_string, try_file_content_to_u32},
+    root::SysRoot,
+    writing::write_u32,
+};
+
+/// Names used by the kernel (and older vendor drivers) for the threshold at
+/// which the battery starts charging
+const START_THRESHOLD_NAMES: [&str; 2] =
+    ["charge_control_start_threshold", "charge_start_threshold"];
+/// Names used by the kernel (and older vendor drivers) for the threshold at
+/// which the battery stops charging
+const END_THRESHOLD_NAMES: [&str; 3] = [
+    "charge_control_end_threshold",
+    "charge_stop_threshold",
+    "charge_end_threshold",
+];
+
+pub struct Battery {
+    pub name: String,
+
+    pub capacity: Option<u32>,
+    pub status: Option<String>,
+    pub technology: Option<String>,
+    pub cycle_count: Option<u32>,
+
+    /// In µWh
+    pub energy_now: Option<u32>,
+    /// In µWh
+    pub energy_full: Option<u32>,
+    /// In µWh
+    pub energy_full_design: Option<u32>,
+    /// In µW
+    pub power_now: Option<u32>,
+
+    /// None if the battery does not support a start threshold. Some vendors
+    /// (e.g. asus-wmi) only expose the end threshold
+    pub start_threshold: Option<u32>,
+    /// None if the battery does not support an end threshold
+    pub end_threshold: Option<u32>,
+
+    start_threshold_path: Option<PathBuf>,
+    end_threshold_path: Option<PathBuf>,
+}
+
+impl Battery {
+    pub fn from_dir(entry: DirEntry) -> Battery {
+        let path = entry.path();
+
+        let start_threshold_path = START_THRESHOLD_NAMES
+            .iter()
+            .map(|name| path.join(name))
+            .find(|path| fs::metadata(path).is_ok());
+        let end_threshold_path = END_THRESHOLD_NAMES
+            .iter()
+            .map(|name| path.join(name))
+            .find(|path| fs::metadata(path).is_ok());
+
+        // Some batteries report charge (µAh) and current (µA) instead of
+        // energy and power, those need to be converted with the voltage. The
+        // design voltage keeps energies comparable as the battery drains,
+        // the power is instantaneous and needs the current voltage
+        let voltage_now = try_file_content_to_u32(path.join("voltage_now"));
+        let voltage = try_file_content_to_u32(path.join("voltage_min_design")).or(voltage_now);
+        let read_energy = |name: &str| {
+            try_file_content_to_u32(path.join(format!("energy_{name}"))).or_else(|| {
+                let charge = try_file_content_to_u32(path.join(format!("charge_{name}")))?;
+                Some((charge as u64 * voltage? as u64 / 1_000_000) as u32)
+            })
+        };
+
+        Battery {
+            name: entry.file_name().to_string_lossy().to_string(),
+
+            capacity: try_file_content_to_u32(path.join("capacity")),
+            status: try_file_content_to_string(path.join("status")),
+            technology: try_file_content_to_string(path.join("technology")),
+            // Reported as 0 by most firmwares that do not track it
+            cycle_count: try_file_content_to_u32(path.join("cycle_count")).filter(|c| *c != 0),
+
+            energy_now: read_energy("now"),
+            energy_full: read_energy("full"),
+            energy_full_design: read_energy("full_design"),
+            power_now: try_file_content_to_u32(path.join("power_now")).or_else(|| {
+                let current = try_file_content_to_u32(path.join("current_now"))?;
+                Some((current as u64 * voltage_now? as u64 / 1_000_000) as u32)
+            }),
+
+            start_threshold: start_threshold_path
+                .as_ref()
+                .and_then(try_file_content_to_u32),
+            end_threshold: end_threshold_path
+                .as_ref()
+                .and_then(try_file_content_to_u32),
+            start_threshold_path,
+            end_threshold_path,
+        }
+    }
+
+    pub fn set_start_threshold(&self, threshold: u32) {
+        if let Some(ref path) = self.start_threshold_path {
+            write_u32(path, threshold);
+        }
+    }
+    pub fn set_end_threshold(&self, threshold: u32) {
+        if let Some(ref path) = self.end_threshold_path {
+            write_u32(path, threshold);
+        }
+    }
+}
+
+/// Iterates over the batteries powering the system. Batteries of peripherals
+/// like mice or headsets are skipped
+pub fn iterate_batteries() -> impl Iterator<Item = Battery> {
+    let mut entries: Vec<_> = fs::read_dir(SysRoot::path("/sys/class/power_supply"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            fs::read_to_string(entry.path().join("type")).is_ok_and(|t| t.trim() == "Battery")
+                && !fs::read_to_string(entry.path().join("scope"))
+                    .is_ok_and(|scope| scope.trim() == "Device")
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        natord::compare(
+            &a.file_name().to_string_lossy(),
+            &b.file_name().to_string_lossy(),
+        )
+    });
+
+    entries.into_iter().map(Battery::from_dir)
+}
@@ -5,6 +5,7 @@ use std::{
 
 use super::{
     reading::{file_content_to_string, file_content_to_u32},
+    root::SysRoot,
     writing::{write_str, write_u32},
 };
 
@@ -138,7 +139,7 @@ impl AmdGpu {
 }
 
 pub fn iterate_intel_gpus() -> impl Iterator<Item = IntelGpu> {
-    fs::read_dir("/sys/class/drm")
+    fs::read_dir(SysRoot::path("/sys/class/drm"))
         .expect("Could not read sysfs drm directory")
         .flatten()
         .filter(|entry| {
@@ -155,7 +156,7 @@ pub fn iterate_intel_gpus() -> impl Iterator<Item = IntelGpu> {
 }
 
 pub fn iterate_amd_gpus() -> impl Iterator<Item = AmdGpu> {
-    fs::read_dir("/sys/class/drm")
+    fs::read_dir(SysRoot::path("/sys/class/drm"))
         .expect("Could not read sysfs drm directory")
         .flatten()
         .filter(|entry| {
@@ -1,4 +1,7 @@
+pub mod battery;
 pub mod gpu;
 pub mod rapl;
 pub mod reading;
+pub mod root;
+pub mod snapshot;
 pub mod writing;
@@ -8,7 +8,7 @@ use regex::Regex;
 
 use crate::sysfs::reading::{file_content_to_u32, try_file_content_to_u32};
 
-use super::{reading::file_content_to_string, writing::write_u32};
+use super::{reading::file_content_to_string, root::SysRoot, writing::write_u32};
 
 pub struct IntelRaplInterface {
     pub interface_type: InterfaceType,
@@ -92,11 +92,11 @@ impl IntelRaplConstraint {
 }
 
 pub fn iterate_rapl_interfaces() -> Option<impl Iterator<Item = IntelRaplInterface>> {
-    if fs::metadata("/sys/class/powercap").is_err() {
+    if fs::metadata(SysRoot::path("/sys/class/powercap")).is_err() {
         None
     } else {
         let re = Regex::new(r"^intel-rapl(:\d+){1,2}").unwrap();
-        fs::read_dir("/sys/class/powercap")
+        fs::read_dir(SysRoot::path("/sys/class/powercap"))
             .expect("Could not read powercap sysfs dir")
             .flatten()
             .filter(move |d| re.is_match(&d.file_name().into_string().unwrap()))
@@ -0,0 +1,48 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use lazy_static::lazy_static;
+use log::info;
+
+lazy_static! {
+    static ref SYS_ROOT: RwLock<PathBuf> = RwLock::new(PathBuf::from("/"));
+}
+
+/// The directory under which /sys and /proc are looked up. Defaults to the
+/// real filesystem root, but can be pointed at a fake tree so that system
+/// info can be obtained and profiles applied without touching the hardware.
+///
+/// The root is shared by the whole process since profiles are applied from
+/// multiple threads.
+pub struct SysRoot;
+
+impl SysRoot {
+    pub fn set(root: impl Into<PathBuf>) {
+        let root = root.into();
+        info!("Using {} as sysfs/procfs root", root.display());
+        *SYS_ROOT.write().unwrap() = root;
+    }
+
+    pub fn get() -> PathBuf {
+        SYS_ROOT.read().unwrap().clone()
+    }
+
+    pub fn is_default() -> bool {
+        *SYS_ROOT.read().unwrap() == Path::new("/")
+    }
+
+    /// Maps an absolute path such as "/sys/class/drm" to its location under
+    /// the current root
+    pub fn path(path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        let root = SYS_ROOT.read().unwrap();
+
+        if *root == Path::new("/") {
+            return path.to_path_buf();
+        }
+
+        root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+use log::{debug, info};
+
+use crate::report::{current_group, record_group, ApplyOutcome, GroupReport, SettingsGroup};
+
+use super::writing::write_str_unsnapshotted;
+
+struct OriginalValue {
+    /// Group that first wrote to the node, None if it was written outside of
+    /// a profile application
+    group: Option<SettingsGroup>,
+    value: String,
+}
+
+lazy_static! {
+    /// Values that sysfs/procfs nodes had before the daemon first wrote to
+    /// them
+    static ref ORIGINAL_VALUES: Mutex<HashMap<PathBuf, OriginalValue>> =
+        Mutex::new(HashMap::new());
+
+    /// The last value the daemon wrote to each node and what the node read
+    /// back right after, which differs when the driver rounds, clamps or
+    /// normalizes it
+    static ref WRITTEN_VALUES: Mutex<HashMap<PathBuf, (String, String)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Remembers the current value of `path` if the daemon has not written to it
+/// before
+pub(crate) fn remember_original(path: &Path) {
+    let mut originals = ORIGINAL_VALUES.lock().unwrap();
+    if originals.contains_key(path) {
+        return;
+    }
+
+    // Nodes that cannot be read cannot be restored either
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+
+    originals.insert(
+        path.to_path_buf(),
+        OriginalValue {
+            group: current_group(),
+            value: selected_value(&content),
+        },
+    );
+}
+
+/// Remembers what `path` reads after the daemon wrote `payload` to it
+pub(crate) fn remember_written(path: &Path, payload: &str) {
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+
+    WRITTEN_VALUES.lock().unwrap().insert(
+        path.to_path_buf(),
+        (payload.trim().to_string(), selected_value(&content)),
+    );
+}
+
+/// What `path` read right after the daemon last wrote `payload` to it, None
+/// if it has not written that value
+pub(crate) fn written_value(path: &Path, payload: &str) -> Option<String> {
+    WRITTEN_VALUES
+        .lock()
+        .unwrap()
+        .get(path)
+        .filter(|(written, _)| written == payload.trim())
+        .map(|(_, read_back)| read_back.clone())
+}
+
+/// Writes back the original value of every node the daemon wrote to. Values
+/// will be changed again on the next profile application
+pub fn restore_all() {
+    let originals: Vec<_> = ORIGINAL_VALUES.lock().unwrap().drain().collect();
+
+    info!("Restoring original values of {} nodes", originals.len());
+
+    for (path, original) in originals {
+        write_str_unsnapshotted(&path, &original.value);
+    }
+}
+
+/// Restores the nodes that the group of `report` changed before but did not
+/// write to this time, because their settings are now unset. The outcome of
+/// the restoration is added to the report
+pub fn restore_unset(mut report: GroupReport) -> GroupReport {
+    let written: HashSet<&str> = report
+        .outcomes
+        .iter()
+        .filter_map(|outcome| match outcome {
+            ApplyOutcome::Succeeded { target } | ApplyOutcome::Failed { target, .. } => {
+                Some(target.as_str())
+            }
+            ApplyOutcome::Unsupported { .. } => None,
+        })
+        .collect();
+
+    let unset: Vec<_> = {
+        let mut originals = ORIGINAL_VALUES.lock().unwrap();
+
+        let paths: Vec<_> = originals
+            .iter()
+            .filter(|(path, original)| {
+                original.group == Some(report.group)
+                    && !written.contains(path.display().to_string().as_str())
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let original = originals.remove(&path).unwrap();
+                (path, original.value)
+            })
+            .collect()
+    };
+
+    if unset.is_empty() {
+        return report;
+    }
+
+    debug!(
+        "Restoring {} nodes of unset {:?} settings",
+        unset.len(),
+        report.group
+    );
+
+    let restored = record_group(report.group, || {
+        for (path, value) in unset {
+            write_str_unsnapshotted(&path, &value);
+        }
+    });
+    report.outcomes.extend(restored.outcomes);
+
+    report
+}
+
+/// Some nodes list all possible values and mark the current one in
+/// brackets, e.g. "[default] performance powersave"
+pub(crate) fn selected_value(content: &str) -> String {
+    let content = content.trim();
+
+    content
+        .split_whitespace()
+        .find_map(|value| value.strip_prefix('[')?.strip_suffix(']'))
+        .unwrap_or(content)
+        .to_string()
+}
@@ -6,6 +6,17 @@ use std::{
 
 use log::{debug, error, warn};
 
+use crate::{
+    plan::{record_if_planning, PlannedAction},
+    report::{record_outcome, record_unsupported, ApplyOutcome},
+    transaction::{record_undo, UndoAction},
+};
+
+use super::{
+    root::SysRoot,
+    snapshot::{remember_original, remember_written, selected_value},
+};
+
 /// Writes a bool value to a /sys path, mapping `true` to "1" and `false` to
 /// "0".
 ///
@@ -23,26 +34,118 @@ pub fn write_u32(path: impl AsRef<Path>, value: u32) {
     write_str(path, &payload)
 }
 
-/// Writes a string value to a /sys path.
+/// Writes a string value to a /sys path, remembering the value it had before
+/// the daemon first wrote to it.
 ///
 /// Logs when encountering an error but does not crash.
 pub fn write_str(path: impl AsRef<Path>, payload: &str) {
     let path = path.as_ref();
-    match write_str_inner(path, payload) {
-        Ok(()) => {}
-        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+
+    if record_if_planning(|| PlannedAction::Write {
+        path: path.display().to_string(),
+        value: payload.to_string(),
+    }) {
+        return;
+    }
+
+    remember_original(path);
+    let previous = fs::read_to_string(path).ok().map(|p| selected_value(&p));
+    if let Some(ref previous) = previous {
+        record_undo(|| UndoAction::Write {
+            path: path.to_path_buf(),
+            value: previous.clone(),
+        });
+    }
+
+    if write_str_unsnapshotted(path, payload) {
+        if let Some(previous) = previous {
+            verify_write(path, payload, &previous);
+        }
+        remember_written(path, payload);
+    }
+}
+
+/// Same as `write_str` but does not remember the original value, used when
+/// writing those original values back. Returns whether the write succeeded
+pub(crate) fn write_str_unsnapshotted(path: &Path, payload: &str) -> bool {
+    let result = write_str_inner(path, payload);
+
+    match result {
+        Ok(()) => {
+            record_outcome(|| ApplyOutcome::Succeeded {
+                target: path.display().to_string(),
+            });
+            return true;
+        }
+        // Nodes that are missing or that the driver currently refuses to
+        // change, e.g. the EPP under the performance governor of amd_pstate,
+        // should not make the rest of their group roll back
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
             warn!(
                 "Attempted to write \"{payload}\" to {}, but path does not exist!",
                 path.display()
             );
+            record_unsupported(&format!("{} does not exist", path.display()));
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::ResourceBusy => {
+            warn!(
+                "Could not write \"{payload}\" to {}, the device is busy",
+                path.display()
+            );
+            record_unsupported(&format!("{} is busy", path.display()));
         }
         Err(e) => {
             error!(
                 "Error writing \"{payload}\" to path {}: {e:?}",
                 path.display()
             );
+            record_outcome(|| ApplyOutcome::Failed {
+                target: path.display().to_string(),
+                errno: e.raw_os_error(),
+                error: e.to_string(),
+            });
         }
     }
+
+    false
+}
+
+/// Nodes that hold exactly what was written to them. Others may round, clamp
+/// or normalize it, e.g. frequencies or the EPB, and cannot be verified
+const ECHOING_NODES: [&str; 3] = [
+    "scaling_governor",
+    "energy_performance_preference",
+    "platform_profile",
+];
+
+/// Reads the node back after a successful write that changed its value, for
+/// the nodes that are known to echo what is written to them
+fn verify_write(path: &Path, payload: &str, previous: &str) {
+    let payload = payload.trim();
+    let echoes = path
+        .file_name()
+        .is_some_and(|name| ECHOING_NODES.iter().any(|node| name == *node));
+    if !echoes || previous == payload {
+        return;
+    }
+
+    let Ok(actual) = fs::read_to_string(path) else {
+        return;
+    };
+    let actual = selected_value(&actual);
+    if actual == payload {
+        return;
+    }
+
+    error!(
+        "Wrote \"{payload}\" to {} but it reads \"{actual}\"",
+        path.display()
+    );
+    record_outcome(|| ApplyOutcome::Failed {
+        target: path.display().to_string(),
+        errno: None,
+        error: format!("Reads \"{actual}\" after the write"),
+    });
 }
 
 /// Writes a value to all CPU paths under /sys/devices/cpu/cpu* that are
@@ -50,7 +153,8 @@ pub fn write_str(path: impl AsRef<Path>, payload: &str) {
 /// warning if any of the individual cores can't have that value set.
 pub fn write_all_cores(path: impl AsRef<Path>, data: &str) {
     let path = path.as_ref();
-    let raw = fs::read_dir("/sys/devices/system/cpu/").expect("Error reading CPU list");
+    let raw =
+        fs::read_dir(SysRoot::path("/sys/devices/system/cpu/")).expect("Error reading CPU list");
     let relevant = raw.filter_map(Result::ok).filter(|ent| {
         let raw_filename = ent.file_name();
         let name = raw_filename.to_string_lossy();
@@ -66,6 +170,7 @@ pub fn write_all_cores(path: impl AsRef<Path>, data: &str) {
                 "Attempted to write {data} to CPU sysfs path {}, but that path does not exist!",
                 target.display()
             );
+            record_unsupported(&format!("{} does not exist", target.display()));
             continue;
         }
         write_str(target, data);
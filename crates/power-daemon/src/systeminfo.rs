@@ -5,13 +5,16 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    helpers::{command_exists, run_command_with_output},
+    helpers::{command_exists, run_command_with_output, system_on_ac},
     sysfs::{
+        battery::{iterate_batteries, Battery},
         gpu::{IntelGpu, *},
         rapl::{iterate_rapl_interfaces, IntelRaplConstraint, IntelRaplInterface, InterfaceType},
         reading::{
-            file_content_to_bool, file_content_to_list, file_content_to_string, file_content_to_u32,
+            file_content_to_bool, file_content_to_list, file_content_to_string,
+            file_content_to_u32, try_file_content_to_string,
         },
+        root::SysRoot,
     },
 };
 
@@ -24,6 +27,7 @@ pub struct SystemInfo {
     pub firmware_info: FirmwareInfo,
     pub gpu_info: GpuInfo,
     pub rapl_info: IntelRaplInfo,
+    pub battery_info: BatteryInfo,
     pub opt_features_info: OptionalFeaturesInfo,
 }
 
@@ -39,6 +43,7 @@ impl SystemInfo {
             firmware_info: FirmwareInfo::obtain(),
             gpu_info: GpuInfo::obtain(),
             rapl_info: IntelRaplInfo::obtain(),
+            battery_info: BatteryInfo::obtain(),
             opt_features_info: OptionalFeaturesInfo::obtain(),
         }
     }
@@ -56,6 +61,9 @@ pub struct CPUInfo {
     pub driver: CPUFreqDriver,
     pub mode: Option<String>,
 
+    /// Governors supported by the scaling driver, empty if unknown
+    pub available_governors: Vec<String>,
+
     pub has_epp: bool,
     pub has_epb: bool,
 
@@ -100,9 +108,10 @@ pub struct CoreInfo {
 
 impl CPUInfo {
     pub fn obtain() -> CPUInfo {
-        let driver = if fs::metadata("/sys/devices/system/cpu/intel_pstate").is_ok() {
+        let driver = if fs::metadata(SysRoot::path("/sys/devices/system/cpu/intel_pstate")).is_ok()
+        {
             CPUFreqDriver::Intel
-        } else if fs::metadata("/sys/devices/system/cpu/amd_pstate").is_ok() {
+        } else if fs::metadata(SysRoot::path("/sys/devices/system/cpu/amd_pstate")).is_ok() {
             CPUFreqDriver::Amd
         } else {
             CPUFreqDriver::Other
@@ -122,21 +131,32 @@ impl CPUInfo {
             mode: if driver == CPUFreqDriver::Other {
                 None
             } else {
-                Some(file_content_to_string(format!(
+                Some(file_content_to_string(SysRoot::path(format!(
                     "/sys/devices/system/cpu/{}/status",
                     well_known_driver_name
-                )))
+                ))))
             },
 
-            has_epp: fs::metadata(
+            available_governors: try_file_content_to_string(SysRoot::path(
+                "/sys/devices/system/cpu/cpu0/cpufreq/scaling_available_governors",
+            ))
+            .map(|governors| governors.split_whitespace().map(String::from).collect())
+            .unwrap_or_default(),
+
+            has_epp: fs::metadata(SysRoot::path(
                 "/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_available_preferences",
-            )
+            ))
+            .is_ok(),
+            has_epb: fs::metadata(SysRoot::path(
+                "/sys/devices/system/cpu/cpu0/power/energy_perf_bias",
+            ))
             .is_ok(),
-            has_epb: fs::metadata("/sys/devices/system/cpu/cpu0/power/energy_perf_bias").is_ok(),
 
             // This feature is exclusive to intel
-            has_perf_pct_scaling: fs::metadata("/sys/devices/system/cpu/intel_pstate/min_perf_pct")
-                .is_ok(),
+            has_perf_pct_scaling: fs::metadata(SysRoot::path(
+                "/sys/devices/system/cpu/intel_pstate/min_perf_pct",
+            ))
+            .is_ok(),
 
             hybrid: false,
             cores: Vec::default(),
@@ -145,18 +165,18 @@ impl CPUInfo {
             total_max_frequency: 0,
 
             boost: match driver {
-                CPUFreqDriver::Intel => Some(!file_content_to_bool(
+                CPUFreqDriver::Intel => Some(!file_content_to_bool(SysRoot::path(
                     "/sys/devices/system/cpu/intel_pstate/no_turbo",
-                )),
-                CPUFreqDriver::Amd => Some(file_content_to_bool(
+                ))),
+                CPUFreqDriver::Amd => Some(file_content_to_bool(SysRoot::path(
                     "/sys/devices/system/cpu/cpufreq/boost",
-                )),
+                ))),
                 CPUFreqDriver::Other => None,
             },
             hwp_dynamic_boost: if let CPUFreqDriver::Intel = driver {
-                Some(file_content_to_bool(
+                Some(file_content_to_bool(SysRoot::path(
                     "/sys/devices/system/cpu/intel_pstate/hwp_dynamic_boost",
-                ))
+                )))
             } else {
                 None
             },
@@ -217,7 +237,7 @@ impl CPUInfo {
 
         let mut count = 0;
 
-        let mut entries: Vec<_> = fs::read_dir("/sys/devices/system/cpu/")
+        let mut entries: Vec<_> = fs::read_dir(SysRoot::path("/sys/devices/system/cpu/"))
             .expect("Could not read sysfs directory")
             .filter_map(Result::ok)
             .collect();
@@ -386,7 +406,7 @@ pub struct ASPMInfo {
 
 impl PCIInfo {
     pub fn obtain() -> PCIInfo {
-        let mut entries: Vec<_> = fs::read_dir("/sys/bus/pci/devices")
+        let mut entries: Vec<_> = fs::read_dir(SysRoot::path("/sys/bus/pci/devices"))
             .expect("Could not read sysfs directory")
             .filter_map(Result::ok)
             .collect();
@@ -398,12 +418,27 @@ impl PCIInfo {
         let mut pci_devices = Vec::new();
         let label_re = Regex::new(r"^[0-9a-f]+:[0-9a-f]+.[0-9] (.+) \(rev.*\)").unwrap();
 
+        let has_lspci = command_exists("lspci");
+        // Make lspci look the devices up in the same tree we are reading
+        let lspci_args = if SysRoot::is_default() {
+            String::new()
+        } else {
+            format!(
+                " -A linux-sysfs -O sysfs.path=\"{}\"",
+                SysRoot::path("/sys/bus/pci").display()
+            )
+        };
+
         for device in entries {
-            let display_name = run_command_with_output(&format!(
-                "lspci -s \"{}\"",
-                device.file_name().into_string().unwrap(),
-            ))
-            .0;
+            let display_name = if has_lspci {
+                run_command_with_output(&format!(
+                    "lspci -s \"{}\"{lspci_args}",
+                    device.file_name().into_string().unwrap(),
+                ))
+                .0
+            } else {
+                String::new()
+            };
 
             let display_name = label_re
                 .captures(&display_name)
@@ -427,11 +462,15 @@ impl PCIInfo {
 impl ASPMInfo {
     pub fn obtain() -> ASPMInfo {
         ASPMInfo {
-            supported_modes: if fs::metadata("/sys/module/pcie_aspm/parameters/policy").is_err() {
+            supported_modes: if fs::metadata(SysRoot::path(
+                "/sys/module/pcie_aspm/parameters/policy",
+            ))
+            .is_err()
+            {
                 None
             } else {
                 Some(
-                    file_content_to_list("/sys/module/pcie_aspm/parameters/policy")
+                    file_content_to_list(SysRoot::path("/sys/module/pcie_aspm/parameters/policy"))
                         .into_iter()
                         .map(|s| {
                             // The current enabled mode is written [mode_name] when reading the sysfs entry
@@ -463,17 +502,60 @@ pub struct USBDeviceInfo {
 impl USBInfo {
     pub fn obtain() -> USBInfo {
         let mut usb_devices = Vec::new();
-        let lsusb = run_command_with_output("lsusb").0;
 
-        let re = Regex::new(r"ID (\w+:\w+) (.+)").unwrap();
-        for line in lsusb.lines() {
-            let captures = re.captures(line).unwrap();
-            let name = &captures[2];
-            let id = &captures[1];
+        // lsusb resolves nicer names from the usb.ids database, but it can
+        // only ever see the real system
+        let mut lsusb_names = HashMap::new();
+        if SysRoot::is_default() && command_exists("lsusb") {
+            let lsusb = run_command_with_output("lsusb").0;
+
+            let re = Regex::new(r"ID (\w+:\w+) (.+)").unwrap();
+            for captures in lsusb.lines().filter_map(|line| re.captures(line)) {
+                lsusb_names.insert(captures[1].to_string(), captures[2].to_string());
+            }
+        }
+
+        let mut entries: Vec<_> = fs::read_dir(SysRoot::path("/sys/bus/usb/devices"))
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            // Entries containing ':' are interfaces of a device
+            .filter(|entry| !entry.file_name().to_string_lossy().contains(':'))
+            .collect();
+
+        entries.sort_by(|a, b| {
+            natord::compare(a.path().to_str().unwrap(), b.path().to_str().unwrap())
+        });
+
+        for entry in entries {
+            let path = entry.path();
+
+            let (Some(vendor_id), Some(product_id)) = (
+                try_file_content_to_string(path.join("idVendor")),
+                try_file_content_to_string(path.join("idProduct")),
+            ) else {
+                continue;
+            };
+            let id = format!("{vendor_id}:{product_id}");
+
+            let display_name = lsusb_names.get(&id).cloned().unwrap_or_else(|| {
+                [
+                    try_file_content_to_string(path.join("manufacturer")),
+                    try_file_content_to_string(path.join("product")),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ")
+            });
 
             usb_devices.push(USBDeviceInfo {
-                display_name: name.to_string(),
-                id: id.to_string(),
+                display_name: if display_name.is_empty() {
+                    "Unknown device".to_string()
+                } else {
+                    display_name
+                },
+                id,
             });
         }
 
@@ -488,7 +570,7 @@ pub struct SATAInfo {
 
 impl SATAInfo {
     pub fn obtain() -> SATAInfo {
-        let raw = fs::read_dir("/sys/class/scsi_host/");
+        let raw = fs::read_dir(SysRoot::path("/sys/class/scsi_host/"));
         match raw {
             Ok(itr) => SATAInfo {
                 hosts: itr.filter_map(Result::ok).count() as u32,
@@ -509,10 +591,12 @@ pub struct FirmwareInfo {
 
 impl FirmwareInfo {
     pub fn obtain() -> FirmwareInfo {
-        let supports_acpi_profiles = fs::metadata("/sys/firmware/acpi/platform_profile").is_ok();
+        let supports_acpi_profiles =
+            fs::metadata(SysRoot::path("/sys/firmware/acpi/platform_profile")).is_ok();
         FirmwareInfo {
             platform_profiles: if supports_acpi_profiles {
-                file_content_to_list("/sys/firmware/acpi/platform_profile_choices").into()
+                file_content_to_list(SysRoot::path("/sys/firmware/acpi/platform_profile_choices"))
+                    .into()
             } else {
                 None
             },
@@ -647,6 +731,206 @@ impl From<IntelRaplConstraint> for IntelRaplConstraintInfo {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatteryInfo {
+    pub ac_online: bool,
+    pub batteries: Vec<BatteryDeviceInfo>,
+    /// Combined state of all batteries, None if the system has no batteries
+    pub total: Option<BatteryTotalInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum BatteryStatus {
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatteryDeviceInfo {
+    /// Name of the battery under /sys/class/power_supply
+    pub name: String,
+
+    /// Charge percentage
+    pub capacity: Option<u8>,
+    pub status: BatteryStatus,
+    /// Battery chemistry (Li-ion, Li-poly...)
+    pub technology: Option<String>,
+    pub cycle_count: Option<u32>,
+
+    /// In Wh
+    pub energy_now: Option<f32>,
+    /// In Wh
+    pub energy_full: Option<f32>,
+    /// In Wh
+    pub energy_full_design: Option<f32>,
+    /// Percentage of the design capacity that the battery can still hold
+    pub health: Option<f32>,
+    /// Charge or discharge rate in W
+    pub power_now: Option<f32>,
+
+    /// None if unsupported
+    pub charge_start_threshold: Option<u8>,
+    /// None if unsupported
+    pub charge_end_threshold: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatteryTotalInfo {
+    /// Charge percentage weighted by the energy of each battery
+    pub capacity: Option<u8>,
+    /// Charging if any battery is charging, otherwise discharging if any
+    /// battery is discharging
+    pub status: BatteryStatus,
+
+    /// In Wh
+    pub energy_now: Option<f32>,
+    /// In Wh
+    pub energy_full: Option<f32>,
+    /// In Wh
+    pub energy_full_design: Option<f32>,
+    pub health: Option<f32>,
+    /// In W
+    pub power_now: Option<f32>,
+}
+
+impl BatteryInfo {
+    pub fn obtain() -> BatteryInfo {
+        let batteries: Vec<_> = iterate_batteries()
+            .map(BatteryDeviceInfo::from_battery)
+            .collect();
+
+        BatteryInfo {
+            ac_online: system_on_ac(),
+            total: BatteryTotalInfo::from_batteries(&batteries),
+            batteries,
+        }
+    }
+
+    pub fn supports_start_threshold(&self) -> bool {
+        self.batteries
+            .iter()
+            .any(|b| b.charge_start_threshold.is_some())
+    }
+    pub fn supports_end_threshold(&self) -> bool {
+        self.batteries
+            .iter()
+            .any(|b| b.charge_end_threshold.is_some())
+    }
+}
+
+impl BatteryStatus {
+    fn from_sysfs(status: &str) -> BatteryStatus {
+        match status {
+            "Charging" => BatteryStatus::Charging,
+            "Discharging" => BatteryStatus::Discharging,
+            "Not charging" => BatteryStatus::NotCharging,
+            "Full" => BatteryStatus::Full,
+            _ => BatteryStatus::Unknown,
+        }
+    }
+}
+
+impl BatteryDeviceInfo {
+    fn from_battery(battery: Battery) -> BatteryDeviceInfo {
+        let from_micro = |v: u32| v as f32 / 1_000_000.0;
+
+        BatteryDeviceInfo {
+            name: battery.name,
+
+            capacity: battery.capacity.map(|c| c as u8),
+            status: battery
+                .status
+                .as_deref()
+                .map(BatteryStatus::from_sysfs)
+                .unwrap_or(BatteryStatus::Unknown),
+            technology: battery.technology,
+            cycle_count: battery.cycle_count,
+
+            energy_now: battery.energy_now.map(from_micro),
+            energy_full: battery.energy_full.map(from_micro),
+            energy_full_design: battery.energy_full_design.map(from_micro),
+            health: health(battery.energy_full, battery.energy_full_design),
+            power_now: battery.power_now.map(from_micro),
+
+            charge_start_threshold: battery.start_threshold.map(|t| t as u8),
+            charge_end_threshold: battery.end_threshold.map(|t| t as u8),
+        }
+    }
+}
+
+impl BatteryTotalInfo {
+    fn from_batteries(batteries: &[BatteryDeviceInfo]) -> Option<BatteryTotalInfo> {
+        if batteries.is_empty() {
+            return None;
+        }
+
+        // Only sums if every battery reports the value, a partial sum would
+        // be misleading
+        let sum = |get: fn(&BatteryDeviceInfo) -> Option<f32>| -> Option<f32> {
+            batteries.iter().map(get).sum()
+        };
+
+        let energy_now = sum(|b| b.energy_now);
+        let energy_full = sum(|b| b.energy_full);
+        let energy_full_design = sum(|b| b.energy_full_design);
+
+        let capacity = match (energy_now, energy_full) {
+            (Some(now), Some(full)) if full > 0.0 => Some((now / full * 100.0).round() as u8),
+            _ => {
+                let capacities: Option<Vec<u32>> = batteries
+                    .iter()
+                    .map(|b| b.capacity.map(u32::from))
+                    .collect();
+                capacities.map(|c| (c.iter().sum::<u32>() / c.len() as u32) as u8)
+            }
+        };
+
+        let status = if batteries
+            .iter()
+            .any(|b| b.status == BatteryStatus::Charging)
+        {
+            BatteryStatus::Charging
+        } else if batteries
+            .iter()
+            .any(|b| b.status == BatteryStatus::Discharging)
+        {
+            BatteryStatus::Discharging
+        } else if batteries.iter().all(|b| b.status == BatteryStatus::Full) {
+            BatteryStatus::Full
+        } else if batteries
+            .iter()
+            .any(|b| b.status == BatteryStatus::NotCharging)
+        {
+            BatteryStatus::NotCharging
+        } else {
+            BatteryStatus::Unknown
+        };
+
+        Some(BatteryTotalInfo {
+            capacity,
+            status,
+            energy_now,
+            energy_full,
+            energy_full_design,
+            health: match (energy_full, energy_full_design) {
+                (Some(full), Some(design)) if design > 0.0 => Some(full / design * 100.0),
+                _ => None,
+            },
+            power_now: sum(|b| b.power_now),
+        })
+    }
+}
+
+fn health(energy_full: Option<u32>, energy_full_design: Option<u32>) -> Option<f32> {
+    match (energy_full, energy_full_design) {
+        (Some(full), Some(design)) if design != 0 => Some(full as f32 / design as f32 * 100.0),
+        _ => None,
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct OptionalFeaturesInfo {
     pub supports_xautolock: bool,
@@ -674,13 +958,13 @@ impl OptionalFeaturesInfo {
             supports_xset: command_exists("xset"),
             supports_xrandr: command_exists("xrandr"),
             supports_brightnessctl: command_exists("brightnessctl"),
-            supports_wifi_drivers: fs::metadata("/sys/module/iwlwifi").is_ok()
-                && (fs::metadata("/sys/module/iwlmvm").is_ok()
-                    || fs::metadata("/sys/module/iwldvm").is_ok()),
+            supports_wifi_drivers: fs::metadata(SysRoot::path("/sys/module/iwlwifi")).is_ok()
+                && (fs::metadata(SysRoot::path("/sys/module/iwlmvm")).is_ok()
+                    || fs::metadata(SysRoot::path("/sys/module/iwldvm")).is_ok()),
             supports_ifconfig: command_exists("ifconfig"),
-            audio_module: if fs::metadata("/sys/module/snd_hda_intel/").is_ok() {
+            audio_module: if fs::metadata(SysRoot::path("/sys/module/snd_hda_intel/")).is_ok() {
                 AudioModule::SndHdaIntel
-            } else if fs::metadata("/sys/module/snd_ac97_codec/").is_ok() {
+            } else if fs::metadata(SysRoot::path("/sys/module/snd_ac97_codec/")).is_ok() {
                 AudioModule::SndAc9Codec
             } else {
                 AudioModule::Other
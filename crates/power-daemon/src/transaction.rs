@@ -0,0 +1,80 @@
+use std::{cell::RefCell, path::PathBuf};
+
+use log::{debug, warn};
+
+use crate::{
+    helpers::run_command,
+    report::{record_group, GroupReport, SettingsGroup},
+    sysfs::writing::write_str_unsnapshotted,
+};
+
+/// Reverts a single change made while applying a settings group
+pub(crate) enum UndoAction {
+    /// Write back the value a node had before
+    Write { path: PathBuf, value: String },
+    /// Load back a module that was unloaded, with its previous parameters
+    LoadModule { module: String, parameters: String },
+}
+
+impl UndoAction {
+    fn undo(self) {
+        match self {
+            UndoAction::Write { path, value } => {
+                debug!("Rolling back {} to \"{value}\"", path.display());
+                write_str_unsnapshotted(&path, &value);
+            }
+            UndoAction::LoadModule { module, parameters } => {
+                debug!("Rolling back unloaded module {module}");
+                // The module may have been loaded again with the new
+                // parameters, which modprobe would keep
+                run_command(&format!("modprobe -r {module}"));
+                run_command(&format!("modprobe {module} {parameters}"));
+            }
+        }
+    }
+}
+
+thread_local! {
+    /// Changes made on this thread by the group that is being applied
+    static UNDO_LOG: RefCell<Option<Vec<UndoAction>>> = const { RefCell::new(None) };
+}
+
+/// Applies a settings group with `f` as a whole: if any of its changes
+/// fails, every change that was made is reverted in reverse order and the
+/// report is marked as rolled back
+pub fn apply_group(group: SettingsGroup, f: impl FnOnce()) -> GroupReport {
+    let previous = UNDO_LOG.with(|u| u.borrow_mut().replace(Vec::new()));
+
+    let mut report = record_group(group, f);
+
+    let undo_log = UNDO_LOG.with(|u| {
+        let mut undo_log = u.borrow_mut();
+        let recorded = undo_log.take().unwrap_or_default();
+        *undo_log = previous;
+        recorded
+    });
+
+    if report.has_failures() && !undo_log.is_empty() {
+        warn!(
+            "Applying {group:?} settings failed, rolling back {} changes",
+            undo_log.len()
+        );
+
+        for action in undo_log.into_iter().rev() {
+            action.undo();
+        }
+        report.rolled_back = true;
+    }
+
+    report
+}
+
+/// Adds the action to the undo log of the group being applied on this
+/// thread, if any
+pub(crate) fn record_undo(action: impl FnOnce() -> UndoAction) {
+    UNDO_LOG.with(|u| {
+        if let Some(ref mut undo_log) = *u.borrow_mut() {
+            undo_log.push(action());
+        }
+    })
+}
@@ -0,0 +1,364 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    report::SettingsGroup, BatterySettings, CPUFreqDriver, CoreSetting, Profile, SystemInfo,
+};
+
+/// A problem found when checking a profile against the hardware it is going to
+/// be applied on
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ProfileDiagnostic {
+    /// `value` is not one of the values the hardware accepts, writing it will
+    /// fail
+    UnsupportedValue {
+        group: SettingsGroup,
+        setting: String,
+        value: String,
+        supported: Vec<String>,
+    },
+    /// `value` is outside of what the hardware supports and will be clamped
+    OutOfRange {
+        group: SettingsGroup,
+        setting: String,
+        value: u32,
+        min: u32,
+        max: u32,
+    },
+    /// The core override targets a CPU that does not exist
+    UnknownCore { cpu_id: u32, cpu_count: u32 },
+    /// The system does not support the setting at all, it will be ignored
+    Unavailable {
+        group: SettingsGroup,
+        setting: String,
+    },
+    /// The value is invalid regardless of the hardware
+    InvalidValue {
+        group: SettingsGroup,
+        setting: String,
+        reason: String,
+    },
+}
+
+impl ProfileDiagnostic {
+    /// Errors make applying the profile fail, the rest only mean that some
+    /// values will not end up as configured
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            ProfileDiagnostic::UnsupportedValue { .. }
+                | ProfileDiagnostic::UnknownCore { .. }
+                | ProfileDiagnostic::InvalidValue { .. }
+        )
+    }
+}
+
+impl Display for ProfileDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileDiagnostic::UnsupportedValue {
+                group,
+                setting,
+                value,
+                supported,
+            } => write!(
+                f,
+                "{group:?} {setting} \"{value}\" is not supported, expected one of: {}",
+                supported.join(", ")
+            ),
+            ProfileDiagnostic::OutOfRange {
+                group,
+                setting,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "{group:?} {setting} {value} is outside of the supported range {min}-{max}"
+            ),
+            ProfileDiagnostic::UnknownCore { cpu_id, cpu_count } => write!(
+                f,
+                "Core override for CPU {cpu_id} but the system only has {cpu_count} CPUs"
+            ),
+            ProfileDiagnostic::Unavailable { group, setting } => {
+                write!(f, "{group:?} {setting} is not supported by this system")
+            }
+            ProfileDiagnostic::InvalidValue {
+                group,
+                setting,
+                reason,
+            } => write!(f, "{group:?} {setting}: {reason}"),
+        }
+    }
+}
+
+pub(crate) fn validate_profile(
+    profile: &Profile,
+    system_info: &SystemInfo,
+) -> Vec<ProfileDiagnostic> {
+    let mut diagnostics = Vec::new();
+
+    validate_cpu(profile, system_info, &mut diagnostics);
+    if let Some(ref cores) = profile.cpu_core_settings.cores {
+        for core in cores {
+            validate_core(core, system_info, &mut diagnostics);
+        }
+    }
+
+    let aspm = &profile.aspm_settings;
+    if let Some(ref mode) = aspm.mode {
+        check_supported(
+            SettingsGroup::ASPM,
+            "mode",
+            mode,
+            system_info.pci_info.aspm_info.supported_modes.as_deref(),
+            &mut diagnostics,
+        );
+    }
+
+    let firmware = &profile.firmware_settings;
+    if let Some(ref platform_profile) = firmware.platform_profile {
+        check_supported(
+            SettingsGroup::Firmware,
+            "platform_profile",
+            platform_profile,
+            system_info.firmware_info.platform_profiles.as_deref(),
+            &mut diagnostics,
+        );
+    }
+
+    if profile.sata_settings.active_link_pm_policy.is_some() && system_info.sata_info.hosts == 0 {
+        diagnostics.push(ProfileDiagnostic::Unavailable {
+            group: SettingsGroup::SATA,
+            setting: "active_link_pm_policy".to_string(),
+        });
+    }
+
+    let rapl = &profile.rapl_settings;
+    if (rapl.package.is_some() || rapl.core.is_some() || rapl.uncore.is_some())
+        && system_info.rapl_info.rapl_missing
+    {
+        diagnostics.push(ProfileDiagnostic::Unavailable {
+            group: SettingsGroup::Rapl,
+            setting: "limits".to_string(),
+        });
+    }
+
+    let battery = &profile.battery_settings;
+    if (battery.charge_start_threshold.is_some() || battery.charge_end_threshold.is_some())
+        && !system_info
+            .battery_info
+            .batteries
+            .iter()
+            .any(|b| b.charge_end_threshold.is_some())
+    {
+        diagnostics.push(ProfileDiagnostic::Unavailable {
+            group: SettingsGroup::Battery,
+            setting: "charge thresholds".to_string(),
+        });
+    }
+    if let Some(reason) = charge_thresholds_problem(battery) {
+        diagnostics.push(ProfileDiagnostic::InvalidValue {
+            group: SettingsGroup::Battery,
+            setting: "charge thresholds".to_string(),
+            reason,
+        });
+    }
+
+    diagnostics
+}
+
+/// Describes why the kernel would reject the charge thresholds, if it would
+pub(crate) fn charge_thresholds_problem(battery: &BatterySettings) -> Option<String> {
+    let (start, end) = (battery.charge_start_threshold, battery.charge_end_threshold);
+
+    if let Some(start) = start.filter(|start| *start >= 100) {
+        return Some(format!("start threshold {start}% must be below 100%"));
+    }
+    if let Some(end) = end.filter(|end| !(1..=100).contains(end)) {
+        return Some(format!("end threshold {end}% must be between 1% and 100%"));
+    }
+    if let (Some(start), Some(end)) = (start, end) {
+        if start >= end {
+            return Some(format!(
+                "start threshold {start}% must be below end threshold {end}%"
+            ));
+        }
+    }
+
+    None
+}
+
+fn validate_cpu(
+    profile: &Profile,
+    system_info: &SystemInfo,
+    diagnostics: &mut Vec<ProfileDiagnostic>,
+) {
+    let cpu = &profile.cpu_settings;
+    let cpu_info = &system_info.cpu_info;
+
+    if let Some(ref mode) = cpu.mode {
+        let modes: Option<&[&str]> = match cpu_info.driver {
+            CPUFreqDriver::Intel => Some(&["active", "passive", "off"]),
+            CPUFreqDriver::Amd => Some(&["active", "passive", "guided"]),
+            CPUFreqDriver::Other => None,
+        };
+        let modes: Option<Vec<String>> =
+            modes.map(|modes| modes.iter().map(|m| m.to_string()).collect());
+
+        check_supported(
+            SettingsGroup::CPU,
+            "mode",
+            mode,
+            modes.as_deref(),
+            diagnostics,
+        );
+    }
+
+    if let Some(ref governor) = cpu.governor {
+        check_supported(
+            SettingsGroup::CPU,
+            "governor",
+            governor,
+            Some(&cpu_info.available_governors),
+            diagnostics,
+        );
+    }
+
+    for (setting, frequency) in [("min_freq", cpu.min_freq), ("max_freq", cpu.max_freq)] {
+        if let Some(frequency) = frequency {
+            check_range(
+                SettingsGroup::CPU,
+                setting,
+                frequency,
+                cpu_info.total_min_frequency,
+                cpu_info.total_max_frequency,
+                diagnostics,
+            );
+        }
+    }
+
+    for (setting, pct) in [
+        ("min_perf_pct", cpu.min_perf_pct),
+        ("max_perf_pct", cpu.max_perf_pct),
+    ] {
+        if pct.is_some() && !cpu_info.has_perf_pct_scaling {
+            diagnostics.push(ProfileDiagnostic::Unavailable {
+                group: SettingsGroup::CPU,
+                setting: setting.to_string(),
+            });
+        }
+    }
+
+    if cpu.boost.is_some() && cpu_info.boost.is_none() {
+        diagnostics.push(ProfileDiagnostic::Unavailable {
+            group: SettingsGroup::CPU,
+            setting: "boost".to_string(),
+        });
+    }
+    if cpu.hwp_dyn_boost.is_some() && cpu_info.hwp_dynamic_boost.is_none() {
+        diagnostics.push(ProfileDiagnostic::Unavailable {
+            group: SettingsGroup::CPU,
+            setting: "hwp_dyn_boost".to_string(),
+        });
+    }
+}
+
+fn validate_core(
+    core: &CoreSetting,
+    system_info: &SystemInfo,
+    diagnostics: &mut Vec<ProfileDiagnostic>,
+) {
+    let cpu_info = &system_info.cpu_info;
+
+    let Some(core_info) = cpu_info
+        .cores
+        .iter()
+        .find(|c| c.logical_cpu_id == core.cpu_id)
+    else {
+        diagnostics.push(ProfileDiagnostic::UnknownCore {
+            cpu_id: core.cpu_id,
+            cpu_count: cpu_info.cores.len() as u32,
+        });
+        return;
+    };
+
+    if let Some(ref governor) = core.governor {
+        check_supported(
+            SettingsGroup::CPUCores,
+            &format!("cpu{} governor", core.cpu_id),
+            governor,
+            Some(&cpu_info.available_governors),
+            diagnostics,
+        );
+    }
+
+    // Frequencies of offline cores are unknown
+    if core_info.total_max_frequency == 0 {
+        return;
+    }
+
+    for (setting, frequency) in [
+        ("min_frequency", core.min_frequency),
+        ("max_frequency", core.max_frequency),
+    ] {
+        if let Some(frequency) = frequency {
+            check_range(
+                SettingsGroup::CPUCores,
+                &format!("cpu{} {setting}", core.cpu_id),
+                frequency,
+                core_info.total_min_frequency,
+                core_info.total_max_frequency,
+                diagnostics,
+            );
+        }
+    }
+}
+
+/// Checks that `value` is one of `supported`, None meaning that the setting is
+/// not available at all
+fn check_supported(
+    group: SettingsGroup,
+    setting: &str,
+    value: &str,
+    supported: Option<&[String]>,
+    diagnostics: &mut Vec<ProfileDiagnostic>,
+) {
+    match supported {
+        None => diagnostics.push(ProfileDiagnostic::Unavailable {
+            group,
+            setting: setting.to_string(),
+        }),
+        // Nothing to check against
+        Some([]) => {}
+        Some(supported) if !supported.iter().any(|s| s == value) => {
+            diagnostics.push(ProfileDiagnostic::UnsupportedValue {
+                group,
+                setting: setting.to_string(),
+                value: value.to_string(),
+                supported: supported.to_vec(),
+            })
+        }
+        Some(_) => {}
+    }
+}
+
+fn check_range(
+    group: SettingsGroup,
+    setting: &str,
+    value: u32,
+    min: u32,
+    max: u32,
+    diagnostics: &mut Vec<ProfileDiagnostic>,
+) {
+    if value < min || value > max {
+        diagnostics.push(ProfileDiagnostic::OutOfRange {
+            group,
+            setting: setting.to_string(),
+            value,
+            min,
+            max,
+        });
+    }
+}
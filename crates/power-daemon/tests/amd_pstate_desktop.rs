@@ -0,0 +1,361 @@
+mod common;
+
+use common::{clear_host_commands, instance_for_fixture, read, use_fixture, use_fixture_copy};
+use power_daemon::{
+    persistence::backup_path,
+    profiles_generator::{self, DefaultProfileType},
+    AmdGpuInfo, CPUFreqDriver, ControlError, CoreSetting, Instance, NameCollision, ProfileBundle,
+    SettingsGroup, StateChange, SystemInfo,
+};
+use std::{
+    collections::HashSet,
+    os::unix::fs::PermissionsExt,
+    sync::{Arc, Mutex},
+};
+
+const FIXTURE: &str = "amd-pstate-desktop";
+
+#[test]
+fn obtains_system_info() {
+    let _guard = use_fixture(FIXTURE);
+
+    let info = SystemInfo::obtain();
+
+    let cpu = &info.cpu_info;
+    assert_eq!(cpu.driver, CPUFreqDriver::Amd);
+    assert_eq!(cpu.mode.as_deref(), Some("active"));
+    assert!(cpu.has_epp);
+    assert!(!cpu.has_epb && !cpu.has_perf_pct_scaling);
+    assert_eq!(cpu.boost, Some(true));
+    assert_eq!(cpu.hwp_dynamic_boost, None);
+    assert!(!cpu.hybrid);
+    assert_eq!(cpu.cores.len(), 8);
+    assert!(cpu.cores.iter().all(|c| c.is_performance_core.is_none()));
+    assert_eq!(cpu.total_max_frequency, 4700);
+
+    assert_eq!(info.pci_info.pci_devices.len(), 4);
+    assert_eq!(
+        info.usb_info
+            .usb_devices
+            .iter()
+            .map(|d| d.display_name.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "Logitech USB Receiver",
+            "Linux 6.8.0 xhci-hcd xHCI Host Controller",
+            "Linux 6.8.0 xhci-hcd xHCI Host Controller"
+        ]
+    );
+
+    assert_eq!(info.sata_info.hosts, 2);
+    assert_eq!(info.firmware_info.platform_profiles, None);
+    assert_eq!(info.gpu_info.intel_info, None);
+    assert_eq!(
+        info.gpu_info.amd_info,
+        Some(AmdGpuInfo::AmdGpu {
+            dpm_perf: "auto".to_string()
+        })
+    );
+    assert!(info.rapl_info.rapl_missing);
+
+    assert!(!info.battery_info.ac_online);
+    assert!(info.battery_info.batteries.is_empty());
+    assert!(!info.battery_info.supports_end_threshold());
+}
+
+#[test]
+fn applies_default_profile() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    let info = SystemInfo::obtain();
+    let mut profile =
+        profiles_generator::create_default("Performance", DefaultProfileType::Performance, &info);
+    clear_host_commands(&mut profile);
+
+    // Only available on intel_pstate
+    profile.cpu_settings.hwp_dyn_boost = Some(true);
+
+    let report = profile.apply_all();
+    assert!(!report.has_failures());
+    let cpu_report = report
+        .groups
+        .iter()
+        .find(|g| g.group == SettingsGroup::CPU)
+        .unwrap();
+    assert!(cpu_report.has_unsupported());
+
+    let cpu = "/sys/devices/system/cpu";
+    assert_eq!(read(&root, &format!("{cpu}/amd_pstate/status")), "active");
+    assert_eq!(read(&root, &format!("{cpu}/cpufreq/boost")), "1");
+    for idx in 0..8 {
+        assert_eq!(
+            read(&root, &format!("{cpu}/cpu{idx}/cpufreq/scaling_governor")),
+            "powersave"
+        );
+        assert_eq!(
+            read(
+                &root,
+                &format!("{cpu}/cpu{idx}/cpufreq/energy_performance_preference")
+            ),
+            "balance_performance"
+        );
+    }
+
+    assert_eq!(
+        read(&root, "/sys/module/pcie_aspm/parameters/policy"),
+        "performance"
+    );
+    assert_eq!(
+        read(&root, "/sys/bus/pci/devices/0000:03:00.0/power/control"),
+        "on"
+    );
+    assert_eq!(
+        read(
+            &root,
+            "/sys/class/scsi_host/host1/link_power_management_policy"
+        ),
+        "max_performance"
+    );
+    assert_eq!(read(&root, "/proc/sys/vm/laptop_mode"), "2");
+    assert_eq!(
+        read(
+            &root,
+            "/sys/class/drm/card0/device/power_dpm_force_performance_level"
+        ),
+        "high"
+    );
+}
+
+#[test]
+fn strips_hardware_specific_settings_on_import() {
+    let _guard = use_fixture(FIXTURE);
+    let (mut instance, _, _) = instance_for_fixture(FIXTURE);
+
+    // A profile tuned on a machine with more cores
+    let mut profile = profiles_generator::create_empty("Shared");
+    profile.cpu_core_settings.cores = Some(vec![CoreSetting {
+        cpu_id: 12,
+        online: Some(false),
+        ..Default::default()
+    }]);
+    let bundle = ProfileBundle::new(profile).to_toml();
+
+    assert!(instance
+        .import_profile(&bundle, NameCollision::Rename, false)
+        .is_err());
+
+    let result = instance
+        .import_profile(&bundle, NameCollision::Rename, true)
+        .unwrap();
+    assert_eq!(result.profile_name, "Shared");
+    assert_eq!(
+        result.hardware_specific_fields,
+        vec!["cpu_core_settings.cores".to_string()]
+    );
+    assert!(result.stripped);
+}
+
+#[test]
+fn keeps_backups_when_renaming_profiles() {
+    let (_guard, _) = use_fixture_copy(FIXTURE);
+    let (mut instance, config_path, profiles_path) = instance_for_fixture(FIXTURE);
+    let notes = profiles_path.join("notes.txt");
+    std::fs::write(&notes, "not a profile").unwrap();
+    let old_name = power_daemon::parse_config(&config_path).profiles[0].clone();
+
+    instance
+        .update_profile_name(0, "Renamed".to_string())
+        .unwrap();
+
+    assert!(notes.exists());
+    assert!(profiles_path.join("Renamed.toml").exists());
+    assert!(!profiles_path.join(format!("{old_name}.toml")).exists());
+    assert!(backup_path(&profiles_path.join(format!("{old_name}.toml"))).exists());
+
+    let config = power_daemon::parse_config(&config_path);
+    assert_eq!(config.profiles[0], "Renamed");
+    let previous = std::fs::read_to_string(backup_path(&config_path)).unwrap();
+    assert!(previous.contains(&format!("\"{old_name}\"")));
+
+    // Profile files deleted by hand are not needed to rename or remove them
+    std::fs::remove_file(profiles_path.join(format!("{}.toml", config.profiles[1]))).unwrap();
+    instance
+        .update_profile_name(1, "Missing".to_string())
+        .unwrap();
+    assert!(profiles_path.join("Missing.toml").exists());
+
+    std::fs::remove_file(profiles_path.join("Missing.toml")).unwrap();
+    instance.remove_profile(1).unwrap();
+    assert!(!power_daemon::parse_config(&config_path)
+        .profiles
+        .contains(&"Missing".to_string()));
+}
+
+#[test]
+fn sets_profile_mapping() {
+    let _guard = use_fixture_copy(FIXTURE);
+    let (mut instance, config_path, _) = instance_for_fixture(FIXTURE);
+    let private = std::fs::Permissions::from_mode(0o600);
+    std::fs::set_permissions(&config_path, private).unwrap();
+
+    assert!(instance.set_ac_profile("Missing".to_string()).is_err());
+    assert!(instance.set_bat_profile("../config".to_string()).is_err());
+    assert!(instance
+        .set_persistent_profile_override(String::new())
+        .is_err());
+
+    instance.set_ac_profile("Balanced".to_string()).unwrap();
+    instance.set_bat_profile("Powersave++".to_string()).unwrap();
+    let config = power_daemon::parse_config(&config_path);
+    assert_eq!(config.ac_profile, "Balanced");
+    assert_eq!(config.bat_profile, "Powersave++");
+    assert_eq!(config.profile_override, None);
+    let mode = std::fs::metadata(&config_path)
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[test]
+fn rejects_invalid_profile_changes() {
+    let _guard = use_fixture_copy(FIXTURE);
+    let (mut instance, config_path, _) = instance_for_fixture(FIXTURE);
+    let profiles = power_daemon::parse_config(&config_path).profiles;
+    instance.update_full();
+    let count = profiles.len();
+    let active = profiles
+        .iter()
+        .position(|name| *name == instance.get_active_profile_name())
+        .unwrap();
+    let other_name = profiles[(active + 1) % count].clone();
+
+    assert_eq!(
+        instance.reset_profile(count),
+        Err(ControlError::IndexOutOfRange(count))
+    );
+    assert_eq!(
+        instance.swap_profile_order(0, count),
+        Err(ControlError::IndexOutOfRange(count))
+    );
+    assert_eq!(
+        instance.update_profile_name(active, other_name.clone()),
+        Err(ControlError::NameTaken(other_name))
+    );
+    assert_eq!(
+        instance.update_profile_name(active, "../config".to_string()),
+        Err(ControlError::InvalidProfileName("../config".to_string()))
+    );
+    assert_eq!(
+        instance.try_set_profile_override("Missing".to_string()),
+        Err(ControlError::ProfileNotFound("Missing".to_string()))
+    );
+    assert!(matches!(
+        instance.remove_profile(active),
+        Err(ControlError::ProfileActive(_))
+    ));
+
+    for name in profiles.iter().filter(|name| **name != profiles[active]) {
+        let idx = power_daemon::parse_config(&config_path)
+            .profiles
+            .iter()
+            .position(|profile| profile == name)
+            .unwrap();
+        instance.remove_profile(idx).unwrap();
+    }
+    assert_eq!(instance.remove_profile(0), Err(ControlError::LastProfile));
+}
+
+#[test]
+fn addresses_profiles_by_id() {
+    let _guard = use_fixture_copy(FIXTURE);
+    let (mut instance, config_path, profiles_path) = instance_for_fixture(FIXTURE);
+    let mut config = power_daemon::parse_config(&config_path);
+    let count = config.profiles.len();
+    let ids = (0..count)
+        .map(|idx| instance.profile_id_at(idx).unwrap())
+        .collect::<Vec<_>>();
+    assert!(ids.iter().all(|id| !id.is_empty()));
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), count);
+
+    // IDs are kept in the profile files, copies get a new one
+    let first_path = profiles_path.join(format!("{}.toml", config.profiles[0]));
+    std::fs::copy(&first_path, profiles_path.join("Copy.toml")).unwrap();
+    let mut with_copy = config.clone();
+    with_copy.profiles.push("Copy".to_string());
+    let reloaded = Instance::new(with_copy, &config_path, &profiles_path);
+    assert_eq!(reloaded.profile_id_at(0).unwrap(), ids[0]);
+    assert!(!ids.contains(&reloaded.profile_id_at(count).unwrap()));
+
+    // Files edited by hand keep the ID the daemon knows, copies listed before
+    // the original do not take it over
+    let mut table: toml::Table = std::fs::read_to_string(&first_path)
+        .unwrap()
+        .parse()
+        .unwrap();
+    table.remove("profile_id");
+    std::fs::write(&first_path, toml::to_string(&table).unwrap()).unwrap();
+    instance.reload_from_disk();
+    assert_eq!(instance.profile_id_at(0).unwrap(), ids[0]);
+    assert!(std::fs::read_to_string(&first_path)
+        .unwrap()
+        .contains(&ids[0]));
+
+    std::fs::copy(&first_path, profiles_path.join("Copy.toml")).unwrap();
+    config.profiles.insert(0, "Copy".to_string());
+    power_daemon::serialize_config(&config, &config_path);
+    instance.reload_from_disk();
+    assert_eq!(instance.find_profile_by_id(&ids[0]), Ok(1));
+    assert!(!ids.contains(&instance.profile_id_at(0).unwrap()));
+
+    instance.swap_profile_order(0, 1).unwrap();
+    assert_eq!(instance.find_profile_by_id(&ids[0]), Ok(0));
+    instance
+        .update_profile_name(0, "Renamed".to_string())
+        .unwrap();
+    instance.reset_profile(0).unwrap();
+    assert_eq!(instance.profile_id_at(0).unwrap(), ids[0]);
+
+    assert_eq!(
+        instance.find_profile_by_id("missing"),
+        Err(ControlError::ProfileIdNotFound("missing".to_string()))
+    );
+}
+
+#[test]
+fn reports_state_changes() {
+    let _guard = use_fixture_copy(FIXTURE);
+    let (mut instance, _, _) = instance_for_fixture(FIXTURE);
+    instance.update_full();
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    instance.set_change_listener({
+        let changes = changes.clone();
+        move |change| changes.lock().unwrap().push(change)
+    });
+
+    // Nothing changes when the same profile is applied again
+    instance.update_full();
+    assert!(changes.lock().unwrap().is_empty());
+
+    instance.set_profile_override("Performance".to_string());
+    assert_eq!(
+        changes.lock().unwrap().drain(..).collect::<Vec<_>>(),
+        vec![
+            StateChange::ActiveProfile("Performance".to_string()),
+            StateChange::Override(instance.get_profile_override()),
+        ]
+    );
+
+    instance
+        .update_profile_name(0, "Renamed".to_string())
+        .unwrap();
+    let renamed = changes.lock().unwrap().drain(..).collect::<Vec<_>>();
+    assert!(renamed.contains(&StateChange::Profiles));
+    assert!(renamed.contains(&StateChange::Config));
+
+    instance.remove_profile_override();
+    assert!(changes
+        .lock()
+        .unwrap()
+        .contains(&StateChange::Override(None)));
+}
@@ -0,0 +1,131 @@
+use std::{
+    fs,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use power_daemon::{
+    profiles_generator::{self, DefaultProfileType},
+    serialize_config,
+    sysfs::root::SysRoot,
+    Config, Instance, NetworkSettings, Profile, RadioSettings, ScreenSettings, SleepSettings,
+    SystemInfo,
+};
+
+/// The sysfs root is global to the process, tests that change it must not run
+/// concurrently
+static SYS_ROOT_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Points the sysfs root at the fixture, which must not be written to
+pub fn use_fixture(name: &str) -> MutexGuard<'static, ()> {
+    let guard = SYS_ROOT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    SysRoot::set(fixture_path(name));
+    guard
+}
+
+/// Points the sysfs root at a fresh copy of the fixture that can be freely
+/// written to. Returns the path of the copy
+pub fn use_fixture_copy(name: &str) -> (MutexGuard<'static, ()>, PathBuf) {
+    let guard = SYS_ROOT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let copy = std::env::temp_dir().join(format!("power-daemon-{name}-{}", std::process::id()));
+    if copy.exists() {
+        fs::remove_dir_all(&copy).unwrap();
+    }
+    copy_dir(&fixture_path(name), &copy);
+
+    SysRoot::set(&copy);
+    (guard, copy)
+}
+
+/// Creates a config file and the default profiles for the current sysfs root
+/// in a fresh directory. Returns the paths of the config file and the
+/// profiles directory
+pub fn create_config_dir(name: &str) -> (PathBuf, PathBuf) {
+    let dir =
+        std::env::temp_dir().join(format!("power-daemon-config-{name}-{}", std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    let profiles_path = dir.join("profiles");
+    fs::create_dir_all(&profiles_path).unwrap();
+
+    let config = Config::create_default();
+    let system_info = SystemInfo::obtain();
+    for name in config.profiles.iter() {
+        profiles_generator::create_profile_file(
+            &profiles_path,
+            DefaultProfileType::from_name(name.clone()).unwrap(),
+            &system_info,
+        );
+    }
+
+    let config_path = dir.join("config.toml");
+    serialize_config(&config, &config_path);
+
+    (config_path, profiles_path)
+}
+
+/// Creates the config files like `create_config_dir` with host commands
+/// cleared from every profile and loads them into an instance. Returns the
+/// instance and the paths of the config file and the profiles directory
+pub fn instance_for_fixture(name: &str) -> (Instance, PathBuf, PathBuf) {
+    let (config_path, profiles_path) = create_config_dir(name);
+
+    for entry in fs::read_dir(&profiles_path).unwrap().flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "toml") {
+            continue;
+        }
+
+        let mut profile: Profile = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        clear_host_commands(&mut profile);
+        fs::write(&path, toml::to_string_pretty(&profile).unwrap()).unwrap();
+    }
+
+    let config = power_daemon::parse_config(&config_path);
+    let instance = Instance::new(config, &config_path, &profiles_path);
+
+    (instance, config_path, profiles_path)
+}
+
+/// Clears the settings that are applied by running commands, which would
+/// affect the host
+pub fn clear_host_commands(profile: &mut Profile) {
+    profile.sleep_settings = SleepSettings::default();
+    profile.screen_settings = ScreenSettings::default();
+    profile.radio_settings = RadioSettings::default();
+    profile.network_settings = NetworkSettings::default();
+}
+
+pub fn read(root: &Path, path: &str) -> String {
+    fs::read_to_string(root.join(path.trim_start_matches('/')))
+        .unwrap_or_else(|e| panic!("Could not read {path}: {e}"))
+        .trim()
+        .to_string()
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+
+    for entry in fs::read_dir(from).unwrap().flatten() {
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type().unwrap();
+
+        if file_type.is_symlink() {
+            symlink(fs::read_link(entry.path()).unwrap(), target).unwrap();
+        } else if file_type.is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
@@ -0,0 +1,689 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{
+    clear_host_commands, create_config_dir, instance_for_fixture, read, use_fixture,
+    use_fixture_copy,
+};
+use power_daemon::{
+    migration,
+    profiles_generator::{self, DefaultProfileType},
+    ASPMSettings, BatteryInfo, BatterySettings, CPUFreqDriver, CoreSetting, Instance,
+    KernelSettings, NameCollision, OverrideExpiry, Profile, ProfileBundle, ProfileDiagnostic,
+    ReducedUpdate, RuleCondition, SettingChange, SettingsGroup, SystemInfo,
+};
+
+const FIXTURE: &str = "intel-hybrid-laptop";
+
+#[test]
+fn obtains_system_info() {
+    let _guard = use_fixture(FIXTURE);
+
+    let info = SystemInfo::obtain();
+
+    let cpu = &info.cpu_info;
+    assert_eq!(cpu.driver, CPUFreqDriver::Intel);
+    assert_eq!(cpu.mode.as_deref(), Some("active"));
+    assert!(cpu.has_epp && cpu.has_epb && cpu.has_perf_pct_scaling);
+    assert_eq!(cpu.boost, Some(true));
+    assert_eq!(cpu.hwp_dynamic_boost, Some(false));
+    assert!(cpu.hybrid);
+    assert_eq!(cpu.cores.len(), 8);
+    assert_eq!(
+        cpu.cores
+            .iter()
+            .map(|c| c.is_performance_core)
+            .collect::<Vec<_>>(),
+        [[Some(true); 4], [Some(false); 4]].concat()
+    );
+    assert_eq!(cpu.cores[0].online, None);
+    assert_eq!(cpu.cores[1].physical_core_id, 0);
+    assert_eq!(cpu.cores[2].physical_core_id, 1);
+    assert_eq!(cpu.total_min_frequency, 400);
+    assert_eq!(cpu.total_max_frequency, 4400);
+
+    assert_eq!(
+        info.pci_info
+            .pci_devices
+            .iter()
+            .map(|d| d.pci_address.as_str())
+            .collect::<Vec<_>>(),
+        [
+            "0000:00:00.0",
+            "0000:00:02.0",
+            "0000:00:14.3",
+            "0000:01:00.0"
+        ]
+    );
+    assert_eq!(
+        info.pci_info.aspm_info.supported_modes,
+        Some(
+            ["default", "performance", "powersave", "powersupersave"]
+                .map(String::from)
+                .to_vec()
+        )
+    );
+
+    let usb_devices = &info.usb_info.usb_devices;
+    assert_eq!(
+        usb_devices
+            .iter()
+            .map(|d| d.id.as_str())
+            .collect::<Vec<_>>(),
+        ["0bda:5634", "8087:0033", "1d6b:0002", "1d6b:0003"]
+    );
+    assert_eq!(usb_devices[0].display_name, "Generic Integrated_Webcam_HD");
+    assert_eq!(usb_devices[1].display_name, "Unknown device");
+
+    assert_eq!(info.sata_info.hosts, 0);
+    assert_eq!(
+        info.firmware_info.platform_profiles,
+        Some(
+            ["low-power", "balanced", "performance"]
+                .map(String::from)
+                .to_vec()
+        )
+    );
+
+    let gpu = info.gpu_info.intel_info.as_ref().unwrap();
+    assert_eq!((gpu.min_frequency, gpu.max_frequency), (100, 1400));
+    assert_eq!(info.gpu_info.amd_info, None);
+
+    assert!(!info.rapl_info.rapl_missing);
+    let package = info.rapl_info.package.as_ref().unwrap();
+    assert_eq!(package.long_term.as_ref().unwrap().power_limit, 28);
+    assert_eq!(package.peak_power.as_ref().unwrap().time_window, None);
+    assert!(info.rapl_info.core.is_some() && info.rapl_info.uncore.is_some());
+
+    let battery = &info.battery_info;
+    assert!(!battery.ac_online);
+    assert_eq!(battery.batteries.len(), 1);
+    assert_eq!(battery.batteries[0].capacity, Some(76));
+    assert_eq!(battery.batteries[0].charge_end_threshold, Some(100));
+    assert!(battery.supports_start_threshold() && battery.supports_end_threshold());
+}
+
+#[test]
+fn reports_system_batteries_only() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    let supplies = root.join("sys/class/power_supply");
+    let mouse = supplies.join("hidpp_battery_0");
+    std::fs::create_dir(&mouse).unwrap();
+    std::fs::write(mouse.join("type"), "Battery").unwrap();
+    std::fs::write(mouse.join("scope"), "Device").unwrap();
+    std::fs::write(mouse.join("capacity"), "100").unwrap();
+
+    // The power is computed from the current and the live voltage
+    let battery = supplies.join("BAT0");
+    std::fs::remove_file(battery.join("power_now")).unwrap();
+    std::fs::write(battery.join("current_now"), "500000").unwrap();
+    std::fs::write(battery.join("voltage_now"), "12000000").unwrap();
+    std::fs::write(battery.join("voltage_min_design"), "11000000").unwrap();
+
+    let info = BatteryInfo::obtain();
+    assert_eq!(info.batteries.len(), 1);
+    assert_eq!(info.batteries[0].name, "BAT0");
+    assert_eq!(info.batteries[0].power_now, Some(6.0));
+    assert_eq!(info.total.unwrap().capacity, Some(76));
+    assert!(RuleCondition::BatteryBelow(80).is_met());
+}
+
+#[test]
+fn applies_default_profile() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    let info = SystemInfo::obtain();
+    let mut profile =
+        profiles_generator::create_default("Powersave", DefaultProfileType::Powersave, &info);
+    clear_host_commands(&mut profile);
+    // Thresholds set through other tools are kept unless configured
+    assert_eq!(profile.battery_settings, BatterySettings::default());
+    profile.battery_settings = BatterySettings {
+        charge_start_threshold: Some(75),
+        charge_end_threshold: Some(80),
+    };
+
+    let report = profile.apply_all();
+    assert_eq!(report.profile_name, "Powersave");
+    assert_eq!(report.groups.len(), 16);
+    assert!(!report.has_failures());
+
+    let cpu = "/sys/devices/system/cpu";
+    assert_eq!(read(&root, &format!("{cpu}/intel_pstate/status")), "active");
+    assert_eq!(read(&root, &format!("{cpu}/intel_pstate/no_turbo")), "1");
+    assert_eq!(
+        read(&root, &format!("{cpu}/intel_pstate/max_perf_pct")),
+        "100"
+    );
+    for idx in 0..8 {
+        assert_eq!(
+            read(
+                &root,
+                &format!("{cpu}/cpu{idx}/cpufreq/energy_performance_preference")
+            ),
+            "balance_power"
+        );
+    }
+
+    assert_eq!(
+        read(&root, "/sys/module/pcie_aspm/parameters/policy"),
+        "powersave"
+    );
+    assert_eq!(
+        read(&root, "/sys/bus/pci/devices/0000:00:14.3/power/control"),
+        "auto"
+    );
+    assert_eq!(
+        read(&root, "/sys/bus/usb/devices/1-6/power/control"),
+        "auto"
+    );
+    assert_eq!(read(&root, "/proc/sys/kernel/nmi_watchdog"), "0");
+    assert_eq!(
+        read(&root, "/proc/sys/vm/dirty_writeback_centisecs"),
+        "4500"
+    );
+    assert_eq!(
+        read(&root, "/sys/firmware/acpi/platform_profile"),
+        "low-power"
+    );
+    assert_eq!(
+        read(&root, "/sys/module/snd_hda_intel/parameters/power_save"),
+        "5"
+    );
+    assert_eq!(read(&root, "/sys/class/drm/card1/gt_max_freq_mhz"), "1140");
+
+    let battery = "/sys/class/power_supply/BAT0";
+    assert_eq!(
+        read(&root, &format!("{battery}/charge_control_start_threshold")),
+        "75"
+    );
+    assert_eq!(
+        read(&root, &format!("{battery}/charge_control_end_threshold")),
+        "80"
+    );
+}
+
+#[test]
+fn restores_original_values() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    let info = SystemInfo::obtain();
+    let mut profile =
+        profiles_generator::create_default("Balanced", DefaultProfileType::Balanced, &info);
+    clear_host_commands(&mut profile);
+
+    profile.apply_all();
+    assert_eq!(read(&root, "/proc/sys/vm/laptop_mode"), "5");
+    assert_eq!(read(&root, "/proc/sys/kernel/nmi_watchdog"), "0");
+
+    // Unsetting a value restores it when its group gets applied again
+    profile.kernel_settings.laptop_mode = None;
+    profile.apply_all();
+    assert_eq!(read(&root, "/proc/sys/vm/laptop_mode"), "0");
+    assert_eq!(read(&root, "/proc/sys/kernel/nmi_watchdog"), "0");
+
+    power_daemon::sysfs::snapshot::restore_all();
+    assert_eq!(read(&root, "/proc/sys/kernel/nmi_watchdog"), "1");
+    assert_eq!(
+        read(&root, "/sys/module/pcie_aspm/parameters/policy"),
+        "default"
+    );
+    assert_eq!(
+        read(
+            &root,
+            "/sys/devices/system/cpu/cpu5/cpufreq/energy_performance_preference"
+        ),
+        "balance_performance"
+    );
+}
+
+#[test]
+fn rolls_back_failed_group() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    // A directory in place of the node makes writing to it fail
+    let laptop_mode = root.join("proc/sys/vm/laptop_mode");
+    std::fs::remove_file(&laptop_mode).unwrap();
+    std::fs::create_dir(&laptop_mode).unwrap();
+
+    let settings = KernelSettings {
+        disable_nmi_watchdog: Some(true),
+        vm_writeback: Some(15),
+        laptop_mode: Some(5),
+    };
+    let report = settings.apply();
+
+    assert!(report.has_failures());
+    assert!(report.rolled_back);
+    assert_eq!(read(&root, "/proc/sys/kernel/nmi_watchdog"), "1");
+    assert_eq!(read(&root, "/proc/sys/vm/dirty_writeback_centisecs"), "500");
+
+    // A missing node only skips that setting
+    std::fs::remove_dir(&laptop_mode).unwrap();
+    let report = settings.apply();
+
+    assert!(!report.has_failures() && report.has_unsupported());
+    assert!(!report.rolled_back);
+    assert_eq!(read(&root, "/proc/sys/kernel/nmi_watchdog"), "0");
+    assert_eq!(
+        read(&root, "/proc/sys/vm/dirty_writeback_centisecs"),
+        "1500"
+    );
+}
+
+#[test]
+fn applies_same_profile_again() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    let info = SystemInfo::obtain();
+    let mut profile =
+        profiles_generator::create_default("Powersave", DefaultProfileType::Powersave, &info);
+    clear_host_commands(&mut profile);
+
+    // Without EPP the preference is set through the EPB, which the driver
+    // reads back as a number
+    for cpu in 0..8 {
+        std::fs::remove_file(root.join(format!(
+            "sys/devices/system/cpu/cpu{cpu}/cpufreq/energy_performance_preference"
+        )))
+        .unwrap();
+    }
+
+    let report = profile.apply_all();
+    assert!(!report.has_failures());
+    assert_eq!(report.rolled_back_groups(), vec![]);
+
+    for cpu in [0, 6] {
+        let epb = format!("/sys/devices/system/cpu/cpu{cpu}/power/energy_perf_bias");
+        assert_eq!(read(&root, &epb), "balance-power");
+        std::fs::write(root.join(epb.trim_start_matches('/')), "8").unwrap();
+    }
+
+    let report = profile.apply_all();
+    assert!(!report.has_failures());
+    assert_eq!(report.rolled_back_groups(), vec![]);
+}
+
+#[test]
+fn detects_drift() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    let info = SystemInfo::obtain();
+    let mut profile =
+        profiles_generator::create_default("Powersave", DefaultProfileType::Powersave, &info);
+    clear_host_commands(&mut profile);
+
+    profile.apply_all();
+    assert_eq!(profile.detect_drift(), vec![]);
+
+    let epp = "/sys/devices/system/cpu/cpu5/cpufreq/energy_performance_preference";
+    let expected = read(&root, epp);
+    std::fs::write(root.join(epp.trim_start_matches('/')), "performance").unwrap();
+
+    let drift = profile.detect_drift();
+    assert_eq!(drift.len(), 1);
+    assert_eq!(drift[0].group, SettingsGroup::CPU);
+    assert_eq!(drift[0].expected, expected);
+    assert_eq!(drift[0].actual, "performance");
+
+    profile.apply_settings_group(SettingsGroup::CPU);
+    assert_eq!(read(&root, epp), expected);
+    assert_eq!(profile.detect_drift(), vec![]);
+}
+
+#[test]
+fn ignores_normalized_values_when_detecting_drift() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    let info = SystemInfo::obtain();
+    let mut profile =
+        profiles_generator::create_default("Powersave", DefaultProfileType::Powersave, &info);
+    clear_host_commands(&mut profile);
+
+    for cpu in 0..8 {
+        std::fs::remove_file(root.join(format!(
+            "sys/devices/system/cpu/cpu{cpu}/cpufreq/energy_performance_preference"
+        )))
+        .unwrap();
+    }
+
+    profile.apply_all();
+    assert_eq!(profile.detect_drift(), vec![]);
+
+    // The driver reads back "balance-power" as its number
+    let epb = "/sys/devices/system/cpu/cpu0/power/energy_perf_bias";
+    std::fs::write(root.join(epb.trim_start_matches('/')), "8").unwrap();
+    assert_eq!(profile.detect_drift(), vec![]);
+
+    std::fs::write(root.join(epb.trim_start_matches('/')), "0").unwrap();
+    let drift = profile.detect_drift();
+    assert_eq!(drift.len(), 1);
+    assert_eq!(drift[0].actual, "0");
+}
+
+#[test]
+fn validates_profile() {
+    let _guard = use_fixture(FIXTURE);
+
+    let info = SystemInfo::obtain();
+    let mut profile =
+        profiles_generator::create_default("Balanced", DefaultProfileType::Balanced, &info);
+    assert!(!profile.validate(&info).iter().any(|d| d.is_error()));
+
+    profile.cpu_settings.governor = Some("ondemand".to_string());
+    profile.cpu_settings.max_freq = Some(9000);
+    profile.aspm_settings.mode = Some("bogus".to_string());
+    profile.firmware_settings.platform_profile = Some("quiet".to_string());
+    profile.cpu_core_settings.cores = Some(vec![CoreSetting {
+        cpu_id: 12,
+        online: Some(false),
+        ..Default::default()
+    }]);
+    profile.battery_settings.charge_start_threshold = Some(80);
+    profile.battery_settings.charge_end_threshold = Some(60);
+
+    let diagnostics = profile.validate(&info);
+    assert!(diagnostics.contains(&ProfileDiagnostic::UnsupportedValue {
+        group: SettingsGroup::CPU,
+        setting: "governor".to_string(),
+        value: "ondemand".to_string(),
+        supported: vec!["performance".to_string(), "powersave".to_string()],
+    }));
+    assert!(diagnostics.contains(&ProfileDiagnostic::OutOfRange {
+        group: SettingsGroup::CPU,
+        setting: "max_freq".to_string(),
+        value: 9000,
+        min: info.cpu_info.total_min_frequency,
+        max: info.cpu_info.total_max_frequency,
+    }));
+    assert!(diagnostics.contains(&ProfileDiagnostic::UnknownCore {
+        cpu_id: 12,
+        cpu_count: 8,
+    }));
+    assert!(diagnostics.contains(&ProfileDiagnostic::InvalidValue {
+        group: SettingsGroup::Battery,
+        setting: "charge thresholds".to_string(),
+        reason: "start threshold 80% must be below end threshold 60%".to_string(),
+    }));
+    assert_eq!(diagnostics.iter().filter(|d| d.is_error()).count(), 5);
+}
+
+#[test]
+fn imports_exported_profile() {
+    let _guard = use_fixture(FIXTURE);
+    let (mut instance, _, profiles_path) = instance_for_fixture(FIXTURE);
+
+    let mut profile = ProfileBundle::parse(&instance.export_profile("Balanced").unwrap())
+        .unwrap()
+        .profile;
+    profile.cpu_settings.max_freq = Some(2000);
+    let bundle = ProfileBundle::new(profile).to_toml();
+
+    let result = instance
+        .import_profile(&bundle, NameCollision::Rename, true)
+        .unwrap();
+    assert_eq!(result.profile_name, "Balanced #1");
+    assert!(result
+        .hardware_specific_fields
+        .contains(&"cpu_settings.max_freq".to_string()));
+    assert!(profiles_path.join("Balanced #1.toml").exists());
+
+    let imported = ProfileBundle::parse(&instance.export_profile("Balanced #1").unwrap())
+        .unwrap()
+        .profile;
+    assert_eq!(imported.cpu_settings.max_freq, None);
+
+    assert!(instance
+        .import_profile(&bundle, NameCollision::Fail, true)
+        .is_err());
+}
+
+#[test]
+fn diffs_profiles() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+
+    let info = SystemInfo::obtain();
+    let mut powersave =
+        profiles_generator::create_default("Powersave", DefaultProfileType::Powersave, &info);
+    let performance =
+        profiles_generator::create_default("Performance", DefaultProfileType::Performance, &info);
+
+    assert_eq!(powersave.diff(&powersave), vec![]);
+    assert!(powersave.diff(&performance).contains(&SettingChange {
+        group: SettingsGroup::Kernel,
+        field: "vm_writeback".to_string(),
+        from: Some("45".to_string()),
+        to: Some("15".to_string()),
+    }));
+
+    clear_host_commands(&mut powersave);
+    powersave.apply_all();
+    assert_eq!(powersave.diff_live(&SystemInfo::obtain()), vec![]);
+
+    let governor = "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor";
+    std::fs::write(root.join(governor.trim_start_matches('/')), "performance").unwrap();
+    assert!(powersave
+        .diff_live(&SystemInfo::obtain())
+        .iter()
+        .any(|change| change.field == "governor" && change.to.as_deref() == Some("performance")));
+}
+
+#[test]
+fn reloads_changed_config_files() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+    let (config_path, profiles_path) = create_config_dir(FIXTURE);
+
+    // Keep the profiles from running commands on the host. The ASPM policy
+    // node of the copy loses its list of supported modes once written to,
+    // which would fail the validation of the other profiles
+    let edit_profile = |name: &str, edit: &dyn Fn(&mut Profile)| {
+        let path = profiles_path.join(format!("{name}.toml"));
+        let mut profile: Profile =
+            toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        clear_host_commands(&mut profile);
+        profile.aspm_settings = ASPMSettings::default();
+        edit(&mut profile);
+        std::fs::write(&path, toml::to_string_pretty(&profile).unwrap()).unwrap();
+    };
+    let config = power_daemon::parse_config(&config_path);
+    for name in config.profiles.iter() {
+        edit_profile(name, &|_| {});
+    }
+
+    let mut instance = Instance::new(config.clone(), &config_path, &profiles_path);
+    instance.update_full();
+    let active = instance.get_active_profile_name();
+
+    // Nothing gets applied again if the files did not change, e.g. when the
+    // daemon wrote them itself
+    let laptop_mode = root.join("proc/sys/vm/laptop_mode");
+    std::fs::write(&laptop_mode, "0").unwrap();
+    instance.reload_from_disk();
+    assert_eq!(read(&root, "/proc/sys/vm/laptop_mode"), "0");
+
+    std::fs::write(&config_path, "ac_profile = ").unwrap();
+    instance.reload_from_disk();
+    assert_eq!(read(&root, "/proc/sys/vm/laptop_mode"), "0");
+
+    power_daemon::serialize_config(&config, &config_path);
+    edit_profile(&active, &|profile| {
+        profile.kernel_settings.laptop_mode = Some(1)
+    });
+    instance.reload_from_disk();
+    assert_eq!(read(&root, "/proc/sys/vm/laptop_mode"), "1");
+}
+
+#[test]
+fn replaces_broken_files() {
+    let _guard = use_fixture(FIXTURE);
+    let (config_path, profiles_path) = create_config_dir(FIXTURE);
+
+    let broken_toml = profiles_path.join("Powersave.toml");
+    std::fs::write(&broken_toml, "kernel_settings = [").unwrap();
+    let broken_profile = profiles_path.join("Balanced.toml");
+    std::fs::write(&broken_profile, "base_profile = \"Unknown\"").unwrap();
+    std::fs::remove_file(profiles_path.join("Performance.toml")).unwrap();
+
+    let config = power_daemon::parse_config(&config_path);
+    let instance = Instance::new(config, &config_path, &profiles_path);
+
+    let load_errors: Vec<_> = instance
+        .get_load_errors()
+        .into_iter()
+        .filter(|e| e.path.starts_with(&profiles_path))
+        .collect();
+    assert_eq!(load_errors.len(), 3);
+
+    for path in [&broken_toml, &broken_profile] {
+        let load_error = load_errors.iter().find(|e| e.path == *path).unwrap();
+        let quarantined_to = load_error.quarantined_to.as_ref().unwrap();
+        assert!(quarantined_to.exists());
+
+        // The replacement is the default profile of the same name
+        let replacement: Profile = toml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert!(replacement.base_profile.is_some());
+    }
+    assert!(profiles_path.join("Performance.toml").exists());
+
+    std::fs::write(&config_path, "ac_profile = ").unwrap();
+    let config = power_daemon::parse_config(&config_path);
+    assert_eq!(config, power_daemon::Config::create_default());
+    assert!(power_daemon::load_errors::load_errors()
+        .iter()
+        .any(|e| e.path == config_path && e.quarantined_to.is_some()));
+}
+
+#[test]
+fn migrates_unversioned_files() {
+    let _guard = use_fixture(FIXTURE);
+    let (config_path, profiles_path) = create_config_dir(FIXTURE);
+
+    let unversion = |path: &std::path::Path, remove: &[&str]| {
+        let mut table: toml::Table = std::fs::read_to_string(path).unwrap().parse().unwrap();
+        table.remove("schema_version");
+        for key in remove {
+            table.remove(*key);
+        }
+        std::fs::write(path, toml::to_string(&table).unwrap()).unwrap();
+    };
+    let powersave_path = profiles_path.join("Powersave.toml");
+    unversion(&config_path, &["drift_check_interval"]);
+    unversion(&powersave_path, &["kernel_settings"]);
+    let unversioned = std::fs::read_to_string(&powersave_path).unwrap();
+
+    let migrations = migration::migrate_files(&config_path, &profiles_path, false);
+    assert_eq!(
+        std::fs::read_to_string(&powersave_path).unwrap(),
+        unversioned
+    );
+
+    let powersave = migrations
+        .iter()
+        .find(|m| m.path == powersave_path)
+        .unwrap()
+        .result
+        .as_ref()
+        .unwrap();
+    assert_eq!((powersave.from_version, powersave.to_version), (0, 2));
+    assert!(powersave
+        .changes
+        .contains(&"kernel_settings.vm_writeback: unset -> 45".to_string()));
+    assert!(migrations.iter().all(|m| m.result.is_ok()));
+
+    migration::migrate_files(&config_path, &profiles_path, true);
+    let migrated: Profile =
+        toml::from_str(&std::fs::read_to_string(&powersave_path).unwrap()).unwrap();
+    assert_eq!(migrated.schema_version, migration::PROFILE_SCHEMA_VERSION);
+    assert_eq!(migrated.kernel_settings.vm_writeback, Some(45));
+
+    assert!(Profile::parse("schema_version = 99", "Future").is_err());
+}
+
+#[test]
+fn ends_temporary_override() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+    let (mut instance, _, _) = instance_for_fixture(FIXTURE);
+    instance.update_full();
+    let picked = instance.get_active_profile_name();
+
+    let duration = Duration::from_millis(200);
+    instance.set_profile_override_until("Balanced".to_string(), OverrideExpiry::Duration(duration));
+    assert_eq!(instance.get_active_profile_name(), "Balanced");
+    let remaining = instance.profile_override_remaining().unwrap();
+    assert!(remaining > Duration::ZERO && remaining <= duration);
+
+    std::thread::sleep(duration);
+    instance.reevaluate_profile();
+    assert_eq!(instance.get_profile_override(), None);
+    assert_eq!(instance.get_active_profile_name(), picked);
+
+    instance.set_profile_override_until("Balanced".to_string(), OverrideExpiry::PowerSourceChange);
+    instance.reevaluate_profile();
+    assert_eq!(instance.get_active_profile_name(), "Balanced");
+
+    std::fs::write(root.join("sys/class/power_supply/AC/online"), "1").unwrap();
+    instance.reevaluate_profile();
+    assert_eq!(instance.get_profile_override(), None);
+    assert_ne!(instance.get_active_profile_name(), "Balanced");
+}
+
+#[test]
+fn picks_profile_again_after_resume() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+    let (mut instance, _, _) = instance_for_fixture(FIXTURE);
+    instance.update_full();
+    let on_battery = instance.get_active_profile_name();
+
+    // Plugged in while suspended
+    std::fs::write(root.join("sys/class/power_supply/AC/online"), "1").unwrap();
+    instance.update_after_resume();
+    assert_ne!(instance.get_active_profile_name(), on_battery);
+}
+
+#[test]
+fn applies_whole_profile_picked_on_reduced_update() {
+    let (_guard, root) = use_fixture_copy(FIXTURE);
+    let (mut instance, _, _) = instance_for_fixture(FIXTURE);
+    instance.update_full();
+    let on_battery = instance.get_active_profile_name();
+
+    std::fs::write(root.join("sys/class/power_supply/AC/online"), "1").unwrap();
+    instance.update_reduced(ReducedUpdate::Kernel);
+    assert_ne!(instance.get_active_profile_name(), on_battery);
+
+    let report = instance.get_apply_report();
+    assert_eq!(report.profile_name, instance.get_active_profile_name());
+    assert!(report
+        .groups
+        .iter()
+        .any(|group| group.group == SettingsGroup::CPU));
+}
+
+#[cfg(feature = "communication")]
+#[test]
+fn converts_to_native_dbus_types() {
+    use power_daemon::communication::native::{from_dict, from_variant, to_dict, to_variant};
+
+    let _guard = use_fixture(FIXTURE);
+    let info = SystemInfo::obtain();
+
+    let mut profile =
+        profiles_generator::create_default("Balanced", DefaultProfileType::Balanced, &info);
+    // Unset settings are left out of the dict
+    profile.kernel_settings.vm_writeback = None;
+    let dict = to_dict(&profile);
+    assert!(dict.contains_key("cpu_settings"));
+    assert_eq!(from_dict::<Profile>(dict).unwrap(), profile);
+
+    let expiry = OverrideExpiry::Duration(Duration::from_secs(90));
+    assert_eq!(
+        from_variant::<OverrideExpiry>(&to_variant(&expiry)).unwrap(),
+        expiry
+    );
+    assert_eq!(
+        from_variant::<OverrideExpiry>(&to_variant(&OverrideExpiry::Reboot)).unwrap(),
+        OverrideExpiry::Reboot
+    );
+}
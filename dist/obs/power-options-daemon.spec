@@ -16,7 +16,6 @@ BuildRequires:   llvm-libs
 BuildRequires:   llvm-devel
 %endif
 
-Requires:        acpid
 Requires:        pciutils
 Requires:        usbutils
 Requires:        yad
@@ -46,7 +45,6 @@ cargo build --release --package power-daemon-mgr
 if [ "$1" -eq 1 ]; then
     power-daemon-mgr setup
     systemctl daemon-reload
-    systemctl restart acpid.service
     systemctl enable --now power-options.service
 fi
 
@@ -56,8 +54,8 @@ systemctl daemon-reload
 %files
 %{_bindir}/power-daemon-mgr
 /usr/lib/udev/rules.d/85-power-daemon.rules
-/etc/acpi/events/power-options
 /usr/share/dbus-1/system.d/power-daemon.conf
+/usr/share/polkit-1/actions/io.github.thealexdev23.power_daemon.policy
 /usr/lib/systemd/system/power-options.service
 
 %changelog
@@ -1,5 +1,4 @@
 sudo power-daemon-mgr setup
 
-sudo systemctl restart acpid
 sudo systemctl daemon-reload
 sudo systemctl enable --now power-options
\ No newline at end of file
@@ -43,6 +43,7 @@ remove_files_and_dirs() {
     rm -f /etc/acpi/events/power-options
     rm -f /usr/lib/udev/rules.d/85-power-daemon.rules
     rm -f /usr/share/dbus-1/system.d/power-daemon.conf
+    rm -f /usr/share/polkit-1/actions/io.github.thealexdev23.power_daemon.policy
 }
 
 # Function to disable and remove systemd service